@@ -1,15 +1,38 @@
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
 use actix_web::{web, HttpRequest, HttpResponse, Result};
+use futures_util::stream;
 use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::fmt;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
 
 use crate::auth::AuthService;
-use crate::executor::shell::ShellCommand;
+use crate::calls::{CallEvent, CallStatus};
+use crate::executor::call_forwarding::{self, ForwardingReason, ForwardingState};
+use crate::executor::call_log::{self, CallLogEntry, CallLogFilter};
+use crate::executor::Backend;
+use crate::executor::telephony::CallState;
+use crate::sampler::StatusSampler;
+use crate::ussd::UssdService;
+use crate::executor::shell::{ExecError, ShellCommand, DEFAULT_SUB_ID};
+use super::sim::SimSelector;
 
 #[derive(Deserialize, Serialize)]
 pub struct CallForwardRequest {
     enable: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     number: Option<String>,
+    /// Unconditional when left out
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reason: Option<ForwardingReason>,
+    /// No-reply delay in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    timer: Option<u8>,
+    #[serde(flatten)]
+    target: SimSelector,
 }
 
 #[derive(Serialize)]
@@ -17,6 +40,42 @@ pub struct CallForwardResponse {
     success: bool,
     enabled: bool,
     message: String,
+    /// State the network reported after the change
+    #[serde(skip_serializing_if = "Option::is_none")]
+    forwarding: Option<ForwardingState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+impl CallForwardResponse {
+    fn error(enabled: bool, message: String, error_code: &'static str) -> Self {
+        Self { success: false, enabled, message, forwarding: None, error_code: Some(error_code) }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct CallForwardQuery {
+    /// Query only this reason instead of all four
+    reason: Option<ForwardingReason>,
+    // Not a flattened SimSelector: flatten breaks numbers in query strings
+    sim: Option<usize>,
+    subscription_id: Option<i32>,
+}
+
+#[derive(Serialize)]
+pub struct CallForwardStatusResponse {
+    success: bool,
+    forwarding: Vec<ForwardingState>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+impl CallForwardStatusResponse {
+    fn error(message: String, error_code: &'static str) -> Self {
+        Self { success: false, forwarding: Vec::new(), message: Some(message), error_code: Some(error_code) }
+    }
 }
 
 #[derive(Deserialize, Serialize)]
@@ -28,26 +87,273 @@ pub struct CallDialRequest {
 pub struct CallDialResponse {
     success: bool,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+#[derive(Serialize)]
+pub struct CallControlResponse {
+    success: bool,
+    message: String,
+    /// The call state after the action, or the state that prevented it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    call: Option<CallStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+
+#[derive(Deserialize)]
+pub struct CallLogQuery {
+    /// Only calls that started after this Unix ms
+    since: Option<i64>,
+    /// Page size, 1 to 100 (default 20)
+    limit: Option<usize>,
+    /// Calls to skip, newest first
+    #[serde(default)]
+    offset: usize,
 }
 
-/// POST /call/forward - Configure call forwarding
+#[derive(Serialize)]
+pub struct CallLogResponse {
+    success: bool,
+    calls: Vec<CallLogEntry>,
+    /// Calls matching the filters, across all pages
+    total: usize,
+    has_more: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+impl CallLogResponse {
+    fn error(message: String, error_code: &'static str) -> Self {
+        Self { success: false, calls: Vec::new(), total: 0, has_more: false, message: Some(message), error_code: Some(error_code) }
+    }
+}
+
+/// How long to wait for the call state to reflect an answer or hang up
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(3);
+const SETTLE_POLL: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CallAction {
+    Answer,
+    Reject,
+    Hangup,
+}
+
+impl CallAction {
+    fn command(self) -> ShellCommand {
+        match self {
+            CallAction::Answer => ShellCommand::AnswerCall,
+            CallAction::Reject | CallAction::Hangup => ShellCommand::EndCall,
+        }
+    }
+
+    /// States the action applies to
+    fn allowed(self, state: CallState) -> bool {
+        match self {
+            CallAction::Answer | CallAction::Reject => state == CallState::Ringing,
+            CallAction::Hangup => state != CallState::Idle,
+        }
+    }
+
+    fn done(self) -> &'static str {
+        match self {
+            CallAction::Answer => "Call answered",
+            CallAction::Reject => "Call rejected",
+            CallAction::Hangup => "Call ended",
+        }
+    }
+}
+
+/// A call action that could not be carried out
+#[derive(Debug)]
+pub enum CallControlError {
+    Exec(ExecError),
+    /// No call the action applies to
+    InvalidState(CallStatus),
+    /// The key event was sent but the call state did not change
+    Unchanged(CallStatus),
+}
+
+impl CallControlError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            CallControlError::Exec(e) => super::error_status(e),
+            CallControlError::InvalidState(_) => StatusCode::CONFLICT,
+            CallControlError::Unchanged(_) => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            CallControlError::Exec(e) => e.code(),
+            CallControlError::InvalidState(_) => "invalid_state",
+            CallControlError::Unchanged(_) => "call_unchanged",
+        }
+    }
+
+    fn call(self) -> Option<CallStatus> {
+        match self {
+            CallControlError::Exec(_) => None,
+            CallControlError::InvalidState(call) | CallControlError::Unchanged(call) => Some(call),
+        }
+    }
+}
+
+impl fmt::Display for CallControlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallControlError::Exec(e) => write!(f, "Failed to control call: {}", e),
+            CallControlError::InvalidState(call) => write!(f, "No call to act on, call state is {:?}", call.state),
+            CallControlError::Unchanged(call) => write!(f, "Call state is still {:?}", call.state),
+        }
+    }
+}
+
+/// Check the call state, send the key event and wait until the state changes
+pub async fn control_call(
+    action: CallAction,
+    backend: &dyn Backend,
+    sampler: &StatusSampler,
+) -> Result<CallStatus, CallControlError> {
+    let before = sampler.current_call().await.map_err(CallControlError::Exec)?;
+    if !action.allowed(before.state) {
+        return Err(CallControlError::InvalidState(before));
+    }
+    backend.execute(&action.command()).await.map_err(CallControlError::Exec)?;
+
+    let deadline = tokio::time::Instant::now() + SETTLE_TIMEOUT;
+    loop {
+        let call = sampler.current_call().await.map_err(CallControlError::Exec)?;
+        if call.state != before.state {
+            return Ok(call);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(CallControlError::Unchanged(call));
+        }
+        tokio::time::sleep(SETTLE_POLL).await;
+    }
+}
+
+async fn call_control_response(
+    req: HttpRequest,
+    action: CallAction,
+    auth: &AuthService,
+    backend: &dyn Backend,
+    sampler: &StatusSampler,
+) -> Result<HttpResponse> {
+    // Verify authentication (no body)
+    auth.verify_request(&req, &[])?;
+
+    match control_call(action, backend, sampler).await {
+        Ok(call) => Ok(HttpResponse::Ok().json(CallControlResponse {
+            success: true,
+            message: action.done().to_string(),
+            call: Some(call),
+            error_code: None,
+        })),
+        Err(e) => Ok(HttpResponse::build(e.status()).json(CallControlResponse {
+            success: false,
+            message: e.to_string(),
+            error_code: Some(e.code()),
+            call: e.call(),
+        })),
+    }
+}
+
+/// POST /call/answer - Pick up the ringing call
+pub async fn answer_call(
+    req: HttpRequest,
+    auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+    sampler: web::Data<Arc<StatusSampler>>,
+) -> Result<HttpResponse> {
+    call_control_response(req, CallAction::Answer, &auth, backend.get_ref().as_ref(), &sampler).await
+}
+
+/// POST /call/reject - Decline the ringing call
+pub async fn reject_call(
+    req: HttpRequest,
+    auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+    sampler: web::Data<Arc<StatusSampler>>,
+) -> Result<HttpResponse> {
+    call_control_response(req, CallAction::Reject, &auth, backend.get_ref().as_ref(), &sampler).await
+}
+
+/// POST /call/hangup - End the active call, or decline a ringing one
+pub async fn hangup_call(
+    req: HttpRequest,
+    auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+    sampler: web::Data<Arc<StatusSampler>>,
+) -> Result<HttpResponse> {
+    call_control_response(req, CallAction::Hangup, &auth, backend.get_ref().as_ref(), &sampler).await
+}
+
+/// GET /calls - Call history, newest first
+pub async fn list_calls(
+    req: HttpRequest,
+    query: web::Query<CallLogQuery>,
+    auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+    sampler: web::Data<Arc<StatusSampler>>,
+) -> Result<HttpResponse> {
+    // Verify authentication (no body for GET)
+    auth.verify_request(&req, &[])?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Ok(HttpResponse::BadRequest().json(CallLogResponse::error(
+            format!("limit must be between 1 and {}", MAX_LIMIT),
+            "invalid_request",
+        )));
+    }
+
+    let filter = CallLogFilter { since: query.since };
+    match backend.execute(&ShellCommand::ContentQuery(filter.query())).await {
+        Ok(output) => {
+            // The provider already filtered; this keeps backends that ignore the selection honest
+            let calls: Vec<CallLogEntry> =
+                call_log::parse_calls(&output).into_iter().filter(|c| filter.matches(c)).collect();
+            let total = calls.len();
+            let mut page: Vec<CallLogEntry> = calls.into_iter().skip(query.offset).take(limit).collect();
+            // Slots come from the sampled SIM list; before the first sample they stay null
+            if let Some(subscriptions) = sampler.snapshot().subscriptions.value {
+                call_log::assign_slots(&mut page, &subscriptions);
+            }
+            Ok(HttpResponse::Ok().json(CallLogResponse {
+                success: true,
+                has_more: query.offset + page.len() < total,
+                calls: page,
+                total,
+                message: None,
+                error_code: None,
+            }))
+        }
+        Err(e) => Ok(HttpResponse::build(super::error_status(&e))
+            .json(CallLogResponse::error(format!("Failed to read call log: {}", e), e.code()))),
+    }
+}
+
+/// POST /call/forward - Configure call forwarding and confirm it with the network
 pub async fn set_call_forwarding(
     req: HttpRequest,
     body: web::Json<CallForwardRequest>,
     auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+    sampler: web::Data<Arc<StatusSampler>>,
+    ussd: web::Data<Arc<UssdService>>,
 ) -> Result<HttpResponse> {
     // Extract the inner value for HMAC verification and later use
     let forward_request = body.into_inner();
 
-    // Validate request: if enabling, number must be provided
-    if forward_request.enable && forward_request.number.is_none() {
-        return Ok(HttpResponse::BadRequest().json(CallForwardResponse {
-            success: false,
-            enabled: false,
-            message: "Number required when enabling call forwarding".to_string(),
-        }));
-    }
-
     // Serialize body for HMAC verification
     let body_bytes = serde_json::to_vec(&forward_request)
         .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid JSON: {}", e)))?;
@@ -55,53 +361,150 @@ pub async fn set_call_forwarding(
     // Verify authentication
     auth.verify_request(&req, &body_bytes)?;
 
-    // Validate phone number format if enabling
-    if forward_request.enable {
-        if let Some(ref number) = forward_request.number {
-            if !is_valid_phone_number(number) {
-                return Ok(HttpResponse::BadRequest().json(CallForwardResponse {
-                    success: false,
-                    enabled: false,
-                    message: "Invalid phone number format".to_string(),
-                }));
-            }
+    let reason = forward_request.reason.unwrap_or_default();
+    let invalid = |message: &str| {
+        Ok(HttpResponse::BadRequest().json(CallForwardResponse::error(
+            !forward_request.enable,
+            message.to_string(),
+            "invalid_request",
+        )))
+    };
+
+    // Validate request: if enabling, a well-formed number must be provided
+    let number = match (forward_request.enable, forward_request.number.as_deref()) {
+        (true, None) => return invalid("Number required when enabling call forwarding"),
+        (true, Some(number)) if !is_valid_phone_number(number) => return invalid("Invalid phone number format"),
+        (_, number) => number.map(str::to_string),
+    };
+    if let Some(timer) = forward_request.timer {
+        if reason != ForwardingReason::NoReply || !forward_request.enable {
+            return invalid("timer only applies when enabling no_reply forwarding");
+        }
+        if !call_forwarding::is_valid_timer(timer) {
+            return invalid("timer must be 5 to 30 seconds in steps of 5");
         }
     }
 
+    let sub_id = match forward_request.target.resolve(backend.get_ref().as_ref()).await {
+        Ok(selected) => selected.map_or(DEFAULT_SUB_ID, |sim| sim.subscription.id),
+        Err(e) => {
+            return Ok(HttpResponse::build(e.status())
+                .json(CallForwardResponse::error(!forward_request.enable, e.to_string(), e.code())));
+        }
+    };
+
     // Execute appropriate command
-    let command = if forward_request.enable {
-        // Safe: we validated number exists and format above
-        ShellCommand::EnableCallForwarding(forward_request.number.unwrap())
-    } else {
-        ShellCommand::DisableCallForwarding
+    let command = match number {
+        Some(number) if forward_request.enable => {
+            ShellCommand::EnableCallForwarding { reason, number, timer: forward_request.timer, sub_id }
+        }
+        _ => ShellCommand::DisableCallForwarding { reason, sub_id },
     };
 
-    match command.execute() {
-        Ok(_) => {
-            let response = CallForwardResponse {
+    let result = match ussd.service_code(command).await {
+        // The network answers every activation, the phone words the outcome
+        Ok(answer) if !call_forwarding::is_accepted(&answer) => {
+            return Ok(HttpResponse::BadGateway().json(CallForwardResponse::error(
+                !forward_request.enable,
+                format!("The network refused the change: {}", answer.trim()),
+                "forwarding_rejected",
+            )));
+        }
+        // Read the state back rather than trusting the acknowledgement
+        Ok(_) => ussd.query_forwarding(reason, sub_id).await,
+        Err(e) => Err(e),
+    };
+
+    // The network just told, no need to interrogate every reason again for /status
+    if let Ok(ref state) = result {
+        sampler.record_forwarding(sub_id, state.clone());
+    }
+
+    match result {
+        Ok(state) if state.active == forward_request.enable => {
+            let message = format!(
+                "{} call forwarding {}",
+                reason_name(reason),
+                if forward_request.enable { "enabled" } else { "disabled" }
+            );
+            Ok(HttpResponse::Ok().json(CallForwardResponse {
                 success: true,
-                enabled: forward_request.enable,
-                message: format!(
-                    "Call forwarding {}",
-                    if forward_request.enable { "enabled" } else { "disabled" }
-                ),
-            };
-            Ok(HttpResponse::Ok().json(response))
+                enabled: state.active,
+                message,
+                forwarding: Some(state),
+                error_code: None,
+            }))
         }
+        Ok(state) => Ok(HttpResponse::BadGateway().json(CallForwardResponse {
+            success: false,
+            enabled: state.active,
+            message: format!(
+                "The network accepted the change but still reports {} call forwarding as {}",
+                reason_name(reason),
+                if state.active { "enabled" } else { "disabled" }
+            ),
+            forwarding: Some(state),
+            error_code: Some("forwarding_unconfirmed"),
+        })),
+        Err(e) => Ok(HttpResponse::build(super::ussd::error_status(&e)).json(CallForwardResponse::error(
+            !forward_request.enable, // Assume it stayed in previous state
+            format!("Failed to set call forwarding: {}", e),
+            e.code(),
+        ))),
+    }
+}
+
+/// GET /call/forward - Ask the network which forwarding is set up
+pub async fn get_call_forwarding(
+    req: HttpRequest,
+    query: web::Query<CallForwardQuery>,
+    auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+    ussd: web::Data<Arc<UssdService>>,
+) -> Result<HttpResponse> {
+    // Verify authentication (no body for GET)
+    auth.verify_request(&req, &[])?;
+
+    let selector = SimSelector { sim: query.sim, subscription_id: query.subscription_id };
+    let sub_id = match selector.resolve(backend.get_ref().as_ref()).await {
+        Ok(selected) => selected.map_or(DEFAULT_SUB_ID, |sim| sim.subscription.id),
         Err(e) => {
-            let response = CallForwardResponse {
-                success: false,
-                enabled: !forward_request.enable, // Assume it stayed in previous state
-                message: format!("Failed to set call forwarding: {}", e),
-            };
-            Ok(HttpResponse::InternalServerError().json(response))
+            return Ok(HttpResponse::build(e.status()).json(CallForwardStatusResponse::error(e.to_string(), e.code())));
+        }
+    };
+
+    // One network round trip per reason, a few seconds each
+    let reasons = match query.reason {
+        Some(reason) => vec![reason],
+        None => ForwardingReason::ALL.to_vec(),
+    };
+    let mut forwarding = Vec::new();
+    for reason in reasons {
+        match ussd.query_forwarding(reason, sub_id).await {
+            Ok(state) => forwarding.push(state),
+            Err(e) => {
+                return Ok(HttpResponse::build(super::ussd::error_status(&e)).json(CallForwardStatusResponse::error(
+                    format!("Failed to query {} call forwarding: {}", reason_name(reason), e),
+                    e.code(),
+                )));
+            }
         }
     }
+    Ok(HttpResponse::Ok().json(CallForwardStatusResponse { success: true, forwarding, message: None, error_code: None }))
+}
+
+fn reason_name(reason: ForwardingReason) -> &'static str {
+    match reason {
+        ForwardingReason::Unconditional => "Unconditional",
+        ForwardingReason::Busy => "Busy",
+        ForwardingReason::NoReply => "No-reply",
+        ForwardingReason::Unreachable => "Unreachable",
+    }
 }
 
 /// Validate phone number format
 /// Accepts: +1234567890, 1234567890, or international format
-fn is_valid_phone_number(number: &str) -> bool {
+pub(crate) fn is_valid_phone_number(number: &str) -> bool {
     // Must start with + or digit
     if number.is_empty() {
         return false;
@@ -130,35 +533,12 @@ fn is_valid_phone_number(number: &str) -> bool {
     (7..=15).contains(&digit_count)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_valid_phone_numbers() {
-        assert!(is_valid_phone_number("+1234567890"));
-        assert!(is_valid_phone_number("1234567890"));
-        assert!(is_valid_phone_number("+919876543210"));
-        assert!(is_valid_phone_number("7654321"));
-    }
-
-    #[test]
-    fn test_invalid_phone_numbers() {
-        assert!(!is_valid_phone_number(""));
-        assert!(!is_valid_phone_number("+"));
-        assert!(!is_valid_phone_number("abc123"));
-        assert!(!is_valid_phone_number("+123abc"));
-        assert!(!is_valid_phone_number("123-456-7890")); // No dashes
-        assert!(!is_valid_phone_number("123")); // Too short
-        assert!(!is_valid_phone_number("12345678901234567890")); // Too long
-    }
-}
-
 /// POST /call/dial - Initiate a phone call
 pub async fn dial_call(
     req: HttpRequest,
     body: web::Json<CallDialRequest>,
     auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
 ) -> Result<HttpResponse> {
     // Extract the inner value for HMAC verification
     let dial_request = body.into_inner();
@@ -175,17 +555,19 @@ pub async fn dial_call(
         return Ok(HttpResponse::BadRequest().json(CallDialResponse {
             success: false,
             message: "Invalid phone number format".to_string(),
+            error_code: Some("invalid_request"),
         }));
     }
 
     // Execute dial command
     let command = ShellCommand::DialNumber(dial_request.number.clone());
 
-    match command.execute() {
+    match backend.execute(&command).await {
         Ok(_) => {
             let response = CallDialResponse {
                 success: true,
                 message: format!("Dialing {}", dial_request.number),
+                error_code: None,
             };
             Ok(HttpResponse::Ok().json(response))
         }
@@ -193,8 +575,118 @@ pub async fn dial_call(
             let response = CallDialResponse {
                 success: false,
                 message: format!("Failed to initiate call: {}", e),
+                error_code: Some(e.code()),
             };
-            Ok(HttpResponse::InternalServerError().json(response))
+            Ok(HttpResponse::build(super::error_status(&e)).json(response))
         }
     }
 }
+
+/// GET /call/events - Server-sent events, one `call_state` event per call
+/// state change. Missed events are not replayed; /status has the current state.
+pub async fn call_events(
+    req: HttpRequest,
+    auth: web::Data<Arc<AuthService>>,
+    sampler: web::Data<Arc<StatusSampler>>,
+) -> Result<HttpResponse> {
+    // Verify authentication (no body for GET)
+    auth.verify_request(&req, &[])?;
+
+    Ok(super::sse_response(stream::unfold(sampler.subscribe_calls(), next_call_frame)))
+}
+
+async fn next_call_frame(
+    mut receiver: broadcast::Receiver<CallEvent>,
+) -> Option<(Result<Bytes, Infallible>, broadcast::Receiver<CallEvent>)> {
+    loop {
+        tokio::select! {
+            received = receiver.recv() => match received {
+                Ok(event) => return Some((Ok(super::sse_frame(event.id, "call_state", &event)), receiver)),
+                // Skipped events are stale by now, the next one carries the current state
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            },
+            _ = tokio::time::sleep(super::SSE_KEEPALIVE) => return Some((Ok(super::sse_keepalive()), receiver)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SamplerConfig, UssdConfig};
+    use crate::executor::simulator::SimulatedBackend;
+
+    #[test]
+    fn test_valid_phone_numbers() {
+        assert!(is_valid_phone_number("+1234567890"));
+        assert!(is_valid_phone_number("1234567890"));
+        assert!(is_valid_phone_number("+919876543210"));
+        assert!(is_valid_phone_number("7654321"));
+    }
+
+    #[test]
+    fn test_invalid_phone_numbers() {
+        assert!(!is_valid_phone_number(""));
+        assert!(!is_valid_phone_number("+"));
+        assert!(!is_valid_phone_number("abc123"));
+        assert!(!is_valid_phone_number("+123abc"));
+        assert!(!is_valid_phone_number("123-456-7890")); // No dashes
+        assert!(!is_valid_phone_number("123")); // Too short
+        assert!(!is_valid_phone_number("12345678901234567890")); // Too long
+    }
+
+    #[tokio::test]
+    async fn test_control_call() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let ussd = Arc::new(UssdService::new(backend.clone(), UssdConfig::default()));
+        let sampler = StatusSampler::new(backend.clone(), ussd, SamplerConfig::default());
+
+        let idle = control_call(CallAction::Reject, backend.as_ref(), &sampler).await.unwrap_err();
+        assert_eq!(idle.code(), "invalid_state");
+
+        backend.ring("+15550100");
+        let call = control_call(CallAction::Answer, backend.as_ref(), &sampler).await.unwrap();
+        assert_eq!(call.state, CallState::Offhook);
+        assert_eq!(call.number.as_deref(), Some("+15550100"));
+        let answered = control_call(CallAction::Answer, backend.as_ref(), &sampler).await.unwrap_err();
+        assert_eq!(answered.status(), StatusCode::CONFLICT);
+
+        let call = control_call(CallAction::Hangup, backend.as_ref(), &sampler).await.unwrap();
+        assert_eq!(call.state, CallState::Idle);
+
+        backend.ring("+15550100");
+        let call = control_call(CallAction::Reject, backend.as_ref(), &sampler).await.unwrap();
+        assert_eq!(call.state, CallState::Idle);
+    }
+
+    #[tokio::test]
+    async fn test_forwarding_change_is_read_back() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let ussd = UssdService::new(backend.clone(), UssdConfig::default());
+        let reason = ForwardingReason::NoReply;
+        assert!(!ussd.query_forwarding(reason, 1).await.unwrap().active);
+
+        let enable = ShellCommand::EnableCallForwarding {
+            reason,
+            number: "+15550100".to_string(),
+            timer: Some(15),
+            sub_id: 1,
+        };
+        assert!(call_forwarding::is_accepted(&ussd.service_code(enable).await.unwrap()));
+        let state = ussd.query_forwarding(reason, 1).await.unwrap();
+        assert_eq!((state.active, state.number.as_deref(), state.timer), (true, Some("+15550100"), Some(15)));
+        // Other reasons are separate
+        assert!(!ussd.query_forwarding(ForwardingReason::Busy, 1).await.unwrap().active);
+
+        // Without a network the change is refused
+        backend.execute(&ShellCommand::EnableAirplaneMode).await.unwrap();
+        let busy = ShellCommand::EnableCallForwarding {
+            reason: ForwardingReason::Busy,
+            number: "+15550100".to_string(),
+            timer: None,
+            sub_id: 1,
+        };
+        assert!(!call_forwarding::is_accepted(&ussd.service_code(busy).await.unwrap()));
+    }
+}
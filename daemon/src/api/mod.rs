@@ -1,3 +1,54 @@
 pub mod status;
 pub mod radio;
 pub mod call;
+pub mod network;
+pub mod sim;
+pub mod sms;
+pub mod tethering;
+pub mod ussd;
+pub mod wifi;
+
+use actix_web::http::StatusCode;
+use actix_web::web::Bytes;
+use actix_web::HttpResponse;
+use futures_util::Stream;
+use serde::Serialize;
+use std::convert::Infallible;
+use std::fmt;
+use std::time::Duration;
+
+use crate::executor::shell::ExecError;
+
+/// Comment line sent on an idle event stream, so proxies and the app keep it open
+pub const SSE_KEEPALIVE: Duration = Duration::from_secs(15);
+
+/// HTTP status for a failed command. Each error kind gets its own status,
+/// alongside the `error_code` in the response body.
+pub fn error_status(error: &ExecError) -> StatusCode {
+    match error {
+        ExecError::NotFound { .. } => StatusCode::NOT_IMPLEMENTED,
+        ExecError::NonZeroExit { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+        ExecError::Timeout(_) => StatusCode::GATEWAY_TIMEOUT,
+        ExecError::PermissionDenied(_) => StatusCode::SERVICE_UNAVAILABLE,
+        ExecError::Parse(_) => StatusCode::BAD_GATEWAY,
+        ExecError::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// One server-sent event
+pub fn sse_frame(id: impl fmt::Display, event: &str, data: &impl Serialize) -> Bytes {
+    let data = serde_json::to_string(data).expect("event serializes");
+    Bytes::from(format!("id: {}\nevent: {}\ndata: {}\n\n", id, event, data))
+}
+
+pub fn sse_keepalive() -> Bytes {
+    Bytes::from_static(b": keepalive\n\n")
+}
+
+/// Stream server-sent events to the client until it disconnects
+pub fn sse_response(events: impl Stream<Item = Result<Bytes, Infallible>> + 'static) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .insert_header(("Cache-Control", "no-cache"))
+        .streaming(events)
+}
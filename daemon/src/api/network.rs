@@ -0,0 +1,96 @@
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use log::warn;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use crate::auth::AuthService;
+use crate::executor::shell::{ExecError, ShellCommand};
+use crate::executor::telephony::{self, NetworkInfo};
+use crate::executor::Backend;
+use super::sim::SimSelector;
+
+#[derive(Serialize)]
+pub struct NetworkResponse {
+    success: bool,
+    #[serde(flatten)]
+    network: Option<NetworkInfo>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+/// GET /network - Operator and registration of the SIM carrying mobile data,
+/// `?sim=1` or `?subscription_id=2` for another one
+pub async fn get_network(
+    req: HttpRequest,
+    query: web::Query<SimSelector>,
+    auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+) -> Result<HttpResponse> {
+    // Verify authentication (no body for GET)
+    auth.verify_request(&req, &[])?;
+
+    let slot = match query.resolve(backend.get_ref().as_ref()).await {
+        Ok(Some(selected)) => selected.subscription.slot,
+        Ok(None) => default_slot(backend.get_ref().as_ref()).await,
+        Err(e) => {
+            return Ok(HttpResponse::build(e.status()).json(NetworkResponse {
+                success: false,
+                network: None,
+                message: Some(e.to_string()),
+                error_code: Some(e.code()),
+            }));
+        }
+    };
+
+    let (registry, properties) = tokio::join!(
+        backend.execute(&ShellCommand::GetSignal),
+        backend.execute(&ShellCommand::GetTelephonyProperties),
+    );
+
+    // The properties only fill gaps, the registry alone is enough
+    let properties = match properties {
+        Ok(output) => telephony::parse_gsm_properties(&output),
+        Err(e) => {
+            warn!("getprop failed, reporting registry data only: {}", e);
+            BTreeMap::new()
+        }
+    };
+
+    let network = registry.and_then(|output| {
+        let sections = telephony::phone_sections(&output);
+        sections
+            .get(slot)
+            .and_then(|section| telephony::parse_network_info(section, &properties, slot))
+            .ok_or_else(|| ExecError::Parse(format!("no mServiceState for phone {} in telephony.registry", slot)))
+    });
+
+    match network {
+        Ok(network) => Ok(HttpResponse::Ok().json(NetworkResponse {
+            success: true,
+            network: Some(network),
+            message: None,
+            error_code: None,
+        })),
+        Err(e) => Ok(HttpResponse::build(super::error_status(&e)).json(NetworkResponse {
+            success: false,
+            network: None,
+            message: Some(format!("Failed to read network registration: {}", e)),
+            error_code: Some(e.code()),
+        })),
+    }
+}
+
+/// Slot of the default data SIM, the first slot on phones without `dumpsys isub`
+async fn default_slot(backend: &dyn Backend) -> usize {
+    let subscriptions = match backend.execute(&ShellCommand::GetSubscriptions).await {
+        Ok(output) => telephony::parse_subscriptions(&output),
+        Err(e) => {
+            warn!("isub failed, reporting the first SIM: {}", e);
+            return 0;
+        }
+    };
+    subscriptions.default_data.and_then(|id| subscriptions.by_id(id)).map_or(0, |sim| sim.slot)
+}
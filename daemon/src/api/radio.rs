@@ -1,13 +1,31 @@
+use actix_web::http::StatusCode;
 use actix_web::{web, HttpRequest, HttpResponse, Result};
+use log::{error, warn};
 use serde::{Deserialize, Serialize};
+use std::fmt;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::auth::AuthService;
-use crate::executor::shell::ShellCommand;
+use crate::executor::airplane::{self, Radio, RadioSetting, RadioStatus};
+use crate::executor::Backend;
+use crate::lockout::{LockoutError, LockoutGuard, PendingRevert, RadioChange};
+use crate::sampler::{Probe, StatusSampler};
+use crate::executor::shell::{ExecError, ShellCommand};
+use super::sim::SimSelector;
+
+/// How long to wait for the cellular radio to switch off
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(10);
+const SETTLE_POLL: Duration = Duration::from_millis(500);
 
 #[derive(Deserialize, Serialize)]
 pub struct DataToggleRequest {
     enable: bool,
+    #[serde(flatten)]
+    target: SimSelector,
+    /// Undo the change after this many seconds unless confirmed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    revert_after_secs: Option<u64>,
 }
 
 #[derive(Serialize)]
@@ -15,11 +33,21 @@ pub struct DataToggleResponse {
     success: bool,
     enabled: bool,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revert: Option<PendingRevert>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct AirplaneModeRequest {
     enable: bool,
+    /// Radios airplane mode should leave on, e.g. the Wi-Fi the daemon is reached over
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    keep: Vec<Radio>,
+    /// Undo the change after this many seconds unless confirmed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    revert_after_secs: Option<u64>,
 }
 
 #[derive(Serialize)]
@@ -27,6 +55,105 @@ pub struct AirplaneModeResponse {
     success: bool,
     enabled: bool,
     message: String,
+    /// Which radios are on afterwards, when some were kept
+    #[serde(skip_serializing_if = "Option::is_none")]
+    radios: Option<RadioStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revert: Option<PendingRevert>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+/// An airplane mode change that could not be carried out
+#[derive(Debug)]
+pub enum AirplaneError {
+    Exec(ExecError),
+    InvalidRequest(&'static str),
+    /// Radios are chosen as airplane mode switches on, which already happened
+    AlreadyOn,
+    /// The cellular radio did not switch off in time
+    Unchanged(RadioStatus),
+}
+
+impl AirplaneError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AirplaneError::Exec(e) => super::error_status(e),
+            AirplaneError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            AirplaneError::AlreadyOn => StatusCode::CONFLICT,
+            AirplaneError::Unchanged(_) => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            AirplaneError::Exec(e) => e.code(),
+            AirplaneError::InvalidRequest(_) => "invalid_request",
+            AirplaneError::AlreadyOn => "airplane_already_on",
+            AirplaneError::Unchanged(_) => "airplane_unchanged",
+        }
+    }
+
+    fn radios(self) -> Option<RadioStatus> {
+        match self {
+            AirplaneError::Unchanged(radios) => Some(radios),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for AirplaneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AirplaneError::Exec(e) => write!(f, "Failed to toggle airplane mode: {}", e),
+            AirplaneError::InvalidRequest(detail) => write!(f, "{}", detail),
+            AirplaneError::AlreadyOn => {
+                write!(f, "Airplane mode is already on, turn it off first to choose the radios it keeps")
+            }
+            AirplaneError::Unchanged(_) => write!(f, "The cellular radio is still on"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct BluetoothToggleRequest {
+    enable: bool,
+    /// Undo the change after this many seconds unless confirmed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    revert_after_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct BluetoothToggleResponse {
+    success: bool,
+    enabled: bool,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revert: Option<PendingRevert>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ConfirmRequest {
+    revert_id: u64,
+}
+
+#[derive(Serialize)]
+pub struct ConfirmResponse {
+    success: bool,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+/// HTTP status for a refused radio change or confirmation
+pub fn lockout_status(error: &LockoutError) -> StatusCode {
+    match error {
+        LockoutError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+        LockoutError::WouldLockOut { .. } => StatusCode::CONFLICT,
+        LockoutError::UnknownRevert(_) => StatusCode::NOT_FOUND,
+    }
 }
 
 /// POST /radio/data - Toggle mobile data on/off
@@ -34,6 +161,9 @@ pub async fn toggle_data(
     req: HttpRequest,
     body: web::Json<DataToggleRequest>,
     auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+    sampler: web::Data<Arc<StatusSampler>>,
+    lockout: web::Data<Arc<LockoutGuard>>,
 ) -> Result<HttpResponse> {
     // Extract the inner value for HMAC verification and later use
     let data_request = body.into_inner();
@@ -45,19 +175,58 @@ pub async fn toggle_data(
     // Verify authentication
     auth.verify_request(&req, &body_bytes)?;
 
-    // Execute appropriate command based on enable flag
-    let command = if data_request.enable {
-        ShellCommand::EnableData
-    } else {
-        ShellCommand::DisableData
+    let selected = match data_request.target.resolve(backend.get_ref().as_ref()).await {
+        Ok(selected) => selected,
+        Err(e) => {
+            return Ok(HttpResponse::build(e.status()).json(DataToggleResponse {
+                success: false,
+                enabled: !data_request.enable,
+                message: e.to_string(),
+                revert: None,
+                error_code: Some(e.code()),
+            }));
+        }
+    };
+
+    // svc data only switches the default data SIM, other SIMs keep a per-subscription setting
+    let default_data = !matches!(selected, Some(ref sim) if !sim.default_data);
+    let command = |enable: bool| match selected {
+        Some(ref sim) if !default_data => ShellCommand::SetSubscriptionData { sub_id: sim.subscription.id, enable },
+        _ if enable => ShellCommand::EnableData,
+        _ => ShellCommand::DisableData,
+    };
+
+    let change = RadioChange::Data { enable: data_request.enable, default_data };
+    let peer = req.peer_addr().map(|addr| addr.ip());
+    let revert_after = match lockout.check(peer, &change, data_request.revert_after_secs).await {
+        Ok(revert_after) => revert_after,
+        Err(e) => {
+            return Ok(HttpResponse::build(lockout_status(&e)).json(DataToggleResponse {
+                success: false,
+                enabled: !data_request.enable,
+                message: e.to_string(),
+                revert: None,
+                error_code: Some(e.code()),
+            }));
+        }
     };
 
-    match command.execute() {
+    match backend.execute(&command(data_request.enable)).await {
         Ok(_) => {
+            // Status changed, do not wait for the next scheduled sample
+            sampler.refresh_later(&[Probe::Data, Probe::Signal]);
+            let revert = revert_after.map(|after| lockout.schedule(command(!data_request.enable), after));
+            let target = selected.map(|sim| format!(" on SIM {}", sim.subscription.slot + 1)).unwrap_or_default();
             let response = DataToggleResponse {
                 success: true,
                 enabled: data_request.enable,
-                message: format!("Mobile data {}", if data_request.enable { "enabled" } else { "disabled" }),
+                message: format!(
+                    "Mobile data {}{}",
+                    if data_request.enable { "enabled" } else { "disabled" },
+                    target
+                ),
+                revert,
+                error_code: None,
             };
             Ok(HttpResponse::Ok().json(response))
         }
@@ -66,17 +235,22 @@ pub async fn toggle_data(
                 success: false,
                 enabled: !data_request.enable, // Assume it stayed in previous state
                 message: format!("Failed to toggle data: {}", e),
+                revert: None,
+                error_code: Some(e.code()),
             };
-            Ok(HttpResponse::InternalServerError().json(response))
+            Ok(HttpResponse::build(super::error_status(&e)).json(response))
         }
     }
 }
 
-/// POST /radio/airplane - Toggle airplane mode on/off
+/// POST /radio/airplane - Toggle airplane mode on/off, optionally keeping some radios on
 pub async fn toggle_airplane_mode(
     req: HttpRequest,
     body: web::Json<AirplaneModeRequest>,
     auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+    sampler: web::Data<Arc<StatusSampler>>,
+    lockout: web::Data<Arc<LockoutGuard>>,
 ) -> Result<HttpResponse> {
     // Extract the inner value for HMAC verification and later use
     let airplane_request = body.into_inner();
@@ -88,29 +262,292 @@ pub async fn toggle_airplane_mode(
     // Verify authentication
     auth.verify_request(&req, &body_bytes)?;
 
-    // Execute appropriate command based on enable flag
-    let command = if airplane_request.enable {
-        ShellCommand::EnableAirplaneMode
-    } else {
-        ShellCommand::DisableAirplaneMode
+    let enable = airplane_request.enable;
+    let change = RadioChange::Airplane { enable, keep: airplane_request.keep.clone() };
+    let peer = req.peer_addr().map(|addr| addr.ip());
+    let revert_after = match lockout.check(peer, &change, airplane_request.revert_after_secs).await {
+        Ok(revert_after) => revert_after,
+        Err(e) => {
+            return Ok(HttpResponse::build(lockout_status(&e)).json(AirplaneModeResponse {
+                success: false,
+                enabled: !enable,
+                message: e.to_string(),
+                radios: None,
+                revert: None,
+                error_code: Some(e.code()),
+            }));
+        }
     };
 
-    match command.execute() {
-        Ok(_) => {
+    // The revert is scheduled as soon as airplane mode switches, so it also
+    // covers a change that fails afterwards, e.g. while waiting for the radio
+    let mut switched = false;
+    let mut revert = None;
+    let on_switched = || {
+        switched = true;
+        let inverse = if enable { ShellCommand::DisableAirplaneMode } else { ShellCommand::EnableAirplaneMode };
+        revert = revert_after.map(|after| lockout.schedule(inverse, after));
+    };
+    let result =
+        change_airplane_mode(backend.get_ref().as_ref(), &sampler, enable, &airplane_request.keep, on_switched).await;
+    match result {
+        Ok(radios) => {
+            // Status changed, do not wait for the next scheduled sample
+            sampler.refresh_later(&[Probe::Airplane, Probe::Data, Probe::Signal, Probe::Wifi, Probe::Bluetooth]);
             let response = AirplaneModeResponse {
                 success: true,
-                enabled: airplane_request.enable,
-                message: format!("Airplane mode {}", if airplane_request.enable { "enabled" } else { "disabled" }),
+                enabled: enable,
+                message: format!("Airplane mode {}", if enable { "enabled" } else { "disabled" }),
+                radios,
+                revert,
+                error_code: None,
             };
             Ok(HttpResponse::Ok().json(response))
         }
+        Err(e) => Ok(HttpResponse::build(e.status()).json(AirplaneModeResponse {
+            success: false,
+            // The setting changed even when a later step failed
+            enabled: if switched { enable } else { !enable },
+            message: e.to_string(),
+            error_code: Some(e.code()),
+            revert,
+            radios: e.radios(),
+        })),
+    }
+}
+
+/// POST /radio/bluetooth - Toggle Bluetooth on/off
+pub async fn toggle_bluetooth(
+    req: HttpRequest,
+    body: web::Json<BluetoothToggleRequest>,
+    auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+    sampler: web::Data<Arc<StatusSampler>>,
+    lockout: web::Data<Arc<LockoutGuard>>,
+) -> Result<HttpResponse> {
+    // Extract the inner value for HMAC verification and later use
+    let bluetooth_request = body.into_inner();
+
+    // Serialize body for HMAC verification
+    let body_bytes = serde_json::to_vec(&bluetooth_request)
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid JSON: {}", e)))?;
+
+    // Verify authentication
+    auth.verify_request(&req, &body_bytes)?;
+
+    let command = |enable: bool| if enable { ShellCommand::EnableBluetooth } else { ShellCommand::DisableBluetooth };
+
+    let change = RadioChange::Bluetooth { enable: bluetooth_request.enable };
+    let peer = req.peer_addr().map(|addr| addr.ip());
+    let revert_after = match lockout.check(peer, &change, bluetooth_request.revert_after_secs).await {
+        Ok(revert_after) => revert_after,
         Err(e) => {
-            let response = AirplaneModeResponse {
+            return Ok(HttpResponse::build(lockout_status(&e)).json(BluetoothToggleResponse {
+                success: false,
+                enabled: !bluetooth_request.enable,
+                message: e.to_string(),
+                revert: None,
+                error_code: Some(e.code()),
+            }));
+        }
+    };
+
+    match backend.execute(&command(bluetooth_request.enable)).await {
+        Ok(_) => {
+            // Status changed, do not wait for the next scheduled sample
+            sampler.refresh_later(&[Probe::Bluetooth]);
+            let response = BluetoothToggleResponse {
+                success: true,
+                enabled: bluetooth_request.enable,
+                message: format!("Bluetooth {}", if bluetooth_request.enable { "enabled" } else { "disabled" }),
+                revert: revert_after.map(|after| lockout.schedule(command(!bluetooth_request.enable), after)),
+                error_code: None,
+            };
+            Ok(HttpResponse::Ok().json(response))
+        }
+        Err(e) => {
+            let response = BluetoothToggleResponse {
                 success: false,
-                enabled: !airplane_request.enable, // Assume it stayed in previous state
-                message: format!("Failed to toggle airplane mode: {}", e),
+                enabled: !bluetooth_request.enable, // Assume it stayed in previous state
+                message: format!("Failed to toggle Bluetooth: {}", e),
+                revert: None,
+                error_code: Some(e.code()),
             };
-            Ok(HttpResponse::InternalServerError().json(response))
+            Ok(HttpResponse::build(super::error_status(&e)).json(response))
         }
     }
 }
+
+/// POST /radio/confirm - Keep a change made with revert_after_secs
+pub async fn confirm_change(
+    req: HttpRequest,
+    body: web::Json<ConfirmRequest>,
+    auth: web::Data<Arc<AuthService>>,
+    lockout: web::Data<Arc<LockoutGuard>>,
+) -> Result<HttpResponse> {
+    // Extract the inner value for HMAC verification and later use
+    let confirm_request = body.into_inner();
+
+    // Serialize body for HMAC verification
+    let body_bytes = serde_json::to_vec(&confirm_request)
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid JSON: {}", e)))?;
+
+    // Verify authentication
+    auth.verify_request(&req, &body_bytes)?;
+
+    match lockout.confirm(confirm_request.revert_id) {
+        Ok(()) => Ok(HttpResponse::Ok().json(ConfirmResponse {
+            success: true,
+            message: format!("Change {} confirmed, it will not be reverted", confirm_request.revert_id),
+            error_code: None,
+        })),
+        Err(e) => Ok(HttpResponse::build(lockout_status(&e)).json(ConfirmResponse {
+            success: false,
+            message: e.to_string(),
+            error_code: Some(e.code()),
+        })),
+    }
+}
+
+/// Switch airplane mode. With radios to keep, `airplane_mode_radios` leaves them
+/// out while airplane mode switches on, and both radio settings are put back once
+/// the cellular radio is off. Returns which radios are on when some were kept.
+/// `on_switched` runs as soon as the airplane mode command succeeds.
+pub async fn change_airplane_mode(
+    backend: &dyn Backend,
+    sampler: &StatusSampler,
+    enable: bool,
+    keep: &[Radio],
+    on_switched: impl FnOnce(),
+) -> Result<Option<RadioStatus>, AirplaneError> {
+    if keep.is_empty() {
+        let command = if enable { ShellCommand::EnableAirplaneMode } else { ShellCommand::DisableAirplaneMode };
+        backend.execute(&command).await.map_err(AirplaneError::Exec)?;
+        on_switched();
+        return Ok(None);
+    }
+    if !enable {
+        return Err(AirplaneError::InvalidRequest("Radios can only be kept with enable: true"));
+    }
+    let airplane = backend.execute(&ShellCommand::GetAirplaneMode).await.map_err(AirplaneError::Exec)?;
+    if airplane.trim() == "1" {
+        return Err(AirplaneError::AlreadyOn);
+    }
+
+    let before = sampler.current_radios().await;
+    let mut previous = Vec::new();
+    for setting in [RadioSetting::Radios, RadioSetting::Toggleable] {
+        let output = backend.execute(&ShellCommand::GetAirplaneRadios(setting)).await.map_err(AirplaneError::Exec)?;
+        previous.push((setting, airplane::parse_setting(&output)));
+    }
+    for (setting, value) in &previous {
+        let kept = airplane::with_kept(*setting, value.as_deref(), keep);
+        if let Err(e) = backend.execute(&ShellCommand::SetAirplaneRadios(*setting, kept)).await {
+            restore_radio_settings(backend, &previous).await;
+            return Err(AirplaneError::Exec(e));
+        }
+    }
+
+    let result = enable_keeping(backend, sampler, keep, before, on_switched).await;
+    restore_radio_settings(backend, &previous).await;
+    result.map(Some)
+}
+
+/// Switch airplane mode on and wait for the cellular radio to go off. A ROM that
+/// ignores `airplane_mode_radios` switches the kept radios off too; those that
+/// were on are switched back on, which the toggleable setting allows.
+async fn enable_keeping(
+    backend: &dyn Backend,
+    sampler: &StatusSampler,
+    keep: &[Radio],
+    before: RadioStatus,
+    on_switched: impl FnOnce(),
+) -> Result<RadioStatus, AirplaneError> {
+    backend.execute(&ShellCommand::EnableAirplaneMode).await.map_err(AirplaneError::Exec)?;
+    on_switched();
+
+    let deadline = tokio::time::Instant::now() + SETTLE_TIMEOUT;
+    let mut radios = loop {
+        let radios = sampler.current_radios().await;
+        if radios.cell == Some(false) {
+            break radios;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(AirplaneError::Unchanged(radios));
+        }
+        tokio::time::sleep(SETTLE_POLL).await;
+    };
+
+    let mut switched_back = false;
+    for &radio in keep {
+        let command = match radio {
+            Radio::Wifi => ShellCommand::EnableWifi,
+            Radio::Bluetooth => ShellCommand::EnableBluetooth,
+            // No whitelisted command switches NFC
+            Radio::Nfc => continue,
+        };
+        if before.get(radio) == Some(true) && radios.get(radio) == Some(false) {
+            warn!("Airplane mode switched {} off although it was kept, switching it back on", radio.as_str());
+            backend.execute(&command).await.map_err(AirplaneError::Exec)?;
+            switched_back = true;
+        }
+    }
+    if switched_back {
+        radios = sampler.current_radios().await;
+    }
+    Ok(radios)
+}
+
+/// Put the radio settings back as they were, deleting those that were unset
+async fn restore_radio_settings(backend: &dyn Backend, previous: &[(RadioSetting, Option<String>)]) {
+    for (setting, value) in previous {
+        let command = match value {
+            Some(value) => ShellCommand::SetAirplaneRadios(*setting, value.clone()),
+            None => ShellCommand::ResetAirplaneRadios(*setting),
+        };
+        if let Err(e) = backend.execute(&command).await {
+            error!("Failed to restore {} to {:?}: {}", setting.key(), value.as_deref().unwrap_or("null"), e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SamplerConfig, UssdConfig};
+    use crate::executor::simulator::SimulatedBackend;
+    use crate::ussd::UssdService;
+
+    #[tokio::test]
+    async fn test_change_airplane_mode_keeping_radios() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let ussd = Arc::new(UssdService::new(backend.clone(), UssdConfig::default()));
+        let sampler = StatusSampler::new(backend.clone(), ussd, SamplerConfig::default());
+
+        // Counts the airplane mode switches, where the handler schedules the revert
+        let mut switched = 0;
+
+        let disabling = change_airplane_mode(backend.as_ref(), &sampler, false, &[Radio::Wifi], || switched += 1).await;
+        assert_eq!(disabling.unwrap_err().status(), StatusCode::BAD_REQUEST);
+        assert_eq!(switched, 0);
+
+        let keeping = change_airplane_mode(backend.as_ref(), &sampler, true, &[Radio::Wifi], || switched += 1).await;
+        let radios = keeping.unwrap().unwrap();
+        assert_eq!(radios, RadioStatus { cell: Some(false), wifi: Some(true), bluetooth: Some(false) });
+        assert_eq!(switched, 1);
+        // The settings are back to unset
+        let setting = backend.execute(&ShellCommand::GetAirplaneRadios(RadioSetting::Radios)).await.unwrap();
+        assert_eq!(setting.trim(), "null");
+
+        let again = change_airplane_mode(backend.as_ref(), &sampler, true, &[Radio::Wifi], || switched += 1).await;
+        assert_eq!(again.unwrap_err().code(), "airplane_already_on");
+        assert_eq!(switched, 1);
+
+        // Without radios to keep, airplane mode switches everything as before
+        change_airplane_mode(backend.as_ref(), &sampler, false, &[], || switched += 1).await.unwrap();
+        change_airplane_mode(backend.as_ref(), &sampler, true, &[], || switched += 1).await.unwrap();
+        assert_eq!(switched, 3);
+        let radios = sampler.current_radios().await;
+        assert_eq!(radios, RadioStatus { cell: Some(false), wifi: Some(false), bluetooth: Some(false) });
+    }
+}
@@ -0,0 +1,122 @@
+use actix_web::http::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+use crate::executor::shell::{ExecError, ShellCommand};
+use crate::executor::telephony::{self, Subscription, Subscriptions};
+use crate::executor::Backend;
+
+/// Optional SIM selection accepted by the data, network and forwarding APIs.
+/// Without either field the phone's default SIM is used, as before.
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+pub struct SimSelector {
+    /// Logical slot, 0 for the first SIM
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sim: Option<usize>,
+    /// Subscription ID as reported in /status `sims`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subscription_id: Option<i32>,
+}
+
+/// A selector that could not be resolved to an active SIM
+#[derive(Debug)]
+pub enum SimError {
+    Unknown(String),
+    Exec(ExecError),
+}
+
+impl SimError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            SimError::Unknown(_) => StatusCode::BAD_REQUEST,
+            SimError::Exec(e) => super::error_status(e),
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            SimError::Unknown(_) => "invalid_request",
+            SimError::Exec(e) => e.code(),
+        }
+    }
+}
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimError::Unknown(detail) => write!(f, "{}", detail),
+            SimError::Exec(e) => write!(f, "failed to list SIMs: {}", e),
+        }
+    }
+}
+
+/// The SIM a request targets
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectedSim {
+    pub subscription: Subscription,
+    /// Whether this SIM is the one carrying mobile data
+    pub default_data: bool,
+}
+
+impl SimSelector {
+    pub fn is_default(&self) -> bool {
+        self.sim.is_none() && self.subscription_id.is_none()
+    }
+
+    /// Find the selected SIM among the active subscriptions
+    pub fn select(&self, subscriptions: &Subscriptions) -> Result<Option<SelectedSim>, SimError> {
+        let subscription = match (self.sim, self.subscription_id) {
+            (None, None) => return Ok(None),
+            (Some(slot), None) => subscriptions
+                .by_slot(slot)
+                .ok_or_else(|| SimError::Unknown(format!("No active SIM in slot {}", slot)))?,
+            (None, Some(id)) => subscriptions
+                .by_id(id)
+                .ok_or_else(|| SimError::Unknown(format!("No active SIM with subscription ID {}", id)))?,
+            (Some(slot), Some(id)) => subscriptions
+                .by_id(id)
+                .filter(|s| s.slot == slot)
+                .ok_or_else(|| SimError::Unknown(format!("Subscription {} is not in slot {}", id, slot)))?,
+        };
+        Ok(Some(SelectedSim {
+            default_data: subscriptions.default_data == Some(subscription.id),
+            subscription: subscription.clone(),
+        }))
+    }
+
+    /// Look the selected SIM up in `dumpsys isub`. Ok(None) when nothing is selected.
+    pub async fn resolve(&self, backend: &dyn Backend) -> Result<Option<SelectedSim>, SimError> {
+        if self.is_default() {
+            return Ok(None);
+        }
+        let output = backend.execute(&ShellCommand::GetSubscriptions).await.map_err(SimError::Exec)?;
+        self.select(&telephony::parse_subscriptions(&output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscriptions() -> Subscriptions {
+        let sim = |id, slot| Subscription { id, slot, display_name: None, carrier_name: None };
+        Subscriptions { active: vec![sim(1, 0), sim(2, 1)], default_data: Some(1) }
+    }
+
+    #[test]
+    fn test_select() {
+        let subscriptions = subscriptions();
+        assert_eq!(SimSelector::default().select(&subscriptions).unwrap(), None);
+
+        let by_slot = SimSelector { sim: Some(1), subscription_id: None }.select(&subscriptions).unwrap().unwrap();
+        assert_eq!(by_slot.subscription.id, 2);
+        assert!(!by_slot.default_data);
+
+        let by_id = SimSelector { sim: None, subscription_id: Some(1) }.select(&subscriptions).unwrap().unwrap();
+        assert_eq!(by_id.subscription.slot, 0);
+        assert!(by_id.default_data);
+
+        assert!(SimSelector { sim: Some(2), subscription_id: None }.select(&subscriptions).is_err());
+        assert!(SimSelector { sim: Some(0), subscription_id: Some(2) }.select(&subscriptions).is_err());
+    }
+}
@@ -0,0 +1,472 @@
+use actix_web::web::Bytes;
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use chrono::Utc;
+use futures_util::stream;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+use crate::auth::AuthService;
+use crate::executor::shell::{ShellCommand, DEFAULT_SUB_ID};
+use crate::executor::sms::{self, InboxFilter, SendStatus, SmsMessage};
+use crate::executor::Backend;
+use crate::forwarder::{SmsEvent, SmsForwarder};
+use super::call::is_valid_phone_number;
+use super::sim::SimSelector;
+
+const DEFAULT_LIMIT: usize = 20;
+const MAX_LIMIT: usize = 100;
+
+/// Sent messages whose status can still be polled
+const OUTBOX_CAPACITY: usize = 100;
+
+/// The phone stamps the message itself, allow for the time the command took
+const CLOCK_SLACK_MS: i64 = 5_000;
+
+#[derive(Deserialize)]
+pub struct SmsQuery {
+    /// Only messages received after this Unix ms
+    since: Option<i64>,
+    /// Only unread messages
+    #[serde(default)]
+    unread: bool,
+    /// Page size, 1 to 100 (default 20)
+    limit: Option<usize>,
+    /// Messages to skip, newest first
+    #[serde(default)]
+    offset: usize,
+}
+
+#[derive(Serialize)]
+pub struct SmsListResponse {
+    success: bool,
+    messages: Vec<SmsMessage>,
+    /// Messages matching the filters, across all pages
+    total: usize,
+    has_more: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+impl SmsListResponse {
+    fn error(message: String, error_code: &'static str) -> Self {
+        Self { success: false, messages: Vec::new(), total: 0, has_more: false, message: Some(message), error_code: Some(error_code) }
+    }
+}
+
+/// GET /sms - Inbox messages, newest first
+pub async fn list_sms(
+    req: HttpRequest,
+    query: web::Query<SmsQuery>,
+    auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+) -> Result<HttpResponse> {
+    // Verify authentication (no body for GET)
+    auth.verify_request(&req, &[])?;
+
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT);
+    if !(1..=MAX_LIMIT).contains(&limit) {
+        return Ok(HttpResponse::BadRequest().json(SmsListResponse::error(
+            format!("limit must be between 1 and {}", MAX_LIMIT),
+            "invalid_request",
+        )));
+    }
+
+    let filter = InboxFilter { since: query.since, unread_only: query.unread };
+    match backend.execute(&ShellCommand::ContentQuery(filter.query())).await {
+        Ok(output) => {
+            // The provider already filtered; this keeps backends that ignore the selection honest
+            let messages: Vec<SmsMessage> =
+                sms::parse_messages(&output).into_iter().filter(|m| filter.matches(m)).collect();
+            let total = messages.len();
+            let page: Vec<SmsMessage> = messages.into_iter().skip(query.offset).take(limit).collect();
+            Ok(HttpResponse::Ok().json(SmsListResponse {
+                success: true,
+                has_more: query.offset + page.len() < total,
+                messages: page,
+                total,
+                message: None,
+                error_code: None,
+            }))
+        }
+        Err(e) => Ok(HttpResponse::build(super::error_status(&e))
+            .json(SmsListResponse::error(format!("Failed to read SMS inbox: {}", e), e.code()))),
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct SmsSendRequest {
+    number: String,
+    body: String,
+    #[serde(flatten)]
+    target: SimSelector,
+}
+
+#[derive(Serialize)]
+pub struct SmsSendResponse {
+    success: bool,
+    /// Poll GET /sms/send/{id} with this
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<SendStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parts: Option<usize>,
+    /// ID of the message in the SMS provider, once it shows up there
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sms_id: Option<i64>,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+impl SmsSendResponse {
+    fn error(message: String, error_code: &'static str) -> Self {
+        Self { success: false, id: None, status: None, parts: None, sms_id: None, message, error_code: Some(error_code) }
+    }
+}
+
+/// A text handed to the phone
+#[derive(Debug, Clone)]
+struct Submission {
+    id: u64,
+    number: String,
+    body: String,
+    parts: usize,
+    /// Unix ms
+    submitted_at: i64,
+    /// Provider row once matched, so identical texts are told apart
+    sms_id: Option<i64>,
+}
+
+/// Recently sent texts, matched against the SMS provider when polled
+pub struct SmsOutbox {
+    state: Mutex<OutboxState>,
+}
+
+struct OutboxState {
+    next_id: u64,
+    /// Oldest first
+    submissions: VecDeque<Submission>,
+}
+
+impl SmsOutbox {
+    pub fn new() -> Self {
+        Self { state: Mutex::new(OutboxState { next_id: 1, submissions: VecDeque::new() }) }
+    }
+
+    fn add(&self, number: String, body: String, parts: usize, submitted_at: i64) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        if state.submissions.len() == OUTBOX_CAPACITY {
+            state.submissions.pop_front();
+        }
+        state.submissions.push_back(Submission { id, number, body, parts, submitted_at, sms_id: None });
+        id
+    }
+
+    fn get(&self, id: u64) -> Option<Submission> {
+        self.state.lock().unwrap().submissions.iter().find(|s| s.id == id).cloned()
+    }
+
+    /// Find the provider row of a submission among `messages`: the oldest
+    /// one to the same number with the same text that no other submission claimed
+    fn resolve<'a>(&self, id: u64, messages: &'a [SmsMessage]) -> Option<&'a SmsMessage> {
+        let mut state = self.state.lock().unwrap();
+        let claimed: Vec<i64> =
+            state.submissions.iter().filter(|s| s.id != id).filter_map(|s| s.sms_id).collect();
+        let submission = state.submissions.iter_mut().find(|s| s.id == id)?;
+        if let Some(sms_id) = submission.sms_id {
+            return messages.iter().find(|m| m.id == sms_id);
+        }
+        let found = messages
+            .iter()
+            .filter(|m| m.date >= submission.submitted_at - CLOCK_SLACK_MS && !claimed.contains(&m.id))
+            .filter(|m| m.address.as_deref().is_some_and(|a| same_number(a, &submission.number)))
+            .filter(|m| m.body == submission.body)
+            .min_by_key(|m| m.date)?;
+        submission.sms_id = Some(found.id);
+        Some(found)
+    }
+}
+
+/// Numbers as typed and as stored by the provider, e.g. "+4915112345678" and "015112345678"
+fn same_number(a: &str, b: &str) -> bool {
+    let digits = |n: &str| n.chars().filter(|c| c.is_ascii_digit()).collect::<String>();
+    let (a, b) = (digits(a), digits(b));
+    // Compare the subscriber part, country and trunk prefixes vary
+    let tail = |n: &str| n[n.len().saturating_sub(9)..].to_string();
+    a == b || (a.len() >= 7 && b.len() >= 7 && tail(&a) == tail(&b))
+}
+
+/// POST /sms/send - Send a text from the phone's SIM
+pub async fn send_sms(
+    req: HttpRequest,
+    body: web::Json<SmsSendRequest>,
+    auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+    outbox: web::Data<Arc<SmsOutbox>>,
+) -> Result<HttpResponse> {
+    // Extract the inner value for HMAC verification and later use
+    let send_request = body.into_inner();
+
+    // Serialize body for HMAC verification
+    let body_bytes = serde_json::to_vec(&send_request)
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid JSON: {}", e)))?;
+
+    // Verify authentication
+    auth.verify_request(&req, &body_bytes)?;
+
+    if !is_valid_phone_number(&send_request.number) {
+        return Ok(HttpResponse::BadRequest()
+            .json(SmsSendResponse::error("Invalid phone number format".to_string(), "invalid_request")));
+    }
+    if send_request.body.is_empty() {
+        return Ok(HttpResponse::BadRequest()
+            .json(SmsSendResponse::error("Message body is empty".to_string(), "invalid_request")));
+    }
+    let parts = sms::split_parts(&send_request.body);
+    if parts.len() > sms::MAX_PARTS {
+        return Ok(HttpResponse::BadRequest().json(SmsSendResponse::error(
+            format!("Message is {} parts long, at most {} are allowed", parts.len(), sms::MAX_PARTS),
+            "invalid_request",
+        )));
+    }
+
+    let sub_id = match send_request.target.resolve(backend.get_ref().as_ref()).await {
+        Ok(selected) => selected.map_or(DEFAULT_SUB_ID, |sim| sim.subscription.id),
+        Err(e) => return Ok(HttpResponse::build(e.status()).json(SmsSendResponse::error(e.to_string(), e.code()))),
+    };
+
+    let part_count = parts.len();
+    let submitted_at = Utc::now().timestamp_millis();
+    let command = ShellCommand::SendSms { number: send_request.number.clone(), parts, sub_id };
+    let result = backend.execute(&command).await.and_then(|output| sms::parse_send_result(&output));
+
+    match result {
+        Ok(()) => {
+            let id = outbox.add(send_request.number.clone(), send_request.body, part_count, submitted_at);
+            Ok(HttpResponse::Ok().json(SmsSendResponse {
+                success: true,
+                id: Some(id),
+                status: Some(SendStatus::Pending),
+                parts: Some(part_count),
+                sms_id: None,
+                message: format!("Sending SMS to {}", send_request.number),
+                error_code: None,
+            }))
+        }
+        Err(e) => Ok(HttpResponse::build(super::error_status(&e))
+            .json(SmsSendResponse::error(format!("Failed to send SMS: {}", e), e.code()))),
+    }
+}
+
+/// GET /sms/send/{id} - How far a text sent with POST /sms/send got
+pub async fn get_send_status(
+    req: HttpRequest,
+    path: web::Path<u64>,
+    auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+    outbox: web::Data<Arc<SmsOutbox>>,
+) -> Result<HttpResponse> {
+    // Verify authentication (no body for GET)
+    auth.verify_request(&req, &[])?;
+
+    let id = path.into_inner();
+    let Some(submission) = outbox.get(id) else {
+        return Ok(HttpResponse::NotFound()
+            .json(SmsSendResponse::error(format!("No sent SMS with ID {}", id), "not_found")));
+    };
+
+    let query = sms::outgoing_query(submission.submitted_at - CLOCK_SLACK_MS);
+    match backend.execute(&ShellCommand::ContentQuery(query)).await {
+        Ok(output) => {
+            let messages = sms::parse_messages(&output);
+            let found = outbox.resolve(id, &messages);
+            let status = found.map_or(SendStatus::Pending, SendStatus::of);
+            Ok(HttpResponse::Ok().json(SmsSendResponse {
+                success: true,
+                id: Some(id),
+                status: Some(status),
+                parts: Some(submission.parts),
+                sms_id: found.map(|m| m.id),
+                message: format!("SMS to {} is {}", submission.number, format!("{:?}", status).to_lowercase()),
+                error_code: None,
+            }))
+        }
+        Err(e) => Ok(HttpResponse::build(super::error_status(&e))
+            .json(SmsSendResponse::error(format!("Failed to read SMS status: {}", e), e.code()))),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct SmsEventsQuery {
+    /// Replay texts received after this SMS ID before streaming new ones
+    after: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct SmsEventsError {
+    success: bool,
+    message: String,
+    error_code: &'static str,
+}
+
+/// GET /sms/events - Server-sent events, one `sms` event per received text.
+/// Each event's `id` is the SMS ID, so a client that reconnects with
+/// `Last-Event-ID` (or `?after=`) gets everything it missed.
+pub async fn sms_events(
+    req: HttpRequest,
+    query: web::Query<SmsEventsQuery>,
+    auth: web::Data<Arc<AuthService>>,
+    forwarder: web::Data<Arc<SmsForwarder>>,
+) -> Result<HttpResponse> {
+    // Verify authentication (no body for GET)
+    auth.verify_request(&req, &[])?;
+
+    if !forwarder.enabled() {
+        return Ok(HttpResponse::ServiceUnavailable().json(SmsEventsError {
+            success: false,
+            message: "SMS forwarding is disabled in the config".to_string(),
+            error_code: "disabled",
+        }));
+    }
+
+    let after = query.after.or_else(|| {
+        req.headers().get("Last-Event-ID").and_then(|v| v.to_str().ok()).and_then(|v| v.trim().parse().ok())
+    });
+    let receiver = forwarder.subscribe();
+    let backlog = match after {
+        Some(after) => match forwarder.received_after(after).await {
+            Ok(events) => events,
+            Err(e) => {
+                return Ok(HttpResponse::build(super::error_status(&e)).json(SmsEventsError {
+                    success: false,
+                    message: format!("Failed to read SMS inbox: {}", e),
+                    error_code: e.code(),
+                }));
+            }
+        },
+        None => Vec::new(),
+    };
+
+    let subscriber = Subscriber {
+        forwarder: forwarder.get_ref().clone(),
+        receiver,
+        pending: backlog.into(),
+        last_id: after.unwrap_or(0),
+    };
+    Ok(super::sse_response(stream::unfold(subscriber, Subscriber::next_frame)))
+}
+
+/// One /sms/events client
+struct Subscriber {
+    forwarder: Arc<SmsForwarder>,
+    receiver: broadcast::Receiver<SmsEvent>,
+    /// Backlog, then live events
+    pending: VecDeque<SmsEvent>,
+    /// Highest SMS ID sent, texts forwarded again after a webhook retry are skipped
+    last_id: i64,
+}
+
+impl Subscriber {
+    async fn next_frame(mut self) -> Option<(Result<Bytes, Infallible>, Self)> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                if event.message.id <= self.last_id {
+                    continue;
+                }
+                self.last_id = event.message.id;
+                return Some((Ok(super::sse_frame(event.message.id, "sms", &event)), self));
+            }
+            tokio::select! {
+                received = self.receiver.recv() => match received {
+                    Ok(event) => self.pending.push_back(event),
+                    // Too slow to keep up with the channel, read what was missed from the provider
+                    Err(RecvError::Lagged(_)) => match self.forwarder.received_after(self.last_id).await {
+                        Ok(events) => self.pending.extend(events),
+                        Err(e) => warn!("SMS event subscriber could not catch up: {}", e),
+                    },
+                    Err(RecvError::Closed) => return None,
+                },
+                _ = tokio::time::sleep(super::SSE_KEEPALIVE) => return Some((Ok(super::sse_keepalive()), self)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::sms::SmsKind;
+
+    fn sent(id: i64, address: &str, body: &str, date: i64) -> SmsMessage {
+        SmsMessage {
+            id,
+            thread_id: None,
+            address: Some(address.to_string()),
+            body: body.to_string(),
+            date,
+            date_sent: None,
+            read: true,
+            kind: SmsKind::Sent,
+            status: Some(-1),
+            subscription_id: Some(1),
+        }
+    }
+
+    #[test]
+    fn test_same_number() {
+        assert!(same_number("+4915112345678", "+4915112345678"));
+        assert!(same_number("+4915112345678", "015112345678"));
+        assert!(!same_number("+4915112345678", "+4915112345679"));
+        assert!(!same_number("12345", "912345"));
+    }
+
+    #[test]
+    fn test_outbox_matches_identical_texts_in_order() {
+        let outbox = SmsOutbox::new();
+        let first = outbox.add("+15550100".into(), "On my way".into(), 1, 1_000_000);
+        let second = outbox.add("+15550100".into(), "On my way".into(), 1, 1_060_000);
+        let messages = vec![
+            sent(12, "+15550100", "On my way", 1_060_500),
+            sent(11, "+15550100", "On my way", 1_000_500),
+            sent(10, "+15550100", "Something else", 1_000_400),
+        ];
+        assert_eq!(outbox.resolve(first, &messages).map(|m| m.id), Some(11));
+        assert_eq!(outbox.resolve(second, &messages).map(|m| m.id), Some(12));
+        // Still the same row on the next poll
+        assert_eq!(outbox.resolve(first, &messages).map(|m| m.id), Some(11));
+        assert_eq!(outbox.resolve(first, &[]), None);
+    }
+
+    #[tokio::test]
+    async fn test_subscriber_replays_backlog() {
+        use crate::config::ForwarderConfig;
+        use crate::executor::simulator::SimulatedBackend;
+
+        let auth = Arc::new(AuthService::new("test-secret".to_string(), 30));
+        let config = ForwarderConfig { cursor_file: "/nonexistent/cursor".to_string(), ..Default::default() };
+        let forwarder = Arc::new(SmsForwarder::new(Arc::new(SimulatedBackend::new()), auth, config).unwrap());
+        let backlog = forwarder.received_after(2).await.unwrap();
+        let mut pending: VecDeque<SmsEvent> = backlog.clone().into();
+        // The same text again, as after a webhook retry
+        pending.push_back(backlog[1].clone());
+
+        let subscriber = Subscriber { receiver: forwarder.subscribe(), forwarder, pending, last_id: 2 };
+        let (frame, subscriber) = subscriber.next_frame().await.unwrap();
+        assert!(String::from_utf8_lossy(&frame.unwrap()).starts_with("id: 3\nevent: sms\ndata: {"));
+        let (frame, subscriber) = subscriber.next_frame().await.unwrap();
+        let frame = String::from_utf8_lossy(&frame.unwrap()).to_string();
+        assert!(frame.contains("\"otp\":\"123456\""), "{}", frame);
+        // The repeat is still queued but at or below last_id, so it is never sent
+        assert_eq!(subscriber.pending.len(), 1);
+        assert_eq!(subscriber.last_id, 4);
+    }
+}
@@ -1,69 +1,268 @@
 use actix_web::{web, HttpRequest, HttpResponse, Result};
-use serde::Serialize;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 use crate::auth::AuthService;
-use crate::executor::shell::{self, ShellCommand};
+use crate::calls::CallStatus;
+use crate::executor::airplane::RadioStatus;
+use crate::executor::battery::BatteryInfo;
+use crate::executor::bluetooth::BluetoothStatus;
+use crate::executor::call_forwarding::ForwardingState;
+use crate::executor::shell::DEFAULT_SUB_ID;
+use crate::executor::telephony::{NetworkInfo, SignalInfo};
+use crate::sampler::{Probe, Sample, StatusSampler};
+use super::sim::SimSelector;
+
+#[derive(Deserialize)]
+pub struct StatusQuery {
+    /// Sample everything now instead of serving the cached snapshot
+    #[serde(default)]
+    fresh: bool,
+    /// Slot whose signal fills `signal_dbm`, `signal` and `call_forwarding_active` (default: the first SIM)
+    sim: Option<usize>,
+    subscription_id: Option<i32>,
+}
 
 #[derive(Serialize)]
 pub struct StatusResponse {
-    battery: i32,
-    charging: bool,
-    signal_dbm: i32,
+    battery: Option<i32>,
+    charging: Option<bool>,
+    battery_info: Option<BatteryInfo>,
+    signal_dbm: Option<i32>,
+    signal: Option<SignalInfo>,
     #[serde(rename = "data_enabled")]
-    data: bool,
+    data: Option<bool>,
     #[serde(rename = "airplane_mode")]
-    airplane: bool,
+    airplane: Option<bool>,
+    /// Calls to the selected SIM are forwarded for any reason
     #[serde(rename = "call_forwarding_active")]
-    call_forwarding: bool,
-    uptime: u64,
+    call_forwarding: Option<bool>,
+    uptime: Option<u64>,
+    call: Option<CallStatus>,
+    bluetooth: Option<BluetoothStatus>,
+    /// Which radios are on, whatever airplane mode says
+    radios: RadioStatus,
+    sims: Vec<SimStatus>,
     #[serde(skip_serializing_if = "Option::is_none")]
     raw_battery: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     data_detection_method: Option<String>,
+    #[serde(flatten)]
+    freshness: Freshness,
 }
 
+/// State of one SIM
+#[derive(Serialize)]
+struct SimStatus {
+    slot: usize,
+    subscription_id: Option<i32>,
+    display_name: Option<String>,
+    /// Carries mobile data
+    default_data: bool,
+    /// The SIM's mobile data switch
+    data_enabled: Option<bool>,
+    data_connected: Option<bool>,
+    signal_dbm: Option<i32>,
+    signal: Option<SignalInfo>,
+    network: Option<NetworkInfo>,
+    /// Forwarding for every reason, as the network last reported it
+    call_forwarding: Option<Vec<ForwardingState>>,
+}
+
+#[derive(Serialize)]
+struct StatusError {
+    success: bool,
+    message: String,
+    error_code: &'static str,
+}
+
+/// How current each field of the response is
+#[derive(Serialize, Default)]
+struct Freshness {
+    /// Unix ms at which each field was sampled
+    sampled_at: BTreeMap<&'static str, i64>,
+    /// Fields whose last refresh failed or is overdue, served from an earlier sample
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    stale: Vec<&'static str>,
+    /// Fields that have never been sampled successfully (null)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    unavailable: Vec<&'static str>,
+}
+
+impl Freshness {
+    /// Record how current a sample is and return its value
+    fn report<T: Clone>(&mut self, probe: Probe, sample: &Sample<T>, sampler: &StatusSampler, now: i64) -> Option<T> {
+        let fields = probe.fields();
+        match sample.sampled_at {
+            Some(sampled_at) => {
+                let overdue = now - sampled_at > sampler.max_age(probe).as_millis() as i64;
+                for &field in fields {
+                    // Fields filled by several probes carry the oldest sample
+                    let oldest = self.sampled_at.entry(field).or_insert(sampled_at);
+                    *oldest = (*oldest).min(sampled_at);
+                    if (sample.failed || overdue) && !self.stale.contains(&field) {
+                        self.stale.push(field);
+                    }
+                }
+            }
+            None => {
+                for &field in fields {
+                    if !self.unavailable.contains(&field) {
+                        self.unavailable.push(field);
+                    }
+                }
+            }
+        }
+        sample.value.clone()
+    }
+}
+
+/// GET /status - Latest sampled device status, `?fresh=true` to sample now
 pub async fn get_status(
     req: HttpRequest,
+    query: web::Query<StatusQuery>,
     auth: web::Data<Arc<AuthService>>,
+    sampler: web::Data<Arc<StatusSampler>>,
 ) -> Result<HttpResponse> {
     // Verify authentication (no body for GET)
     auth.verify_request(&req, &[])?;
 
-    // Execute shell commands to gather status
-    let battery_output = ShellCommand::GetBattery.execute()
-        .unwrap_or_default();
-    let signal_output = ShellCommand::GetSignal.execute()
-        .unwrap_or_default();
-    // Use robust mobile data detection
-    let data_output = ShellCommand::GetMobileDataConnection.execute().unwrap_or_default();
-    let data = crate::executor::shell::parse_mobile_data_connected(&data_output);
-    let data_detection_method = "dumpsys_connectivity".to_string();
-    let airplane_output = ShellCommand::GetAirplaneMode.execute()
-        .unwrap_or_default();
-    let uptime_output = ShellCommand::GetUptime.execute()
-        .unwrap_or_default();
-    let forwarding_output = ShellCommand::GetCallForwardingState.execute()
-        .unwrap_or_default();
-
-    // Parse outputs
-    let (battery, charging) = shell::parse_battery(&battery_output);
-    let signal_dbm = shell::parse_signal(&signal_output);
-    let airplane = airplane_output.trim() == "1";
-    let uptime = shell::parse_uptime(&uptime_output);
-    let call_forwarding = shell::parse_call_forwarding(&forwarding_output);
+    if query.fresh {
+        sampler.refresh_all().await;
+    }
+
+    let snapshot = sampler.snapshot();
+    let now = Utc::now().timestamp_millis();
+    let mut freshness = Freshness::default();
+
+    let battery = freshness.report(Probe::Battery, &snapshot.battery, &sampler, now);
+    let phones = freshness.report(Probe::Signal, &snapshot.phones, &sampler, now).unwrap_or_default();
+    let subscriptions = freshness.report(Probe::Subscriptions, &snapshot.subscriptions, &sampler, now);
+
+    // Before the first isub sample a slot can still be picked directly
+    let selector = SimSelector { sim: query.sim, subscription_id: query.subscription_id };
+    let slot = match subscriptions {
+        Some(ref subscriptions) => selector.select(subscriptions).map(|s| s.map_or(0, |sim| sim.subscription.slot)),
+        None if selector.subscription_id.is_none() => Ok(selector.sim.unwrap_or(0)),
+        None => selector.select(&Default::default()).map(|_| 0),
+    };
+    let slot = match slot {
+        Ok(slot) => slot,
+        Err(e) => {
+            return Ok(HttpResponse::build(e.status()).json(StatusError {
+                success: false,
+                message: e.to_string(),
+                error_code: e.code(),
+            }));
+        }
+    };
+    let signal = phones.get(slot);
+    let forwarding = freshness.report(Probe::CallForwarding, &snapshot.call_forwarding, &sampler, now);
+    // Without isub only the first SIM is interrogated, under the default subscription
+    let sub_id = |slot: usize| match subscriptions {
+        Some(ref subscriptions) => subscriptions.by_slot(slot).map(|s| s.id),
+        None => (slot == 0).then_some(DEFAULT_SUB_ID),
+    };
+    let forwarding_of = |slot: usize| forwarding.as_ref().zip(sub_id(slot)).and_then(|(f, id)| f.get(&id).cloned());
+    let call_forwarding = forwarding_of(slot).map(|states| states.iter().any(|s| s.active));
+
+    let sims = phones
+        .iter()
+        .enumerate()
+        .filter_map(|(slot, phone)| {
+            let subscription = subscriptions.as_ref().and_then(|s| s.by_slot(slot));
+            // Without isub every phone section is reported, with it only slots holding a SIM
+            if subscriptions.is_some() && subscription.is_none() {
+                return None;
+            }
+            Some(SimStatus {
+                slot,
+                subscription_id: subscription.map(|s| s.id),
+                display_name: subscription.and_then(|s| s.display_name.clone()),
+                default_data: match (subscription, &subscriptions) {
+                    (Some(sub), Some(subs)) => subs.default_data == Some(sub.id),
+                    _ => slot == 0,
+                },
+                data_enabled: phone.data_enabled,
+                data_connected: phone.data_connected,
+                signal_dbm: phone.dbm,
+                signal: phone.signal.clone(),
+                network: phone.network.clone(),
+                call_forwarding: forwarding_of(slot),
+            })
+        })
+        .collect();
+    let data = freshness.report(Probe::Data, &snapshot.data, &sampler, now);
+    let airplane = freshness.report(Probe::Airplane, &snapshot.airplane, &sampler, now);
+    let call = freshness.report(Probe::Call, &snapshot.call, &sampler, now);
+    let bluetooth = freshness.report(Probe::Bluetooth, &snapshot.bluetooth, &sampler, now);
+    freshness.report(Probe::Wifi, &snapshot.wifi, &sampler, now);
+    freshness.report(Probe::Cell, &snapshot.cell, &sampler, now);
+    let radios = snapshot.radios();
+    // Uptime keeps counting between samples
+    let uptime = freshness
+        .report(Probe::Uptime, &snapshot.uptime, &sampler, now)
+        .zip(snapshot.uptime.sampled_at)
+        .map(|(secs, sampled_at)| secs + ((now - sampled_at).max(0) / 1000) as u64);
 
     let response = StatusResponse {
-        battery,
-        charging,
-        signal_dbm,
+        battery: battery.as_ref().map(|b| b.level),
+        charging: battery.as_ref().map(|b| b.charging),
+        battery_info: battery,
+        signal_dbm: signal.and_then(|s| s.dbm),
+        signal: signal.and_then(|s| s.signal.clone()),
         data,
         airplane,
         call_forwarding,
         uptime,
-        raw_battery: Some(battery_output),
-        data_detection_method: Some(data_detection_method),
+        call,
+        bluetooth,
+        radios,
+        sims,
+        raw_battery: snapshot.raw_battery,
+        data_detection_method: Some("dumpsys_connectivity".to_string()),
+        freshness,
     };
 
     Ok(HttpResponse::Ok().json(response))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{SamplerConfig, UssdConfig};
+    use crate::executor::simulator::SimulatedBackend;
+    use crate::ussd::UssdService;
+
+    #[test]
+    fn test_freshness_report() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let ussd = Arc::new(UssdService::new(backend.clone(), UssdConfig::default()));
+        let sampler = StatusSampler::new(backend.clone(), ussd, SamplerConfig::default());
+        let now = 1_700_000_000_000;
+        let mut freshness = Freshness::default();
+
+        // Never sampled: unavailable
+        let never: Sample<u64> = Sample::default();
+        assert_eq!(freshness.report(Probe::Uptime, &never, &sampler, now), None);
+        assert_eq!(freshness.unavailable, vec!["uptime"]);
+
+        // Recent sample: fresh
+        let recent = Sample { value: Some(82), sampled_at: Some(now - 1000), failed: false };
+        assert_eq!(freshness.report(Probe::Signal, &recent, &sampler, now), Some(82));
+        assert_eq!(freshness.sampled_at["signal_dbm"], now - 1000);
+        assert!(freshness.stale.is_empty());
+
+        // Last refresh failed: stale
+        let failed = Sample { value: Some(true), sampled_at: Some(now - 1000), failed: true };
+        assert_eq!(freshness.report(Probe::Data, &failed, &sampler, now), Some(true));
+        assert_eq!(freshness.stale, vec!["data_enabled"]);
+
+        // Refresh overdue: stale
+        let old = Sample { value: Some(false), sampled_at: Some(now - 3_600_000), failed: false };
+        freshness.report(Probe::Airplane, &old, &sampler, now);
+        assert_eq!(freshness.stale, vec!["data_enabled", "airplane_mode"]);
+    }
+}
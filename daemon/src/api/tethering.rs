@@ -0,0 +1,378 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::auth::AuthService;
+use crate::config::TetheringConfig;
+use crate::executor::shell::{ExecError, ShellCommand};
+use crate::executor::tethering::{self, TetherKind, TetheringClient};
+use crate::executor::Backend;
+use crate::lockout::{LockoutGuard, PendingRevert, RadioChange};
+
+/// How long to wait for tethering to come up or go down
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(10);
+const SETTLE_POLL: Duration = Duration::from_millis(500);
+
+/// A tethered interface and its traffic since it came up
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TetheredInterface {
+    pub name: String,
+    pub kind: TetherKind,
+    /// Uploaded by the clients on this interface
+    pub rx_bytes: u64,
+    /// Downloaded by the clients on this interface
+    pub tx_bytes: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TetheringStatus {
+    pub hotspot: bool,
+    pub usb: bool,
+    pub interfaces: Vec<TetheredInterface>,
+    pub clients: Vec<TetheringClient>,
+    /// Interfaces the tethered traffic leaves through, e.g. `rmnet_data2`
+    pub upstream: Vec<String>,
+}
+
+impl TetheringStatus {
+    fn active(&self, target: TetheringTarget) -> bool {
+        match target {
+            TetheringTarget::Hotspot => self.hotspot,
+            TetheringTarget::Usb => self.usb,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct TetheringResponse {
+    success: bool,
+    #[serde(flatten)]
+    tethering: Option<TetheringStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+/// What POST /tethering switches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TetheringTarget {
+    Hotspot,
+    Usb,
+}
+
+impl TetheringTarget {
+    fn name(self) -> &'static str {
+        match self {
+            TetheringTarget::Hotspot => "Wi-Fi hotspot",
+            TetheringTarget::Usb => "USB tethering",
+        }
+    }
+
+    fn kind(self) -> TetherKind {
+        match self {
+            TetheringTarget::Hotspot => TetherKind::Hotspot,
+            TetheringTarget::Usb => TetherKind::Usb,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TetheringRequest {
+    #[serde(rename = "type")]
+    target: TetheringTarget,
+    enable: bool,
+    /// Undo the change after this many seconds unless confirmed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    revert_after_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct TetheringControlResponse {
+    success: bool,
+    message: String,
+    /// State after the change, also when it did not take
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tethering: Option<TetheringStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revert: Option<PendingRevert>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+/// A tethering change that could not be carried out
+#[derive(Debug)]
+pub enum TetheringError {
+    Exec(ExecError),
+    /// No `[tethering.hotspot]` in the config
+    NoHotspot,
+    /// Tethering did not come up or go down in time
+    Unchanged(TetheringTarget, TetheringStatus),
+}
+
+impl TetheringError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            TetheringError::Exec(e) => super::error_status(e),
+            TetheringError::NoHotspot => StatusCode::SERVICE_UNAVAILABLE,
+            TetheringError::Unchanged(..) => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            TetheringError::Exec(e) => e.code(),
+            TetheringError::NoHotspot => "disabled",
+            TetheringError::Unchanged(..) => "tethering_unchanged",
+        }
+    }
+
+    fn tethering(self) -> Option<TetheringStatus> {
+        match self {
+            TetheringError::Unchanged(_, tethering) => Some(tethering),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TetheringError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TetheringError::Exec(e) => write!(f, "Failed to control tethering: {}", e),
+            TetheringError::NoHotspot => write!(f, "No hotspot is set up under [tethering] in the config"),
+            TetheringError::Unchanged(target, tethering) => write!(
+                f,
+                "{} is still {}",
+                target.name(),
+                if tethering.active(*target) { "on" } else { "off" }
+            ),
+        }
+    }
+}
+
+/// Read what is tethered, with the traffic and clients of those interfaces
+pub async fn tethering_status(backend: &dyn Backend) -> Result<TetheringStatus, ExecError> {
+    let (state, counters, neighbours) = tokio::join!(
+        backend.execute(&ShellCommand::GetTetheringState),
+        backend.execute(&ShellCommand::GetInterfaceCounters),
+        backend.execute(&ShellCommand::GetNeighbours),
+    );
+    let state = tethering::parse_tether_state(&state?)
+        .ok_or_else(|| ExecError::Parse("no Tether state in dumpsys tethering".to_string()))?;
+    let counters = tethering::parse_interface_counters(&counters?);
+
+    let interfaces: Vec<TetheredInterface> = state
+        .tethered
+        .into_iter()
+        .map(|(name, kind)| {
+            // An interface that just went away has no counters
+            let traffic = counters.get(&name).copied().unwrap_or_default();
+            TetheredInterface { name, kind, rx_bytes: traffic.rx_bytes, tx_bytes: traffic.tx_bytes }
+        })
+        .collect();
+    let clients = tethering::parse_neighbours(&neighbours?)
+        .into_iter()
+        .filter(|client| interfaces.iter().any(|iface| iface.name == client.interface))
+        .collect();
+
+    Ok(TetheringStatus {
+        hotspot: interfaces.iter().any(|iface| iface.kind == TetherKind::Hotspot),
+        usb: interfaces.iter().any(|iface| iface.kind == TetherKind::Usb),
+        interfaces,
+        clients,
+        upstream: state.upstream,
+    })
+}
+
+/// Command that starts or stops the hotspot or USB tethering
+fn tethering_command(
+    config: &TetheringConfig,
+    target: TetheringTarget,
+    enable: bool,
+) -> Result<ShellCommand, TetheringError> {
+    Ok(match (target, enable) {
+        (TetheringTarget::Hotspot, true) => {
+            ShellCommand::StartHotspot(config.hotspot.clone().ok_or(TetheringError::NoHotspot)?)
+        }
+        (TetheringTarget::Hotspot, false) => ShellCommand::StopHotspot,
+        (TetheringTarget::Usb, true) => ShellCommand::StartUsbTethering(config.usb_function),
+        (TetheringTarget::Usb, false) => ShellCommand::StopUsbTethering,
+    })
+}
+
+/// Start or stop the hotspot or USB tethering and wait until tethering reports
+/// it. `on_switched` runs as soon as the command succeeds, and not at all when
+/// tethering already was as asked.
+pub async fn change_tethering(
+    backend: &dyn Backend,
+    config: &TetheringConfig,
+    target: TetheringTarget,
+    enable: bool,
+    on_switched: impl FnOnce(),
+) -> Result<TetheringStatus, TetheringError> {
+    let command = tethering_command(config, target, enable)?;
+
+    let status = tethering_status(backend).await.map_err(TetheringError::Exec)?;
+    if status.active(target) == enable {
+        return Ok(status);
+    }
+    backend.execute(&command).await.map_err(TetheringError::Exec)?;
+    on_switched();
+
+    let deadline = tokio::time::Instant::now() + SETTLE_TIMEOUT;
+    loop {
+        let status = tethering_status(backend).await.map_err(TetheringError::Exec)?;
+        if status.active(target) == enable {
+            return Ok(status);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(TetheringError::Unchanged(target, status));
+        }
+        tokio::time::sleep(SETTLE_POLL).await;
+    }
+}
+
+/// GET /tethering - What is tethered, the connected clients and bytes moved
+pub async fn get_tethering(
+    req: HttpRequest,
+    auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+) -> Result<HttpResponse> {
+    // Verify authentication (no body for GET)
+    auth.verify_request(&req, &[])?;
+
+    match tethering_status(backend.get_ref().as_ref()).await {
+        Ok(tethering) => Ok(HttpResponse::Ok().json(TetheringResponse {
+            success: true,
+            tethering: Some(tethering),
+            message: None,
+            error_code: None,
+        })),
+        Err(e) => Ok(HttpResponse::build(super::error_status(&e)).json(TetheringResponse {
+            success: false,
+            tethering: None,
+            message: Some(format!("Failed to read tethering state: {}", e)),
+            error_code: Some(e.code()),
+        })),
+    }
+}
+
+/// POST /tethering - Start or stop the Wi-Fi hotspot or USB tethering
+pub async fn set_tethering(
+    req: HttpRequest,
+    body: web::Json<TetheringRequest>,
+    auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+    config: web::Data<Arc<TetheringConfig>>,
+    lockout: web::Data<Arc<LockoutGuard>>,
+) -> Result<HttpResponse> {
+    // Extract the inner value for HMAC verification and later use
+    let tethering_request = body.into_inner();
+
+    // Serialize body for HMAC verification
+    let body_bytes = serde_json::to_vec(&tethering_request)
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid JSON: {}", e)))?;
+
+    // Verify authentication
+    auth.verify_request(&req, &body_bytes)?;
+
+    let (target, enable) = (tethering_request.target, tethering_request.enable);
+    let change = RadioChange::Tethering { kind: target.kind(), enable };
+    let peer = req.peer_addr().map(|addr| addr.ip());
+    let revert_after = match lockout.check(peer, &change, tethering_request.revert_after_secs).await {
+        Ok(revert_after) => revert_after,
+        Err(e) => {
+            return Ok(HttpResponse::build(super::radio::lockout_status(&e)).json(TetheringControlResponse {
+                success: false,
+                message: e.to_string(),
+                tethering: None,
+                revert: None,
+                error_code: Some(e.code()),
+            }));
+        }
+    };
+    // Restarting a stopped hotspot needs one in the config; find out before stopping it
+    let inverse = match revert_after.map(|_| tethering_command(&config, target, !enable)).transpose() {
+        Ok(inverse) => inverse,
+        Err(e) => {
+            return Ok(HttpResponse::build(e.status()).json(TetheringControlResponse {
+                success: false,
+                message: e.to_string(),
+                tethering: None,
+                revert: None,
+                error_code: Some(e.code()),
+            }));
+        }
+    };
+
+    // Scheduled once the command succeeds, so it also covers tethering that
+    // does not report the change in time
+    let mut revert = None;
+    let on_switched = || {
+        revert = revert_after.zip(inverse).map(|(after, inverse)| lockout.schedule(inverse, after));
+    };
+    match change_tethering(backend.get_ref().as_ref(), &config, target, enable, on_switched).await {
+        Ok(tethering) => Ok(HttpResponse::Ok().json(TetheringControlResponse {
+            success: true,
+            message: format!("{} {}", target.name(), if enable { "started" } else { "stopped" }),
+            tethering: Some(tethering),
+            revert,
+            error_code: None,
+        })),
+        Err(e) => Ok(HttpResponse::build(e.status()).json(TetheringControlResponse {
+            success: false,
+            message: e.to_string(),
+            error_code: Some(e.code()),
+            revert,
+            tethering: e.tethering(),
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{WifiNetwork, WifiSecurity};
+    use crate::executor::simulator::SimulatedBackend;
+
+    #[tokio::test]
+    async fn test_change_tethering() {
+        let backend = SimulatedBackend::new();
+        let mut switched = 0;
+        let no_hotspot =
+            change_tethering(&backend, &TetheringConfig::default(), TetheringTarget::Hotspot, true, || switched += 1)
+                .await;
+        assert_eq!(no_hotspot.unwrap_err().code(), "disabled");
+
+        let config = TetheringConfig {
+            hotspot: Some(WifiNetwork {
+                ssid: "OhMyPhone".to_string(),
+                security: WifiSecurity::Wpa2,
+                passphrase: Some("correct horse".to_string()),
+            }),
+            ..Default::default()
+        };
+        let on = change_tethering(&backend, &config, TetheringTarget::Hotspot, true, || switched += 1).await.unwrap();
+        assert!(on.hotspot && !on.usb);
+        assert_eq!(on.upstream, ["rmnet_data2"]);
+        // Only clients of tethered interfaces
+        assert_eq!(on.clients.len(), 1);
+        assert_eq!(on.clients[0].interface, "wlan1");
+        assert!(on.interfaces[0].tx_bytes > 0);
+
+        let both = change_tethering(&backend, &config, TetheringTarget::Usb, true, || switched += 1).await.unwrap();
+        assert!(both.hotspot && both.usb);
+        assert_eq!(both.clients.len(), 2);
+        // Already running, nothing to revert
+        change_tethering(&backend, &config, TetheringTarget::Usb, true, || switched += 1).await.unwrap();
+        assert_eq!(switched, 2);
+
+        let off = change_tethering(&backend, &config, TetheringTarget::Hotspot, false, || switched += 1).await.unwrap();
+        assert!(!off.hotspot && off.usb);
+        assert_eq!(switched, 3);
+    }
+}
@@ -0,0 +1,120 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::auth::AuthService;
+use crate::executor::shell::DEFAULT_SUB_ID;
+use crate::executor::Backend;
+use crate::ussd::{UssdError, UssdReply, UssdService};
+use super::sim::SimSelector;
+
+#[derive(Deserialize, Serialize)]
+pub struct UssdRequest {
+    code: String,
+    #[serde(flatten)]
+    target: SimSelector,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct UssdReplyRequest {
+    /// Menu choice, e.g. "1"
+    input: String,
+}
+
+#[derive(Serialize)]
+pub struct UssdResponse {
+    success: bool,
+    /// Text the network answered with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    reply: Option<String>,
+    /// Set while the network waits for a menu choice; answer with POST /ussd/{session_id}
+    #[serde(skip_serializing_if = "Option::is_none")]
+    session_id: Option<u64>,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+impl UssdResponse {
+    fn error(message: String, error_code: &'static str) -> Self {
+        Self { success: false, reply: None, session_id: None, message, error_code: Some(error_code) }
+    }
+}
+
+/// HTTP status for a USSD failure
+pub fn error_status(error: &UssdError) -> StatusCode {
+    match error {
+        UssdError::Disabled => StatusCode::SERVICE_UNAVAILABLE,
+        UssdError::NotAllowed(_) => StatusCode::FORBIDDEN,
+        UssdError::InvalidInput(_) => StatusCode::BAD_REQUEST,
+        UssdError::UnknownSession(_) => StatusCode::NOT_FOUND,
+        UssdError::Busy(_) => StatusCode::CONFLICT,
+        UssdError::Failed => StatusCode::BAD_GATEWAY,
+        UssdError::NoReply => StatusCode::GATEWAY_TIMEOUT,
+        UssdError::Exec(e) => super::error_status(e),
+    }
+}
+
+fn ussd_response(result: std::result::Result<UssdReply, UssdError>) -> HttpResponse {
+    match result {
+        Ok(reply) => HttpResponse::Ok().json(UssdResponse {
+            success: true,
+            message: match reply.session_id {
+                Some(id) => format!("USSD menu open, answer with POST /ussd/{}", id),
+                None => "USSD complete".to_string(),
+            },
+            reply: Some(reply.message),
+            session_id: reply.session_id,
+            error_code: None,
+        }),
+        Err(e) => HttpResponse::build(error_status(&e)).json(UssdResponse::error(e.to_string(), e.code())),
+    }
+}
+
+/// POST /ussd - Run an allowlisted USSD code and return the network's reply
+pub async fn run_ussd(
+    req: HttpRequest,
+    body: web::Json<UssdRequest>,
+    auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+    ussd: web::Data<Arc<UssdService>>,
+) -> Result<HttpResponse> {
+    // Extract the inner value for HMAC verification and later use
+    let ussd_request = body.into_inner();
+
+    // Serialize body for HMAC verification
+    let body_bytes = serde_json::to_vec(&ussd_request)
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid JSON: {}", e)))?;
+
+    // Verify authentication
+    auth.verify_request(&req, &body_bytes)?;
+
+    let sub_id = match ussd_request.target.resolve(backend.get_ref().as_ref()).await {
+        Ok(selected) => selected.map_or(DEFAULT_SUB_ID, |sim| sim.subscription.id),
+        Err(e) => return Ok(HttpResponse::build(e.status()).json(UssdResponse::error(e.to_string(), e.code()))),
+    };
+
+    Ok(ussd_response(ussd.run(&ussd_request.code, sub_id).await))
+}
+
+/// POST /ussd/{session_id} - Answer the menu of an open USSD session
+pub async fn reply_ussd(
+    req: HttpRequest,
+    path: web::Path<u64>,
+    body: web::Json<UssdReplyRequest>,
+    auth: web::Data<Arc<AuthService>>,
+    ussd: web::Data<Arc<UssdService>>,
+) -> Result<HttpResponse> {
+    // Extract the inner value for HMAC verification and later use
+    let reply_request = body.into_inner();
+
+    // Serialize body for HMAC verification
+    let body_bytes = serde_json::to_vec(&reply_request)
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid JSON: {}", e)))?;
+
+    // Verify authentication
+    auth.verify_request(&req, &body_bytes)?;
+
+    Ok(ussd_response(ussd.reply(path.into_inner(), &reply_request.input).await))
+}
@@ -0,0 +1,360 @@
+use actix_web::http::StatusCode;
+use actix_web::{web, HttpRequest, HttpResponse, Result};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::auth::AuthService;
+use crate::config::WifiConfig;
+use crate::executor::shell::{ExecError, ShellCommand};
+use crate::executor::wifi::{self, WifiStatus};
+use crate::executor::Backend;
+use crate::lockout::{LockoutError, LockoutGuard, PendingRevert, RadioChange};
+
+/// How long to wait for Wi-Fi to switch on or off
+const SETTLE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long to wait for the phone to associate and get an address
+const JOIN_TIMEOUT: Duration = Duration::from_secs(15);
+const SETTLE_POLL: Duration = Duration::from_millis(500);
+
+#[derive(Serialize)]
+pub struct WifiResponse {
+    success: bool,
+    #[serde(flatten)]
+    wifi: Option<WifiStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct WifiRequest {
+    enable: bool,
+    /// Join this network from the `[wifi]` config once enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ssid: Option<String>,
+    /// Undo switching Wi-Fi on or off after this many seconds unless confirmed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    revert_after_secs: Option<u64>,
+}
+
+#[derive(Serialize)]
+pub struct WifiControlResponse {
+    success: bool,
+    message: String,
+    /// State after the change, also when it did not take
+    #[serde(skip_serializing_if = "Option::is_none")]
+    wifi: Option<WifiStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    revert: Option<PendingRevert>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error_code: Option<&'static str>,
+}
+
+/// A Wi-Fi change that could not be carried out
+#[derive(Debug)]
+pub enum WifiError {
+    Exec(ExecError),
+    InvalidRequest(&'static str),
+    /// The SSID is not in the `[wifi]` config
+    NotAllowed(String),
+    /// Wi-Fi did not switch on or off in time
+    Unchanged(WifiStatus),
+    /// The phone did not connect to the network in time
+    NotJoined(String, WifiStatus),
+}
+
+impl WifiError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            WifiError::Exec(e) => super::error_status(e),
+            WifiError::InvalidRequest(_) => StatusCode::BAD_REQUEST,
+            WifiError::NotAllowed(_) => StatusCode::FORBIDDEN,
+            WifiError::Unchanged(_) | WifiError::NotJoined(..) => StatusCode::GATEWAY_TIMEOUT,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            WifiError::Exec(e) => e.code(),
+            WifiError::InvalidRequest(_) => "invalid_request",
+            WifiError::NotAllowed(_) => "not_allowed",
+            WifiError::Unchanged(_) => "wifi_unchanged",
+            WifiError::NotJoined(..) => "wifi_not_joined",
+        }
+    }
+
+    fn wifi(self) -> Option<WifiStatus> {
+        match self {
+            WifiError::Unchanged(wifi) | WifiError::NotJoined(_, wifi) => Some(wifi),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for WifiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WifiError::Exec(e) => write!(f, "Failed to control Wi-Fi: {}", e),
+            WifiError::InvalidRequest(detail) => write!(f, "{}", detail),
+            WifiError::NotAllowed(ssid) => write!(f, "Wi-Fi network {:?} is not in the config", ssid),
+            WifiError::Unchanged(wifi) => {
+                write!(f, "Wi-Fi is still {}", if wifi.enabled { "enabled" } else { "disabled" })
+            }
+            WifiError::NotJoined(ssid, _) => write!(f, "Wi-Fi has not joined {:?}", ssid),
+        }
+    }
+}
+
+/// Read the Wi-Fi state, with the interface address when the status leaves it out
+pub async fn wifi_status(backend: &dyn Backend) -> Result<WifiStatus, ExecError> {
+    let output = backend.execute(&ShellCommand::GetWifiStatus).await?;
+    let mut status = wifi::parse_wifi_status(&output)
+        .ok_or_else(|| ExecError::Parse("no Wifi is enabled/disabled line in cmd wifi status".to_string()))?;
+    if status.connected && status.ip.is_none() {
+        // The address only fills a gap, the status alone is enough
+        match backend.execute(&ShellCommand::GetWifiAddress).await {
+            Ok(output) => status.ip = wifi::parse_interface_address(&output),
+            Err(e) => warn!("Failed to read the wlan0 address: {}", e),
+        }
+    }
+    Ok(status)
+}
+
+/// Poll the Wi-Fi state until `done`, or return what it is at the deadline
+async fn settle(
+    backend: &dyn Backend,
+    timeout: Duration,
+    done: impl Fn(&WifiStatus) -> bool,
+) -> Result<Result<WifiStatus, WifiStatus>, ExecError> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let status = wifi_status(backend).await?;
+        if done(&status) {
+            return Ok(Ok(status));
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Ok(Err(status));
+        }
+        tokio::time::sleep(SETTLE_POLL).await;
+    }
+}
+
+/// Switch Wi-Fi on or off and optionally join a configured network, waiting
+/// until the phone reports the change. `on_switched` runs as soon as Wi-Fi is
+/// switched on or off, or else as soon as it starts joining the network, and
+/// not at all when nothing changes.
+pub async fn change_wifi(
+    backend: &dyn Backend,
+    config: &WifiConfig,
+    enable: bool,
+    ssid: Option<&str>,
+    on_switched: impl FnOnce(),
+) -> Result<WifiStatus, WifiError> {
+    let network = match ssid {
+        Some(_) if !enable => return Err(WifiError::InvalidRequest("A network can only be joined with enable: true")),
+        Some(ssid) => Some(config.network(ssid).ok_or_else(|| WifiError::NotAllowed(ssid.to_string()))?),
+        None => None,
+    };
+
+    let mut on_switched = Some(on_switched);
+    let mut status = wifi_status(backend).await.map_err(WifiError::Exec)?;
+    if status.enabled != enable {
+        let command = if enable { ShellCommand::EnableWifi } else { ShellCommand::DisableWifi };
+        backend.execute(&command).await.map_err(WifiError::Exec)?;
+        if let Some(on_switched) = on_switched.take() {
+            on_switched();
+        }
+        status = settle(backend, SETTLE_TIMEOUT, |s| s.enabled == enable)
+            .await
+            .map_err(WifiError::Exec)?
+            .map_err(WifiError::Unchanged)?;
+    }
+
+    let Some(network) = network else { return Ok(status) };
+    let joined = |s: &WifiStatus| s.connected && s.ssid.as_deref() == Some(network.ssid.as_str());
+    if joined(&status) {
+        return Ok(status);
+    }
+    backend.execute(&ShellCommand::ConnectWifi(network.clone())).await.map_err(WifiError::Exec)?;
+    if let Some(on_switched) = on_switched.take() {
+        on_switched();
+    }
+    settle(backend, JOIN_TIMEOUT, joined)
+        .await
+        .map_err(WifiError::Exec)?
+        .map_err(|status| WifiError::NotJoined(network.ssid.clone(), status))
+}
+
+/// GET /wifi - Wi-Fi state, SSID, signal, link speed and address
+pub async fn get_wifi(
+    req: HttpRequest,
+    auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+) -> Result<HttpResponse> {
+    // Verify authentication (no body for GET)
+    auth.verify_request(&req, &[])?;
+
+    match wifi_status(backend.get_ref().as_ref()).await {
+        Ok(wifi) => Ok(HttpResponse::Ok().json(WifiResponse {
+            success: true,
+            wifi: Some(wifi),
+            message: None,
+            error_code: None,
+        })),
+        Err(e) => Ok(HttpResponse::build(super::error_status(&e)).json(WifiResponse {
+            success: false,
+            wifi: None,
+            message: Some(format!("Failed to read Wi-Fi status: {}", e)),
+            error_code: Some(e.code()),
+        })),
+    }
+}
+
+/// POST /wifi - Enable or disable Wi-Fi, or join a network from the config
+pub async fn set_wifi(
+    req: HttpRequest,
+    body: web::Json<WifiRequest>,
+    auth: web::Data<Arc<AuthService>>,
+    backend: web::Data<Arc<dyn Backend>>,
+    config: web::Data<Arc<WifiConfig>>,
+    lockout: web::Data<Arc<LockoutGuard>>,
+) -> Result<HttpResponse> {
+    // Extract the inner value for HMAC verification and later use
+    let wifi_request = body.into_inner();
+
+    // Serialize body for HMAC verification
+    let body_bytes = serde_json::to_vec(&wifi_request)
+        .map_err(|e| actix_web::error::ErrorBadRequest(format!("Invalid JSON: {}", e)))?;
+
+    // Verify authentication
+    auth.verify_request(&req, &body_bytes)?;
+
+    let enable = wifi_request.enable;
+    let ssid = wifi_request.ssid.as_deref();
+    // Wi-Fi before a join, to tell whether it leaves another network
+    let before = match ssid {
+        Some(_) if enable => match wifi_status(backend.get_ref().as_ref()).await {
+            Ok(status) => Some(status),
+            Err(e) => {
+                return Ok(HttpResponse::build(super::error_status(&e)).json(WifiControlResponse {
+                    success: false,
+                    message: format!("Failed to read Wi-Fi state: {}", e),
+                    wifi: None,
+                    revert: None,
+                    error_code: Some(e.code()),
+                }));
+            }
+        },
+        _ => None,
+    };
+    let previous = before.as_ref().filter(|status| status.connected).and_then(|status| status.ssid.clone());
+    let previous = previous.filter(|current| Some(current.as_str()) != ssid);
+    // Leaving a network is undone by joining it again, which needs it in the config.
+    // Joining while Wi-Fi is on without a network leaves nothing to go back to.
+    let inverse = match (&previous, &before) {
+        (Some(previous), _) => config.network(previous).cloned().map(ShellCommand::ConnectWifi),
+        (None, Some(before)) if before.enabled => None,
+        (None, _) if enable => Some(ShellCommand::DisableWifi),
+        (None, _) => Some(ShellCommand::EnableWifi),
+    };
+    let change = RadioChange::Wifi { enable, join: previous.as_ref().and(ssid).map(str::to_string) };
+    let peer = req.peer_addr().map(|addr| addr.ip());
+    let checked = match lockout.check(peer, &change, wifi_request.revert_after_secs).await {
+        Ok(Some(_)) if previous.is_some() && inverse.is_none() => Err(LockoutError::InvalidRequest(format!(
+            "Wi-Fi cannot be reverted to {:?}, which is not in the config",
+            previous.as_deref().unwrap_or_default()
+        ))),
+        checked => checked,
+    };
+    let revert_after = match checked {
+        Ok(revert_after) => revert_after,
+        Err(e) => {
+            return Ok(HttpResponse::build(super::radio::lockout_status(&e)).json(WifiControlResponse {
+                success: false,
+                message: e.to_string(),
+                wifi: None,
+                revert: None,
+                error_code: Some(e.code()),
+            }));
+        }
+    };
+
+    // Scheduled once Wi-Fi switches or starts joining, so a join that fails afterwards is covered too
+    let mut revert = None;
+    let on_switched = || {
+        revert = revert_after.zip(inverse).map(|(after, inverse)| lockout.schedule(inverse, after));
+    };
+    match change_wifi(backend.get_ref().as_ref(), &config, enable, ssid, on_switched).await {
+        Ok(wifi) => Ok(HttpResponse::Ok().json(WifiControlResponse {
+            success: true,
+            message: match ssid {
+                Some(ssid) => format!("Wi-Fi connected to {}", ssid),
+                None => format!("Wi-Fi {}", if enable { "enabled" } else { "disabled" }),
+            },
+            wifi: Some(wifi),
+            revert,
+            error_code: None,
+        })),
+        Err(e) => Ok(HttpResponse::build(e.status()).json(WifiControlResponse {
+            success: false,
+            message: e.to_string(),
+            error_code: Some(e.code()),
+            revert,
+            wifi: e.wifi(),
+        })),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{WifiNetwork, WifiSecurity};
+    use crate::executor::simulator::SimulatedBackend;
+
+    #[tokio::test]
+    async fn test_change_wifi() {
+        let backend = SimulatedBackend::new();
+        let config = WifiConfig {
+            networks: vec![
+                WifiNetwork {
+                    ssid: "HomeNet".to_string(),
+                    security: WifiSecurity::Wpa2,
+                    passphrase: Some("correct horse".to_string()),
+                },
+                WifiNetwork { ssid: "Office".to_string(), security: WifiSecurity::Open, passphrase: None },
+            ],
+        };
+
+        let mut switched = 0;
+        let off = change_wifi(&backend, &config, false, None, || switched += 1).await.unwrap();
+        assert!(!off.enabled && !off.connected);
+        assert_eq!(switched, 1);
+
+        let unknown = change_wifi(&backend, &config, true, Some("CoffeeShop"), || switched += 1).await.unwrap_err();
+        assert_eq!(unknown.code(), "not_allowed");
+        let disabling = change_wifi(&backend, &config, false, Some("HomeNet"), || switched += 1).await.unwrap_err();
+        assert_eq!(disabling.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(switched, 1);
+
+        // Enables Wi-Fi on the way
+        let home = change_wifi(&backend, &config, true, Some("HomeNet"), || switched += 1).await.unwrap();
+        assert!(home.enabled && home.connected);
+        assert_eq!(home.ssid.as_deref(), Some("HomeNet"));
+        assert_eq!(home.ip.as_deref(), Some("192.168.232.2"));
+        assert_eq!(switched, 2);
+        // Already on, nothing to revert
+        change_wifi(&backend, &config, true, None, || switched += 1).await.unwrap();
+        change_wifi(&backend, &config, true, Some("HomeNet"), || switched += 1).await.unwrap();
+        assert_eq!(switched, 2);
+        // Leaving HomeNet for another network is a change too
+        let office = change_wifi(&backend, &config, true, Some("Office"), || switched += 1).await.unwrap();
+        assert_eq!(office.ssid.as_deref(), Some("Office"));
+        assert_eq!(switched, 3);
+
+        assert!(format!("{:?}", config.networks[0]).contains("***"));
+    }
+}
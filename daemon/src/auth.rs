@@ -73,11 +73,7 @@ impl AuthService {
         }
 
         // Compute expected HMAC
-        let message = [body, time_header.as_bytes()].concat();
-        let mut mac = HmacSha256::new_from_slice(&self.secret)
-            .map_err(|_| error::ErrorInternalServerError("HMAC initialization failed"))?;
-        mac.update(&message);
-        let expected = hex::encode(mac.finalize().into_bytes());
+        let expected = self.sign(body, time_header);
 
         // Compare HMAC
         if expected != auth_header {
@@ -86,6 +82,14 @@ impl AuthService {
 
         Ok(())
     }
+
+    /// HMAC over body and timestamp, as sent in X-Auth. Also signs the
+    /// requests the daemon makes itself, e.g. to the SMS webhook.
+    pub fn sign(&self, body: &[u8], timestamp: &str) -> String {
+        let mut mac = HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(&[body, timestamp.as_bytes()].concat());
+        hex::encode(mac.finalize().into_bytes())
+    }
 }
 
 #[cfg(test)]
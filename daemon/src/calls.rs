@@ -0,0 +1,218 @@
+//! Call state machine, fed with `mCallState` samples by the status sampler
+
+use serde::Serialize;
+
+use crate::executor::telephony::{CallReading, CallState};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallDirection {
+    Incoming,
+    /// Went offhook without ringing first
+    Outgoing,
+}
+
+/// The phone's current call, or the call that just ended in an event
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CallStatus {
+    pub state: CallState,
+    /// SIM slot of the call, null when idle
+    pub slot: Option<usize>,
+    pub direction: Option<CallDirection>,
+    /// Caller of an incoming call, when the phone reports it
+    pub number: Option<String>,
+    /// Unix ms at which the call started ringing or was dialed
+    pub started_at: Option<i64>,
+    /// Unix ms at which an incoming call was picked up
+    pub answered_at: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ended_at: Option<i64>,
+    /// Unix ms of the last state change
+    pub since: i64,
+}
+
+impl CallStatus {
+    fn idle(since: i64) -> Self {
+        Self {
+            state: CallState::Idle,
+            slot: None,
+            direction: None,
+            number: None,
+            started_at: None,
+            answered_at: None,
+            ended_at: None,
+            since,
+        }
+    }
+}
+
+/// One state change, as pushed to /call/events
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CallEvent {
+    /// Increases by one per event while the daemon runs
+    pub id: u64,
+    pub from: CallState,
+    pub to: CallState,
+    /// The call after the change; when `to` is idle, the call that ended
+    pub call: CallStatus,
+    /// The call rang and ended without being answered
+    pub missed: bool,
+}
+
+/// Turns call state samples into transitions. A call that starts and ends
+/// between two samples is not seen.
+pub struct CallTracker {
+    status: CallStatus,
+    next_id: u64,
+}
+
+impl CallTracker {
+    pub fn new(now: i64) -> Self {
+        Self { status: CallStatus::idle(now), next_id: 1 }
+    }
+
+    pub fn status(&self) -> &CallStatus {
+        &self.status
+    }
+
+    /// Feed one sample, one reading per phone section in slot order.
+    /// Returns the transitions it caused, oldest first.
+    pub fn observe(&mut self, readings: &[CallReading], now: i64) -> Vec<CallEvent> {
+        // The busiest SIM wins; stay with the SIM of the current call while it is as busy
+        let busiest = readings.iter().map(|r| r.state).max().unwrap_or(CallState::Idle);
+        let slot = self
+            .status
+            .slot
+            .filter(|&slot| readings.get(slot).is_some_and(|r| r.state == busiest))
+            .or_else(|| readings.iter().position(|r| r.state == busiest));
+        let number = slot.and_then(|slot| readings[slot].incoming_number.clone());
+
+        let mut events = Vec::new();
+        let current = self.status.state;
+        let same_call = current == CallState::Idle || self.status.slot == slot;
+        match (current, busiest) {
+            (from, to) if from == to && same_call => {
+                // Some builds fill in the caller a sample after the ringing starts
+                if to == CallState::Ringing && self.status.number.is_none() {
+                    self.status.number = number;
+                }
+            }
+            (CallState::Ringing, CallState::Offhook) if same_call => {
+                self.status.state = CallState::Offhook;
+                self.status.answered_at = Some(now);
+                self.status.since = now;
+                events.push(self.event(CallState::Ringing, self.status.clone(), false));
+            }
+            (from, to) => {
+                if from != CallState::Idle {
+                    events.push(self.end(now));
+                }
+                if to != CallState::Idle {
+                    let direction =
+                        if to == CallState::Ringing { CallDirection::Incoming } else { CallDirection::Outgoing };
+                    self.status = CallStatus {
+                        state: to,
+                        slot,
+                        direction: Some(direction),
+                        number: if direction == CallDirection::Incoming { number } else { None },
+                        started_at: Some(now),
+                        answered_at: None,
+                        ended_at: None,
+                        since: now,
+                    };
+                    events.push(self.event(CallState::Idle, self.status.clone(), false));
+                }
+            }
+        }
+        events
+    }
+
+    /// End the current call and go idle
+    fn end(&mut self, now: i64) -> CallEvent {
+        let from = self.status.state;
+        let ended = CallStatus { state: CallState::Idle, ended_at: Some(now), since: now, ..self.status.clone() };
+        let missed = ended.direction == Some(CallDirection::Incoming) && ended.answered_at.is_none();
+        self.status = CallStatus::idle(now);
+        self.event(from, ended, missed)
+    }
+
+    fn event(&mut self, from: CallState, call: CallStatus, missed: bool) -> CallEvent {
+        let id = self.next_id;
+        self.next_id += 1;
+        CallEvent { id, from, to: call.state, call, missed }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reading(state: CallState, number: Option<&str>) -> CallReading {
+        CallReading { state, incoming_number: number.map(str::to_string) }
+    }
+
+    fn idle() -> CallReading {
+        reading(CallState::Idle, None)
+    }
+
+    #[test]
+    fn test_incoming_call_answered() {
+        let mut tracker = CallTracker::new(0);
+        assert!(tracker.observe(&[idle()], 1_000).is_empty());
+
+        let events = tracker.observe(&[reading(CallState::Ringing, Some("+15550100"))], 2_000);
+        assert_eq!((events[0].from, events[0].to), (CallState::Idle, CallState::Ringing));
+        assert_eq!(events[0].call.number.as_deref(), Some("+15550100"));
+        assert_eq!(events[0].call.direction, Some(CallDirection::Incoming));
+        assert!(tracker.observe(&[reading(CallState::Ringing, Some("+15550100"))], 3_000).is_empty());
+
+        let events = tracker.observe(&[reading(CallState::Offhook, Some("+15550100"))], 4_000);
+        assert_eq!((events[0].from, events[0].to), (CallState::Ringing, CallState::Offhook));
+        assert_eq!(tracker.status().answered_at, Some(4_000));
+        assert_eq!(tracker.status().started_at, Some(2_000));
+
+        let events = tracker.observe(&[idle()], 9_000);
+        assert_eq!((events[0].from, events[0].to), (CallState::Offhook, CallState::Idle));
+        assert_eq!(events[0].call.ended_at, Some(9_000));
+        assert_eq!(events[0].call.number.as_deref(), Some("+15550100"));
+        assert!(!events[0].missed);
+        assert_eq!(events[0].id, 3);
+        assert_eq!(*tracker.status(), CallStatus::idle(9_000));
+    }
+
+    #[test]
+    fn test_missed_call_and_late_number() {
+        let mut tracker = CallTracker::new(0);
+        tracker.observe(&[reading(CallState::Ringing, None)], 1_000);
+        assert!(tracker.observe(&[reading(CallState::Ringing, Some("+15550100"))], 2_000).is_empty());
+        assert_eq!(tracker.status().number.as_deref(), Some("+15550100"));
+
+        let events = tracker.observe(&[idle()], 3_000);
+        assert!(events[0].missed);
+    }
+
+    #[test]
+    fn test_outgoing_call_on_second_sim() {
+        let mut tracker = CallTracker::new(0);
+        let events = tracker.observe(&[idle(), reading(CallState::Offhook, None)], 1_000);
+        assert_eq!(events[0].call.direction, Some(CallDirection::Outgoing));
+        assert_eq!(events[0].call.slot, Some(1));
+        assert_eq!(events[0].call.answered_at, None);
+
+        let events = tracker.observe(&[idle(), idle()], 2_000);
+        assert!(!events[0].missed);
+    }
+
+    #[test]
+    fn test_call_replaced_between_samples() {
+        // The call ended and a new one rang before the next sample
+        let mut tracker = CallTracker::new(0);
+        tracker.observe(&[reading(CallState::Offhook, None)], 1_000);
+        let events = tracker.observe(&[reading(CallState::Ringing, Some("+15550100"))], 2_000);
+        let transitions: Vec<_> = events.iter().map(|e| (e.from, e.to)).collect();
+        assert_eq!(
+            transitions,
+            vec![(CallState::Offhook, CallState::Idle), (CallState::Idle, CallState::Ringing)]
+        );
+    }
+}
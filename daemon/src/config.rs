@@ -1,4 +1,5 @@
 use serde::Deserialize;
+use std::fmt;
 use std::fs;
 
 #[derive(Debug, Deserialize, Clone)]
@@ -7,6 +8,20 @@ pub struct Config {
     pub security: SecurityConfig,
     #[allow(dead_code)]
     pub logging: LoggingConfig,
+    #[serde(default)]
+    pub executor: ExecutorConfig,
+    #[serde(default)]
+    pub sampler: SamplerConfig,
+    #[serde(default)]
+    pub forwarder: ForwarderConfig,
+    #[serde(default)]
+    pub ussd: UssdConfig,
+    #[serde(default)]
+    pub wifi: WifiConfig,
+    #[serde(default)]
+    pub tethering: TetheringConfig,
+    #[serde(default)]
+    pub lockout: LockoutConfig,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -28,6 +43,279 @@ pub struct LoggingConfig {
     pub file: String,
 }
 
+#[derive(Debug, Deserialize, Clone)]
+pub struct ExecutorConfig {
+    #[serde(default)]
+    pub backend: BackendKind,
+    /// Kill a command that runs longer than this
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    /// Stdout/stderr beyond this many bytes is discarded
+    #[serde(default = "default_max_output_bytes")]
+    pub max_output_bytes: usize,
+    /// How many commands may run at once
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+    /// Append every command and its output to this replay file
+    #[serde(default)]
+    pub record_to: Option<String>,
+    /// Replay file served by the replay backend
+    #[serde(default)]
+    pub replay_from: Option<String>,
+}
+
+impl Default for ExecutorConfig {
+    fn default() -> Self {
+        Self {
+            backend: BackendKind::default(),
+            timeout_ms: default_timeout_ms(),
+            max_output_bytes: default_max_output_bytes(),
+            max_concurrent: default_max_concurrent(),
+            record_to: None,
+            replay_from: None,
+        }
+    }
+}
+
+fn default_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_max_output_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_max_concurrent() -> usize {
+    4
+}
+
+/// Which backend runs the whitelisted commands
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// Real Android shell (dumpsys, svc, settings, ...)
+    #[default]
+    Shell,
+    /// In-memory phone for development off-device
+    Simulator,
+    /// Outputs recorded on a phone with `record_to`, served back in order
+    Replay,
+}
+
+/// How often the background sampler refreshes each /status field (seconds)
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct SamplerConfig {
+    /// Also return the unparsed `dumpsys battery` output, for debugging parsers
+    pub raw_battery: bool,
+    pub battery_secs: u64,
+    pub signal_secs: u64,
+    pub data_secs: u64,
+    pub airplane_secs: u64,
+    pub uptime_secs: u64,
+    pub call_forwarding_secs: u64,
+    pub subscriptions_secs: u64,
+    pub bluetooth_secs: u64,
+    /// Whether Wi-Fi is on, for `radios`. The cellular radio comes with the signal.
+    pub wifi_secs: u64,
+    /// Call state while a /call/events client is connected. It comes from the
+    /// same telephony.registry fetch as the signal, which otherwise only runs
+    /// every `signal_secs`.
+    pub call_events_secs: u64,
+}
+
+impl Default for SamplerConfig {
+    fn default() -> Self {
+        Self {
+            raw_battery: false,
+            battery_secs: 60,
+            signal_secs: 30,
+            data_secs: 15,
+            airplane_secs: 15,
+            uptime_secs: 300,
+            // Queries the network, keep it rare
+            call_forwarding_secs: 600,
+            // SIMs rarely change while the daemon runs
+            subscriptions_secs: 300,
+            bluetooth_secs: 30,
+            wifi_secs: 15,
+            call_events_secs: 5,
+        }
+    }
+}
+
+/// Pushing received SMS to /sms/events subscribers and a webhook
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct ForwarderConfig {
+    pub enabled: bool,
+    /// How often the SMS provider is checked for new texts (seconds), while a
+    /// webhook is set or an /sms/events client is connected
+    pub poll_secs: u64,
+    /// Holds the ID of the last forwarded text, so none are lost across restarts
+    pub cursor_file: String,
+    /// POST every received text here as JSON, signed like API requests (http:// only)
+    pub webhook_url: Option<String>,
+    pub webhook_timeout_ms: u64,
+    /// Regexes that find one-time codes; the first capture group is the code
+    pub otp_patterns: Vec<String>,
+}
+
+impl Default for ForwarderConfig {
+    fn default() -> Self {
+        Self {
+            // Each poll is a root content query, so only on request
+            enabled: false,
+            poll_secs: 15,
+            cursor_file: "/data/local/tmp/ohmyphone-sms-cursor".to_string(),
+            webhook_url: None,
+            webhook_timeout_ms: 5000,
+            otp_patterns: vec![
+                // "Your verification code is 123456", "OTP: 123-456"
+                r"(?i)(?:code|otp|pin|passcode|password)\D{0,20}?\b(\d{3}[- ]\d{3}|\d{4,8})\b".to_string(),
+                // "123456 is your login code"
+                r"(?i)\b(\d{4,8}) is your\b".to_string(),
+            ],
+        }
+    }
+}
+
+/// Running USSD codes through POST /ussd
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct UssdConfig {
+    /// Codes that may be run, exactly as dialed ("*100#"). Empty disables /ussd.
+    pub allowlist: Vec<String>,
+    /// How long to wait for the network's reply, also for call forwarding codes
+    pub reply_timeout_ms: u64,
+    /// Forget a menu session after this long without a reply (seconds)
+    pub session_timeout_secs: u64,
+}
+
+impl Default for UssdConfig {
+    fn default() -> Self {
+        Self { allowlist: Vec::new(), reply_timeout_ms: 15_000, session_timeout_secs: 60 }
+    }
+}
+
+/// Refusing radio changes that would cut off the connection a request came in on
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct LockoutConfig {
+    /// Check radio changes against the request's path; off, they go through as asked
+    pub enabled: bool,
+    /// Longest `revert_after_secs` a request may ask for
+    pub max_revert_secs: u64,
+}
+
+impl Default for LockoutConfig {
+    fn default() -> Self {
+        Self { enabled: true, max_revert_secs: 600 }
+    }
+}
+
+/// Networks POST /wifi may join
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct WifiConfig {
+    pub networks: Vec<WifiNetwork>,
+}
+
+impl WifiConfig {
+    pub fn network(&self, ssid: &str) -> Option<&WifiNetwork> {
+        self.networks.iter().find(|network| network.ssid == ssid)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        self.networks.iter().try_for_each(WifiNetwork::validate)
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct WifiNetwork {
+    pub ssid: String,
+    #[serde(default)]
+    pub security: WifiSecurity,
+    pub passphrase: Option<String>,
+}
+
+impl WifiNetwork {
+    /// Every network needs a passphrase unless it is open
+    pub fn validate(&self) -> Result<(), String> {
+        if self.ssid.is_empty() || self.ssid.len() > 32 {
+            return Err(format!("invalid SSID {:?}, 1 to 32 bytes", self.ssid));
+        }
+        let open = matches!(self.security, WifiSecurity::Open | WifiSecurity::Owe);
+        if open != self.passphrase.is_none() {
+            return Err(format!("{}: a passphrase is needed for wpa2/wpa3 and not allowed otherwise", self.ssid));
+        }
+        Ok(())
+    }
+}
+
+/// Commands are logged with Debug, keep the passphrase out. Replay files mask
+/// it in `ShellCommand::recorded_argv`.
+impl fmt::Debug for WifiNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WifiNetwork")
+            .field("ssid", &self.ssid)
+            .field("security", &self.security)
+            .field("passphrase", &self.passphrase.as_ref().map(|_| "***"))
+            .finish()
+    }
+}
+
+/// Security types `cmd wifi connect-network` takes
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WifiSecurity {
+    Open,
+    /// Enhanced open
+    Owe,
+    #[default]
+    Wpa2,
+    Wpa3,
+}
+
+impl WifiSecurity {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WifiSecurity::Open => "open",
+            WifiSecurity::Owe => "owe",
+            WifiSecurity::Wpa2 => "wpa2",
+            WifiSecurity::Wpa3 => "wpa3",
+        }
+    }
+}
+
+/// Hotspot and USB tethering through POST /tethering
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct TetheringConfig {
+    /// Name and passphrase the hotspot starts with. Without it only USB tethering works.
+    pub hotspot: Option<WifiNetwork>,
+    pub usb_function: UsbFunction,
+}
+
+/// USB gadget function that carries tethering
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum UsbFunction {
+    #[default]
+    Rndis,
+    /// Pixel 6 and later tether over NCM
+    Ncm,
+}
+
+impl UsbFunction {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            UsbFunction::Rndis => "rndis",
+            UsbFunction::Ncm => "ncm",
+        }
+    }
+}
+
 impl Config {
     pub fn load(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
         let contents = fs::read_to_string(path)?;
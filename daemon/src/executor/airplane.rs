@@ -0,0 +1,119 @@
+//! Which radios airplane mode switches off, from the `airplane_mode_radios`
+//! and `airplane_mode_toggleable_radios` global settings
+
+use serde::{Deserialize, Serialize};
+
+/// Radios airplane mode turns off when the setting is unset (`def_airplane_mode_radios`)
+pub const DEFAULT_RADIOS: &str = "cell,bluetooth,wifi,nfc,wimax";
+/// Radios the user may turn back on in airplane mode when the setting is unset
+pub const DEFAULT_TOGGLEABLE_RADIOS: &str = "bluetooth,wifi,nfc";
+
+/// A radio airplane mode can be told to leave alone (Settings.Global.RADIO_*).
+/// The cellular radio is always switched off, so it is not one of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Radio {
+    Wifi,
+    Bluetooth,
+    Nfc,
+}
+
+impl Radio {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Radio::Wifi => "wifi",
+            Radio::Bluetooth => "bluetooth",
+            Radio::Nfc => "nfc",
+        }
+    }
+}
+
+/// One of the two global settings that list radios
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RadioSetting {
+    /// Radios airplane mode switches off
+    Radios,
+    /// Radios that may be switched back on while in airplane mode
+    Toggleable,
+}
+
+impl RadioSetting {
+    pub fn key(self) -> &'static str {
+        match self {
+            RadioSetting::Radios => "airplane_mode_radios",
+            RadioSetting::Toggleable => "airplane_mode_toggleable_radios",
+        }
+    }
+
+    fn default_value(self) -> &'static str {
+        match self {
+            RadioSetting::Radios => DEFAULT_RADIOS,
+            RadioSetting::Toggleable => DEFAULT_TOGGLEABLE_RADIOS,
+        }
+    }
+}
+
+/// `settings get global <key>` output: the raw value, or None when unset (`null`)
+pub fn parse_setting(output: &str) -> Option<String> {
+    Some(output.trim()).filter(|value| *value != "null").map(String::from)
+}
+
+/// The setting with `keep` taken out of the radios airplane mode switches off,
+/// or added to those that may be switched back on. Unknown radios are left as they are.
+pub fn with_kept(setting: RadioSetting, current: Option<&str>, keep: &[Radio]) -> String {
+    let current = current.unwrap_or(setting.default_value());
+    let mut radios: Vec<&str> = current.split(',').map(str::trim).filter(|r| !r.is_empty()).collect();
+    match setting {
+        RadioSetting::Radios => radios.retain(|radio| !keep.iter().any(|k| k.as_str() == *radio)),
+        RadioSetting::Toggleable => {
+            for radio in keep {
+                if !radios.contains(&radio.as_str()) {
+                    radios.push(radio.as_str());
+                }
+            }
+        }
+    }
+    radios.join(",")
+}
+
+/// Whether a radio is switched off by airplane mode with this setting value
+pub fn is_sensitive(radios: Option<&str>, radio: Radio) -> bool {
+    radios.unwrap_or(DEFAULT_RADIOS).split(',').any(|r| r.trim() == radio.as_str())
+}
+
+/// Which radios are on, each None when it could not be read
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct RadioStatus {
+    pub cell: Option<bool>,
+    pub wifi: Option<bool>,
+    pub bluetooth: Option<bool>,
+}
+
+impl RadioStatus {
+    pub fn get(&self, radio: Radio) -> Option<bool> {
+        match radio {
+            Radio::Wifi => self.wifi,
+            Radio::Bluetooth => self.bluetooth,
+            Radio::Nfc => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_with_kept() {
+        assert_eq!(with_kept(RadioSetting::Radios, None, &[Radio::Wifi]), "cell,bluetooth,nfc,wimax");
+        let both = [Radio::Wifi, Radio::Bluetooth];
+        assert_eq!(with_kept(RadioSetting::Radios, Some("cell,wifi,bluetooth"), &both), "cell");
+        assert_eq!(with_kept(RadioSetting::Toggleable, Some("bluetooth"), &both), "bluetooth,wifi");
+        assert_eq!(with_kept(RadioSetting::Toggleable, Some(""), &[Radio::Nfc]), "nfc");
+
+        assert_eq!(parse_setting("null\n"), None);
+        assert_eq!(parse_setting("cell,bluetooth\n").as_deref(), Some("cell,bluetooth"));
+        assert!(is_sensitive(None, Radio::Wifi));
+        assert!(!is_sensitive(Some("cell,bluetooth"), Radio::Wifi));
+    }
+}
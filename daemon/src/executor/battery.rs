@@ -0,0 +1,158 @@
+//! Parser for `dumpsys battery`
+
+use serde::Serialize;
+
+/// BatteryManager.BATTERY_STATUS_*
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatteryStatus {
+    Unknown,
+    Charging,
+    Discharging,
+    NotCharging,
+    Full,
+}
+
+/// BatteryManager.BATTERY_HEALTH_*
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatteryHealth {
+    Unknown,
+    Good,
+    Overheat,
+    Dead,
+    OverVoltage,
+    UnspecifiedFailure,
+    Cold,
+}
+
+/// Where the phone is drawing power from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerSource {
+    Ac,
+    Usb,
+    Wireless,
+    Dock,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BatteryInfo {
+    /// Charge level in percent
+    pub level: i32,
+    pub status: BatteryStatus,
+    /// Charging or full, as reported by the legacy `charging` field
+    pub charging: bool,
+    /// None when unplugged
+    pub plugged: Option<PowerSource>,
+    pub health: BatteryHealth,
+    pub present: bool,
+    pub temperature_c: Option<f64>,
+    pub voltage_mv: Option<i32>,
+    /// e.g. "Li-ion"
+    pub technology: Option<String>,
+    /// Remaining charge in µAh
+    pub charge_counter_uah: Option<i64>,
+    /// Current the charger offers, in µA (0 when unplugged)
+    pub max_charging_current_ua: Option<i64>,
+}
+
+/// Parse `dumpsys battery`. None when there is no `level:` line.
+pub fn parse_battery_info(output: &str) -> Option<BatteryInfo> {
+    // First occurrence wins: vendors append their own duplicates
+    // (e.g. Samsung's lowercase "charge counter") further down
+    let value = |key: &str| {
+        output
+            .lines()
+            .filter_map(|line| line.trim().split_once(':'))
+            .find(|(k, _)| *k == key)
+            .map(|(_, v)| v.trim())
+    };
+    let number = |key: &str| value(key).and_then(|v| v.parse::<i64>().ok());
+    let flag = |key: &str| value(key) == Some("true");
+
+    let level = number("level")?;
+    let scale = number("scale").filter(|&s| s > 0).unwrap_or(100);
+    let status = match number("status") {
+        Some(2) => BatteryStatus::Charging,
+        Some(3) => BatteryStatus::Discharging,
+        Some(4) => BatteryStatus::NotCharging,
+        Some(5) => BatteryStatus::Full,
+        _ => BatteryStatus::Unknown,
+    };
+    let health = match number("health") {
+        Some(2) => BatteryHealth::Good,
+        Some(3) => BatteryHealth::Overheat,
+        Some(4) => BatteryHealth::Dead,
+        Some(5) => BatteryHealth::OverVoltage,
+        Some(6) => BatteryHealth::UnspecifiedFailure,
+        Some(7) => BatteryHealth::Cold,
+        _ => BatteryHealth::Unknown,
+    };
+    let plugged = [
+        ("AC powered", PowerSource::Ac),
+        ("USB powered", PowerSource::Usb),
+        ("Wireless powered", PowerSource::Wireless),
+        ("Dock powered", PowerSource::Dock),
+    ]
+    .into_iter()
+    .find(|(key, _)| flag(key))
+    .map(|(_, source)| source);
+
+    Some(BatteryInfo {
+        level: (level * 100 / scale) as i32,
+        status,
+        charging: matches!(status, BatteryStatus::Charging | BatteryStatus::Full),
+        plugged,
+        health,
+        present: value("present").is_none_or(|v| v == "true"),
+        // Reported in tenths of a degree Celsius
+        temperature_c: number("temperature").map(|t| t as f64 / 10.0),
+        voltage_mv: number("voltage").map(|v| v as i32),
+        technology: value("technology").filter(|t| !t.is_empty()).map(str::to_string),
+        charge_counter_uah: number("Charge counter"),
+        max_charging_current_ua: number("Max charging current"),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::fixtures::{expected_bool, expected_int, fixture_cases};
+
+    #[test]
+    fn test_parse_battery() {
+        let output = "Current Battery Service state:\n  AC powered: false\n  USB powered: true\n  status: 2\n  health: 2\n  level: 85\n  scale: 100\n";
+        let info = parse_battery_info(output).unwrap();
+        assert_eq!(info.level, 85);
+        assert!(info.charging);
+        assert_eq!(info.plugged, Some(PowerSource::Usb));
+
+        let output = "level: 42\nstatus: 4\nhealth: 3\n";
+        let info = parse_battery_info(output).unwrap();
+        assert_eq!(info.status, BatteryStatus::NotCharging);
+        assert_eq!(info.health, BatteryHealth::Overheat);
+        assert_eq!(info.temperature_c, None);
+
+        let info = parse_battery_info("  level: 82\n  status: 3\n").unwrap();
+        assert_eq!(info.level, 82);
+        assert!(!info.charging);
+
+        assert_eq!(parse_battery_info("Can't find service: battery"), None);
+    }
+
+    #[test]
+    fn test_fixture_battery() {
+        for (name, output, expected) in fixture_cases("battery") {
+            let info = parse_battery_info(&output).unwrap();
+            assert_eq!(info.level as i64, expected_int(&expected, "level"), "{}", name);
+            assert_eq!(info.charging, expected_bool(&expected, "charging"), "{}", name);
+
+            let Some(details) = expected.get("info") else { continue };
+            let parsed = serde_json::to_value(&info).unwrap();
+            for (key, value) in details.as_table().unwrap() {
+                assert_eq!(parsed[key], serde_json::to_value(value).unwrap(), "{}: {}", name, key);
+            }
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Parser for `dumpsys bluetooth_manager`: the adapter state, bonded devices
+//! and which of them a profile is connected to
+
+use serde::Serialize;
+
+/// Profile state machine states in which the device is connected
+const CONNECTED_STATES: [&str; 4] = ["Connected", "AudioOn", "AudioConnecting", "AudioDisconnecting"];
+
+/// A bonded (paired) device
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BluetoothDevice {
+    /// Android 13+ redacts all but the last two octets, e.g. `XX:XX:XX:XX:11:22`
+    pub address: String,
+    pub name: Option<String>,
+    /// Any profile (A2DP, headset, ...) is connected
+    pub connected: bool,
+}
+
+/// Adapter state and bonded devices
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BluetoothStatus {
+    pub enabled: bool,
+    /// Empty while Bluetooth is off, the adapter service is not bound then
+    pub devices: Vec<BluetoothDevice>,
+}
+
+/// Parse `dumpsys bluetooth_manager`. The `enabled:` line of the manager comes
+/// first; bonded devices are listed by the adapter properties, and connections
+/// show up as the profile state machines that dump the device they serve.
+pub fn parse_bluetooth_status(output: &str) -> Option<BluetoothStatus> {
+    let enabled = output.lines().find_map(|line| match line.trim().strip_prefix("enabled: ")? {
+        "true" => Some(true),
+        "false" => Some(false),
+        _ => None,
+    })?;
+
+    let mut devices = bonded_devices(output);
+    let mut device: Option<&str> = None;
+    for line in output.lines().map(str::trim) {
+        if let Some(address) = line.strip_prefix("mDevice: ").or_else(|| line.strip_prefix("mCurrentDevice: ")) {
+            device = Some(address);
+        } else if let (Some(address), Some((_, state))) = (device, line.split_once(" state=")) {
+            // "StateMachine: name=A2dpStateMachine state=Connected"
+            if CONNECTED_STATES.contains(&state.trim()) {
+                devices.iter_mut().filter(|d| d.address == address).for_each(|d| d.connected = true);
+            }
+        }
+    }
+    Some(BluetoothStatus { enabled, devices })
+}
+
+/// The `Bonded devices:` section of AdapterProperties, one device per line:
+/// `F4:4E:FC:11:22:33 [BR/EDR] Car Multimedia` (Android 11), or with the class
+/// of device after the type, `XX:XX:XX:XX:11:22 [BR/EDR][ 0x240404 ] Car Multimedia`
+fn bonded_devices(output: &str) -> Vec<BluetoothDevice> {
+    // AdapterService lists them before AdapterProperties on some releases, the last list is the complete one
+    let lines: Vec<&str> = output.lines().collect();
+    let Some(start) = lines.iter().rposition(|line| line.trim() == "Bonded devices:") else { return Vec::new() };
+    let indent = lines[start].len() - lines[start].trim_start().len();
+
+    lines[start + 1..]
+        .iter()
+        .take_while(|line| line.len() - line.trim_start().len() > indent)
+        .filter_map(|line| {
+            let line = line.trim();
+            let (address, rest) = line.split_once(' ').unwrap_or((line, ""));
+            if address.split(':').count() != 6 {
+                return None;
+            }
+            let name = rest.rsplit_once(']').map_or(rest, |(_, name)| name).trim();
+            Some(BluetoothDevice {
+                address: address.to_string(),
+                name: Some(name.to_string()).filter(|name| !name.is_empty() && name != "null"),
+                connected: false,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::fixtures::fixture_cases;
+
+    #[test]
+    fn test_fixture_bluetooth() {
+        for (name, output, expected) in fixture_cases("bluetooth") {
+            let status = parse_bluetooth_status(&output).unwrap();
+            assert_eq!(status.enabled, expected["enabled"].as_bool().unwrap(), "{}", name);
+            let devices: Vec<String> = status
+                .devices
+                .iter()
+                .map(|d| format!("{}|{}|{}", d.address, d.name.as_deref().unwrap_or(""), d.connected))
+                .collect();
+            let expected: Vec<&str> =
+                expected["devices"].as_array().unwrap().iter().map(|v| v.as_str().unwrap()).collect();
+            assert_eq!(devices, expected, "{}", name);
+        }
+        assert_eq!(parse_bluetooth_status("Can't find service: bluetooth_manager\n"), None);
+    }
+}
@@ -0,0 +1,126 @@
+//! Call forwarding MMI codes (3GPP TS 22.030) and the network's answers to them
+
+use serde::{Deserialize, Serialize};
+
+/// When calls are forwarded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ForwardingReason {
+    /// Always
+    #[default]
+    Unconditional,
+    /// While on another call
+    Busy,
+    /// When not answered within the timer
+    NoReply,
+    /// When the phone is off or out of coverage
+    Unreachable,
+}
+
+impl ForwardingReason {
+    pub const ALL: [ForwardingReason; 4] =
+        [ForwardingReason::Unconditional, ForwardingReason::Busy, ForwardingReason::NoReply, ForwardingReason::Unreachable];
+
+    /// Supplementary service code
+    fn service_code(self) -> &'static str {
+        match self {
+            ForwardingReason::Unconditional => "21",
+            ForwardingReason::Busy => "67",
+            ForwardingReason::NoReply => "61",
+            ForwardingReason::Unreachable => "62",
+        }
+    }
+
+    /// Activate with a number, e.g. `*61*+15550100**20#` for no reply after 20 seconds
+    pub fn enable_code(self, number: &str, timer: Option<u8>) -> String {
+        match timer {
+            Some(secs) if self == ForwardingReason::NoReply => format!("*{}*{}**{}#", self.service_code(), number, secs),
+            _ => format!("*{}*{}#", self.service_code(), number),
+        }
+    }
+
+    pub fn disable_code(self) -> String {
+        format!("#{}#", self.service_code())
+    }
+
+    /// Interrogate, e.g. `*#67#`
+    pub fn query_code(self) -> String {
+        format!("*#{}#", self.service_code())
+    }
+}
+
+/// No-reply timers the network accepts: 5 to 30 seconds in steps of 5
+pub fn is_valid_timer(secs: u8) -> bool {
+    (5..=30).contains(&secs) && secs.is_multiple_of(5)
+}
+
+/// Forwarding of voice calls for one reason, as the network reports it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ForwardingState {
+    pub reason: ForwardingReason,
+    pub active: bool,
+    pub number: Option<String>,
+    /// Seconds before a call is forwarded, no-reply forwarding only
+    pub timer: Option<u8>,
+}
+
+/// Parse the network's answer to an interrogation code. The phone formats it
+/// per service class ("Voice: +15550100 after 20 seconds", "Voice: Not
+/// forwarded"); only the voice class matters here.
+pub fn parse_interrogation(reason: ForwardingReason, text: &str) -> Option<ForwardingState> {
+    let inactive = ForwardingState { reason, active: false, number: None, timer: None };
+    // No class is forwarded
+    if text.trim().starts_with("Service has been disabled") {
+        return Some(inactive);
+    }
+    let voice = text.lines().find_map(|line| line.trim().strip_prefix("Voice: "))?;
+    if voice.starts_with("Not forwarded") {
+        return Some(inactive);
+    }
+    let (number, timer) = match voice.split_once(" after ") {
+        Some((number, delay)) => (number, delay.split_whitespace().next().and_then(|secs| secs.parse().ok())),
+        None => (voice, None),
+    };
+    Some(ForwardingState { reason, active: true, number: Some(number.trim().to_string()), timer })
+}
+
+/// Whether the network's answer to an activation or deactivation code means it took the change
+pub fn is_accepted(text: &str) -> bool {
+    const ACCEPTED: [&str; 4] =
+        ["Service was enabled", "Service has been disabled", "Registration was successful", "Erasure was successful"];
+    ACCEPTED.iter().any(|answer| text.trim().starts_with(answer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_codes() {
+        assert_eq!(ForwardingReason::Unconditional.enable_code("+15550100", None), "*21*+15550100#");
+        assert_eq!(ForwardingReason::NoReply.enable_code("+15550100", Some(20)), "*61*+15550100**20#");
+        assert_eq!(ForwardingReason::Busy.enable_code("+15550100", Some(20)), "*67*+15550100#");
+        assert_eq!(ForwardingReason::Unreachable.disable_code(), "#62#");
+        assert_eq!(ForwardingReason::Busy.query_code(), "*#67#");
+
+        assert!(is_valid_timer(5) && is_valid_timer(30));
+        assert!(!is_valid_timer(0) && !is_valid_timer(12) && !is_valid_timer(35));
+    }
+
+    #[test]
+    fn test_parse_interrogation() {
+        let no_reply = parse_interrogation(ForwardingReason::NoReply, "Voice: +15550100 after 20 seconds").unwrap();
+        assert_eq!((no_reply.active, no_reply.number.as_deref(), no_reply.timer), (true, Some("+15550100"), Some(20)));
+
+        let busy = parse_interrogation(ForwardingReason::Busy, "Data: Not forwarded\nVoice: +15550100").unwrap();
+        assert_eq!((busy.active, busy.number.as_deref(), busy.timer), (true, Some("+15550100"), None));
+
+        assert!(!parse_interrogation(ForwardingReason::Busy, "Voice: Not forwarded").unwrap().active);
+        assert!(!parse_interrogation(ForwardingReason::Busy, "Service has been disabled.").unwrap().active);
+        assert_eq!(parse_interrogation(ForwardingReason::Busy, "Connection problem or invalid MMI code."), None);
+
+        assert!(is_accepted("Registration was successful."));
+        assert!(is_accepted("Service has been disabled."));
+        assert!(!is_accepted("Connection problem or invalid MMI code."));
+    }
+}
@@ -0,0 +1,171 @@
+//! Call history read through the `content://call_log/calls` provider
+
+use serde::Serialize;
+
+use super::content::{self, ContentQuery, ContentUri};
+use super::telephony::Subscriptions;
+
+/// Cached contact name last: it is free text and may hold ", "
+const COLUMNS: &[&str] = &["_id", "number", "date", "duration", "type", "subscription_id", "new", "name"];
+
+/// CallLog.Calls.*_TYPE
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallType {
+    Incoming,
+    Outgoing,
+    Missed,
+    Voicemail,
+    Rejected,
+    Blocked,
+    /// Picked up on another device sharing the number
+    AnsweredExternally,
+    Unknown,
+}
+
+impl CallType {
+    fn from_code(code: Option<i64>) -> Self {
+        match code {
+            Some(1) => CallType::Incoming,
+            Some(2) => CallType::Outgoing,
+            Some(3) => CallType::Missed,
+            Some(4) => CallType::Voicemail,
+            Some(5) => CallType::Rejected,
+            Some(6) => CallType::Blocked,
+            Some(7) => CallType::AnsweredExternally,
+            _ => CallType::Unknown,
+        }
+    }
+
+    pub fn code(self) -> i32 {
+        match self {
+            CallType::Incoming => 1,
+            CallType::Outgoing => 2,
+            CallType::Missed => 3,
+            CallType::Voicemail => 4,
+            CallType::Rejected => 5,
+            CallType::Blocked => 6,
+            CallType::AnsweredExternally => 7,
+            CallType::Unknown => 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CallLogEntry {
+    pub id: i64,
+    /// Other party, null for private and unknown numbers
+    pub number: Option<String>,
+    /// Contact name the dialer cached for the number
+    pub name: Option<String>,
+    /// Unix ms at which the call started
+    pub date: i64,
+    /// Seconds the call was connected, 0 for missed and rejected calls
+    pub duration: i64,
+    pub kind: CallType,
+    /// Subscription the call was on, when the phone account is a subscription ID
+    pub subscription_id: Option<i32>,
+    /// SIM slot of that subscription, while the SIM is inserted
+    pub slot: Option<usize>,
+    /// Missed call the user has not looked at yet
+    pub new: bool,
+}
+
+/// Which calls to read
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CallLogFilter {
+    /// Only calls that started after this Unix ms
+    pub since: Option<i64>,
+}
+
+impl CallLogFilter {
+    /// Newest first. The selection holds only numbers formatted here.
+    pub fn query(&self) -> ContentQuery {
+        ContentQuery {
+            uri: ContentUri::CallLog,
+            projection: COLUMNS,
+            selection: self.since.map(|since| format!("date>{}", since)),
+            sort: Some("date DESC"),
+        }
+    }
+
+    /// Whether a parsed call passes the filter, for backends that ignore the selection
+    pub fn matches(&self, entry: &CallLogEntry) -> bool {
+        self.since.is_none_or(|since| entry.date > since)
+    }
+}
+
+/// Parse the output of a call log query. Rows without an id or date are skipped.
+pub fn parse_calls(output: &str) -> Vec<CallLogEntry> {
+    content::parse_rows(output, COLUMNS)
+        .into_iter()
+        .filter_map(|row| {
+            let number = |key: &str| row.get(key).and_then(|v| v.parse::<i64>().ok());
+            let text = |key: &str| row.get(key).filter(|v| !v.is_empty()).cloned();
+            Some(CallLogEntry {
+                id: number("_id")?,
+                // Private numbers are stored as "" or "-2" depending on the build
+                number: text("number").filter(|n| !n.starts_with('-')),
+                name: text("name"),
+                date: number("date")?,
+                duration: number("duration").unwrap_or(0),
+                kind: CallType::from_code(number("type")),
+                // Android 10 and older store the ICCID here, which is no subscription ID
+                subscription_id: row.get("subscription_id").and_then(|v| v.parse::<i32>().ok()).filter(|&id| id >= 0),
+                slot: None,
+                new: number("new") == Some(1),
+            })
+        })
+        .collect()
+}
+
+/// Fill in the slot of calls made on a SIM that is inserted now
+pub fn assign_slots(entries: &mut [CallLogEntry], subscriptions: &Subscriptions) {
+    for entry in entries {
+        entry.slot = entry.subscription_id.and_then(|id| subscriptions.by_id(id)).map(|s| s.slot);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::fixtures::{assert_fields, expected_int, fixture_cases};
+    use crate::executor::telephony::Subscription;
+
+    #[test]
+    fn test_filter() {
+        assert_eq!(CallLogFilter::default().query().selection, None);
+        let filter = CallLogFilter { since: Some(1709300000000) };
+        assert_eq!(filter.query().selection.as_deref(), Some("date>1709300000000"));
+        assert_eq!(filter.query().uri.as_str(), "content://call_log/calls");
+    }
+
+    #[test]
+    fn test_assign_slots() {
+        let mut calls = parse_calls(
+            "Row: 0 _id=1, number=+15550100, date=1, duration=0, type=3, subscription_id=2, new=1, name=NULL, _id=1, date=1\n\
+             Row: 1 _id=2, number=+15550100, date=1, duration=0, type=3, subscription_id=89014103211118510720, new=1, \
+             name=NULL, _id=2, date=1\n",
+        );
+        let subscriptions = Subscriptions {
+            active: vec![Subscription { id: 2, slot: 1, display_name: None, carrier_name: None }],
+            default_data: None,
+        };
+        assign_slots(&mut calls, &subscriptions);
+        assert_eq!((calls[0].subscription_id, calls[0].slot), (Some(2), Some(1)));
+        assert_eq!((calls[1].subscription_id, calls[1].slot), (None, None));
+    }
+
+    #[test]
+    fn test_fixture_call_log() {
+        for (name, output, expected) in fixture_cases("call_log") {
+            let calls = parse_calls(&output);
+            assert_eq!(calls.len() as i64, expected_int(&expected, "count"), "{}", name);
+
+            let Some(details) = expected.get("calls") else { continue };
+            for (i, table) in details.as_array().unwrap().iter().enumerate() {
+                assert_fields(&format!("{}[{}]", name, i), &calls[i], table.as_table().unwrap());
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+//! Which network a connection uses, from `ip route get` and the default
+//! network in `dumpsys connectivity`
+
+use serde::Serialize;
+
+/// What carries traffic on an interface
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    Cellular,
+    /// Wi-Fi, including the phone's own hotspot
+    Wifi,
+    Bluetooth,
+    Usb,
+    Ethernet,
+    /// A tunnel such as Tailscale, which rides on the default network
+    Vpn,
+    Loopback,
+    Other,
+}
+
+impl Transport {
+    /// Guess from the interface name. Tunnels are `tun0` for VPN apps like
+    /// Tailscale, `tailscale0` when it runs as a root daemon.
+    pub fn from_interface(name: &str) -> Self {
+        let starts = |prefixes: &[&str]| prefixes.iter().any(|prefix| name.starts_with(prefix));
+        if name == "lo" {
+            Transport::Loopback
+        } else if starts(&["tun", "tailscale", "wg", "ppp", "ipsec"]) {
+            Transport::Vpn
+        } else if starts(&["rmnet", "ccmni", "v4-rmnet", "v4-ccmni", "seth", "pdp"]) {
+            Transport::Cellular
+        } else if starts(&["wlan", "swlan", "softap", "ap_br_"]) {
+            Transport::Wifi
+        } else if name.starts_with("bt-pan") {
+            Transport::Bluetooth
+        } else if starts(&["rndis", "ncm", "usb"]) {
+            Transport::Usb
+        } else if name.starts_with("eth") {
+            Transport::Ethernet
+        } else {
+            Transport::Other
+        }
+    }
+
+    /// NetworkCapabilities transport names, as in `Transports: CELLULAR`
+    fn from_name(name: &str) -> Self {
+        match name {
+            "CELLULAR" => Transport::Cellular,
+            "WIFI" | "WIFI_AWARE" => Transport::Wifi,
+            "BLUETOOTH" => Transport::Bluetooth,
+            "USB" => Transport::Usb,
+            "ETHERNET" => Transport::Ethernet,
+            "VPN" => Transport::Vpn,
+            _ => Transport::Other,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Transport::Cellular => "cellular",
+            Transport::Wifi => "Wi-Fi",
+            Transport::Bluetooth => "Bluetooth",
+            Transport::Usb => "USB",
+            Transport::Ethernet => "Ethernet",
+            Transport::Vpn => "VPN",
+            Transport::Loopback => "loopback",
+            Transport::Other => "an unknown network",
+        }
+    }
+}
+
+/// An interface and what it runs over
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Link {
+    pub interface: String,
+    pub transport: Transport,
+}
+
+/// Parse `ip route get <address>`, e.g.
+/// `100.101.1.2 dev tun0 table 1021 src 100.80.1.1 uid 0`
+pub fn parse_route(output: &str) -> Option<Link> {
+    let mut words = output.split_whitespace();
+    words.find(|word| *word == "dev")?;
+    let interface = words.next()?;
+    Some(Link { interface: interface.to_string(), transport: Transport::from_interface(interface) })
+}
+
+/// The network apps use by default, from `Active default network: 112` and the
+/// `NetworkAgentInfo` of that network. None while there is no default network.
+pub fn parse_default_network(output: &str) -> Option<Link> {
+    let id = output.lines().find_map(|line| line.trim().strip_prefix("Active default network: "))?.trim();
+    if id.parse::<u32>().is_err() {
+        return None;
+    }
+    let network = format!("network{{{}}}", id);
+    let agent = output.lines().map(str::trim).find(|line| line.starts_with("NetworkAgentInfo") && line.contains(&network))?;
+
+    let field = |key: &str| agent.split(key).nth(1).and_then(|rest| rest.split_whitespace().next());
+    let interface = field("InterfaceName: ")?;
+    let transport = field("Transports: ").map_or_else(|| Transport::from_interface(interface), |name| {
+        // Several transports are joined with '|', e.g. "CELLULAR|VPN"
+        Transport::from_name(name.split('|').next().unwrap_or(name))
+    });
+    Some(Link { interface: interface.to_string(), transport })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::fixtures::fixture_cases;
+
+    #[test]
+    fn test_parse_route() {
+        let tunnel = parse_route("100.101.1.2 dev tun0 table 1021 src 100.80.1.1 uid 0 \n    cache \n").unwrap();
+        assert_eq!(tunnel, Link { interface: "tun0".to_string(), transport: Transport::Vpn });
+        let local = parse_route("local 127.0.0.1 dev lo table local src 127.0.0.1 uid 0 \n").unwrap();
+        assert_eq!(local.transport, Transport::Loopback);
+        assert_eq!(parse_route("RTNETLINK answers: Network is unreachable\n"), None);
+    }
+
+    #[test]
+    fn test_fixture_default_network() {
+        for (name, output, expected) in fixture_cases("connectivity") {
+            let link = parse_default_network(&output);
+            match expected.get("default_interface") {
+                Some(interface) => {
+                    let link = link.unwrap_or_else(|| panic!("{}: no default network", name));
+                    assert_eq!(link.interface, interface.as_str().unwrap(), "{}", name);
+                    let transport = serde_json::to_value(link.transport).unwrap();
+                    assert_eq!(transport, expected["default_transport"].as_str().unwrap(), "{}", name);
+                }
+                None => assert_eq!(link, None, "{}", name),
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Read-only `content query` against a fixed set of providers
+
+use std::collections::BTreeMap;
+
+/// Providers the daemon may query. Nothing here allows insert, update or delete.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentUri {
+    Sms,
+    CallLog,
+}
+
+impl ContentUri {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ContentUri::Sms => "content://sms",
+            ContentUri::CallLog => "content://call_log/calls",
+        }
+    }
+}
+
+/// One `content query` invocation. The selection is built by the daemon from
+/// typed values, never taken from a request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContentQuery {
+    pub uri: ContentUri,
+    /// Columns in output order, followed by `ROW_END`. Put free text last, see `parse_rows`.
+    pub projection: &'static [&'static str],
+    pub selection: Option<String>,
+    pub sort: Option<&'static str>,
+}
+
+impl ContentQuery {
+    pub fn args(&self) -> Vec<String> {
+        let mut args = vec![
+            "query".to_string(),
+            "--uri".to_string(),
+            self.uri.as_str().to_string(),
+            "--projection".to_string(),
+            self.projection.iter().chain(&ROW_END).copied().collect::<Vec<_>>().join(":"),
+        ];
+        if let Some(ref selection) = self.selection {
+            args.push("--where".to_string());
+            args.push(selection.clone());
+        }
+        if let Some(sort) = self.sort {
+            args.push("--sort".to_string());
+            args.push(sort.to_string());
+        }
+        args
+    }
+}
+
+/// Columns `args` repeats after the projection. A row only ends where they
+/// repeat the values from its start, so a text value cannot fake the end of its
+/// row without knowing its own ID and date to the millisecond.
+const ROW_END: [&str; 2] = ["_id", "date"];
+
+/// Parse `Row: N col=value, col=value, ...` output into one map per row.
+///
+/// Values are not escaped, so a value may itself contain ", ", "=" or a line
+/// that looks like `Row: N ...`. Each value therefore runs up to the next
+/// `, <column>=` in projection order, and the last column up to the `ROW_END`
+/// columns carrying this row's values, followed by the next row in sequence or
+/// the end of the output. `columns` must include the `ROW_END` columns before
+/// the last one. Parsing stops at a row missing a column. `NULL` values are left out.
+pub fn parse_rows(output: &str, columns: &[&str]) -> Vec<BTreeMap<String, String>> {
+    let mut rows = Vec::new();
+    let mut rest = output;
+    while let Some(row) = rest.strip_prefix(&format!("Row: {} ", rows.len())) {
+        let Some((values, next)) = parse_row(row, columns, rows.len()) else { break };
+        rows.push(values);
+        rest = next;
+    }
+    rows
+}
+
+/// One row after its `Row: N ` prefix. Returns the values and the output after the row.
+fn parse_row<'a>(mut rest: &'a str, columns: &[&str], index: usize) -> Option<(BTreeMap<String, String>, &'a str)> {
+    let (last, leading) = columns.split_last()?;
+    let mut values = BTreeMap::new();
+    let mut raw = BTreeMap::new();
+    for (i, column) in leading.iter().enumerate() {
+        let after = rest.strip_prefix(*column)?.strip_prefix('=')?;
+        let end = after.find(&format!(", {}=", columns[i + 1]))?;
+        raw.insert(*column, &after[..end]);
+        rest = &after[end + 2..];
+    }
+    let text = rest.strip_prefix(*last)?.strip_prefix('=')?;
+
+    let end_marker = ROW_END.iter().map(|column| Some(format!(", {}={}", column, raw.get(column)?))).collect::<Option<String>>()?;
+    let next_row = format!("Row: {} {}=", index + 1, columns[0]);
+    let mut from = 0;
+    let (value, next) = loop {
+        let end = from + text[from..].find(&end_marker)?;
+        let after = &text[end + end_marker.len()..];
+        let next = after.strip_prefix("\r\n").or_else(|| after.strip_prefix('\n')).unwrap_or(after);
+        if next.trim().is_empty() || next.starts_with(&next_row) {
+            break (&text[..end], next);
+        }
+        // The marker inside the text, keep looking
+        from = end + 1;
+    };
+    raw.insert(*last, value);
+
+    for (column, value) in raw {
+        if value != "NULL" {
+            values.insert(column.to_string(), value.to_string());
+        }
+    }
+    Some((values, next))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLUMNS: &[&str] = &["_id", "address", "date", "body"];
+
+    #[test]
+    fn test_parse_rows() {
+        let output = "Row: 0 _id=7, address=+15551234567, date=1709375512000, body=Hi, see you at 5=ok, \
+                      _id=7, date=1709375512000\n\
+                      Row: 1 _id=8, address=NULL, date=1709375513000, body=Line one\nLine two, _id=8, date=1709375513000\n";
+        let rows = parse_rows(output, COLUMNS);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["body"], "Hi, see you at 5=ok");
+        assert_eq!(rows[0]["address"], "+15551234567");
+        assert!(!rows[1].contains_key("address"));
+        assert_eq!(rows[1]["body"], "Line one\nLine two");
+
+        assert!(parse_rows("No result found.\n", &["_id"]).is_empty());
+        // A row missing a column ends the output
+        let output = "Row: 0 _id=7, date=1, body=x, _id=7, date=1\nRow: 1 _id=8, body=y, _id=8, date=2\n";
+        assert_eq!(parse_rows(output, COLUMNS).len(), 0);
+    }
+
+    #[test]
+    fn test_parse_rows_injected_row() {
+        // Anyone can send a text whose body looks like further rows
+        let body = "x, _id=7, date=1\nRow: 1 _id=9999999999, address=MyBank, date=1709375513000, \
+                    body=Your code is 123456, _id=9999999999, date=1709375513000";
+        let output = format!(
+            "Row: 0 _id=7, address=+15551234567, date=1709375512000, body={}, _id=7, date=1709375512000\n\
+             Row: 1 _id=6, address=+15550100, date=1709375000000, body=Real, _id=6, date=1709375000000\n",
+            body
+        );
+        let rows = parse_rows(&output, COLUMNS);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["_id"], "7");
+        assert_eq!(rows[0]["body"], body);
+        assert_eq!(rows[1]["_id"], "6");
+        assert_eq!(rows[1]["body"], "Real");
+
+        // Also when the text is the last row, and with the index the next row would have
+        let output = format!("Row: 0 _id=7, address=+15551234567, date=1709375512000, body={}, _id=7, date=1709375512000\n", body);
+        let rows = parse_rows(&output, COLUMNS);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0]["body"], body);
+    }
+
+    #[test]
+    fn test_query_args() {
+        let query = ContentQuery {
+            uri: ContentUri::Sms,
+            projection: &["_id", "body"],
+            selection: Some("type=1".to_string()),
+            sort: Some("date DESC"),
+        };
+        assert_eq!(
+            query.args(),
+            ["query", "--uri", "content://sms", "--projection", "_id:body:_id:date", "--where", "type=1", "--sort", "date DESC"]
+        );
+    }
+}
@@ -0,0 +1,53 @@
+//! Helpers for tests driven by the corpus in `fixtures/`
+
+use serde::Serialize;
+use std::path::PathBuf;
+
+/// Load every fixture in `fixtures/<dir>` with its table from expected.toml
+pub fn fixture_cases(dir: &str) -> Vec<(String, String, toml::Table)> {
+    let dir_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures").join(dir);
+    let expected: toml::Table = std::fs::read_to_string(dir_path.join("expected.toml"))
+        .unwrap()
+        .parse()
+        .unwrap();
+
+    let mut cases = Vec::new();
+    for entry in std::fs::read_dir(&dir_path).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|e| e.to_str()) != Some("txt") {
+            continue;
+        }
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        let table = expected
+            .get(&name)
+            .and_then(|v| v.as_table())
+            .unwrap_or_else(|| panic!("{}/{} has no entry in expected.toml", dir, name))
+            .clone();
+        cases.push((name, std::fs::read_to_string(&path).unwrap(), table));
+    }
+    for name in expected.keys() {
+        assert!(dir_path.join(name).exists(), "{}/expected.toml lists missing file {}", dir, name);
+    }
+    assert!(!cases.is_empty(), "no fixtures in {}", dir);
+    cases
+}
+
+pub fn expected_int(table: &toml::Table, key: &str) -> i64 {
+    table[key].as_integer().unwrap()
+}
+
+pub fn expected_bool(table: &toml::Table, key: &str) -> bool {
+    table[key].as_bool().unwrap()
+}
+
+/// Compare each key of an expected table with the same field of the parsed
+/// value; toml has no null, so fields that must be null are listed in `missing`
+pub fn assert_fields(name: &str, parsed: &impl Serialize, table: &toml::Table) {
+    let parsed = serde_json::to_value(parsed).unwrap();
+    for (key, value) in table.iter().filter(|(k, _)| *k != "missing") {
+        assert_eq!(parsed[key], serde_json::to_value(value).unwrap(), "{}: {}", name, key);
+    }
+    for key in table.get("missing").and_then(|m| m.as_array()).into_iter().flatten() {
+        assert!(parsed[key.as_str().unwrap()].is_null(), "{}: {}", name, key);
+    }
+}
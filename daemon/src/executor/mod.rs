@@ -1 +1,65 @@
+pub mod airplane;
+pub mod battery;
+pub mod bluetooth;
+pub mod call_forwarding;
+pub mod call_log;
+pub mod connectivity;
+pub mod content;
+#[cfg(test)]
+mod fixtures;
+pub mod parcel;
+pub mod replay;
 pub mod shell;
+pub mod simulator;
+pub mod sms;
+pub mod telephony;
+pub mod tethering;
+pub mod ussd;
+pub mod wifi;
+
+use async_trait::async_trait;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::config::{BackendKind, ExecutorConfig};
+use replay::{RecordingBackend, ReplayBackend};
+use shell::{ExecError, ShellBackend, ShellCommand};
+use simulator::SimulatedBackend;
+
+/// Something that can run whitelisted commands and return their output
+#[async_trait]
+pub trait Backend: Send + Sync {
+    async fn execute(&self, command: &ShellCommand) -> Result<String, ExecError>;
+}
+
+/// Run a command with a deadline tighter than the backend's own timeout.
+/// On expiry the command future is dropped, which kills the child process.
+pub async fn execute_within(
+    backend: &dyn Backend,
+    command: &ShellCommand,
+    deadline: Duration,
+) -> Result<String, ExecError> {
+    tokio::time::timeout(deadline, backend.execute(command))
+        .await
+        .unwrap_or(Err(ExecError::Timeout(deadline)))
+}
+
+/// Build the backend selected in the config, wrapped in a recorder if asked to
+pub fn from_config(config: &ExecutorConfig) -> io::Result<Arc<dyn Backend>> {
+    let backend: Arc<dyn Backend> = match config.backend {
+        BackendKind::Shell => Arc::new(ShellBackend::new(config)),
+        BackendKind::Simulator => Arc::new(SimulatedBackend::new()),
+        BackendKind::Replay => {
+            let path = config.replay_from.as_deref().ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "backend = \"replay\" needs replay_from")
+            })?;
+            Arc::new(ReplayBackend::load(path)?)
+        }
+    };
+
+    match config.record_to {
+        Some(ref path) => Ok(Arc::new(RecordingBackend::new(backend, path)?)),
+        None => Ok(backend),
+    }
+}
@@ -0,0 +1,299 @@
+//! Binder parcels as printed by `service call`, e.g.
+//! `Result: Parcel(00000000 00000001   '........')`
+//!
+//! The tool hexdumps the reply as 32-bit words. Parcel data is little-endian
+//! and 4-byte aligned, so the words are turned back into bytes and read the way
+//! `android.os.Parcel` writes them.
+
+use super::shell::ExecError;
+
+/// Exception codes from `android.os.Parcel`
+const EX_SECURITY: i32 = -1;
+const EX_SERVICE_SPECIFIC: i32 = -8;
+const EX_HAS_NOTED_APPOPS_REPLY_HEADER: i32 = -127;
+const EX_HAS_REPLY_HEADER: i32 = -128;
+const EX_TRANSACTION_FAILED: i32 = -129;
+
+/// Reader over the data of one parcel
+#[derive(Debug, Clone)]
+pub struct Parcel {
+    data: Vec<u8>,
+    pos: usize,
+}
+
+impl Parcel {
+    /// Decode `service call` output. A transaction that failed in Binder itself
+    /// (`Parcel(Error: 0xffffffb6 "Not a data message")`) is an error here;
+    /// exceptions thrown by the service are read with `read_exception`.
+    pub fn parse(output: &str) -> Result<Self, ExecError> {
+        let unexpected = || ExecError::Parse(format!("unexpected service call reply: {}", output.trim()));
+        let (_, body) = output.split_once("Result: Parcel(").ok_or_else(unexpected)?;
+        let body = body.trim_start();
+
+        if body.starts_with("NULL") {
+            return Ok(Self { data: Vec::new(), pos: 0 });
+        }
+        if let Some(error) = body.strip_prefix("Error: ") {
+            let (status, detail) = error.split_once(' ').unwrap_or((error, ""));
+            // Printed as a pointer, so 16 digits on 64-bit builds
+            let status = u64::from_str_radix(status.trim_start_matches("0x"), 16).map_err(|_| unexpected())?;
+            let detail = detail.trim_end().trim_end_matches(')').trim_matches('"');
+            return Err(ExecError::NonZeroExit {
+                code: Some(status as i32),
+                stderr: format!("Binder transaction failed: {}", detail),
+            });
+        }
+
+        let mut data = Vec::new();
+        for line in body.lines() {
+            // "0x00000010: 00000091 0000000b '........'", the offset only on multi-line dumps
+            let line = line.trim_start();
+            let line = match line.split_once(": ") {
+                Some((offset, words)) if offset.starts_with("0x") => words,
+                _ => line,
+            };
+            let (words, _ascii) = line.split_once('\'').unwrap_or((line, ""));
+            for word in words.split_whitespace().filter(|w| *w != ")") {
+                let value = u32::from_str_radix(word, 16).ok().filter(|_| word.len() == 8).ok_or_else(unexpected)?;
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+            if line.trim_end().ends_with(')') {
+                break;
+            }
+        }
+        Ok(Self { data, pos: 0 })
+    }
+
+    /// Decode a reply and read its exception header, so what is left is the return value
+    pub fn reply(output: &str) -> Result<Self, ExecError> {
+        let mut parcel = Self::parse(output)?;
+        parcel.read_exception()?;
+        Ok(parcel)
+    }
+
+    /// Bytes not read yet
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], ExecError> {
+        if len > self.remaining() {
+            return Err(ExecError::Parse(format!(
+                "parcel ends at {} bytes, {} more wanted at {}",
+                self.data.len(),
+                len,
+                self.pos
+            )));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub fn read_i32(&mut self) -> Result<i32, ExecError> {
+        let bytes = self.take(4)?;
+        Ok(i32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    #[allow(dead_code)] // No decoded reply carries a long yet
+    pub fn read_i64(&mut self) -> Result<i64, ExecError> {
+        let low = self.read_i32()? as u32 as u64;
+        let high = self.read_i32()? as u32 as u64;
+        Ok((high << 32 | low) as i64)
+    }
+
+    /// Booleans are written as a whole i32
+    pub fn read_bool(&mut self) -> Result<bool, ExecError> {
+        Ok(self.read_i32()? != 0)
+    }
+
+    /// Length in UTF-16 units (-1 for null), the units with a terminating 0, padded to 4 bytes
+    pub fn read_string16(&mut self) -> Result<Option<String>, ExecError> {
+        let len = self.read_i32()?;
+        if len < 0 {
+            return Ok(None);
+        }
+        let size = (len as usize + 1) * 2;
+        let bytes = self.take(size.next_multiple_of(4))?;
+        let units: Vec<u16> =
+            bytes[..len as usize * 2].chunks_exact(2).map(|unit| u16::from_le_bytes([unit[0], unit[1]])).collect();
+        String::from_utf16(&units).map(Some).map_err(|e| ExecError::Parse(format!("invalid String16: {}", e)))
+    }
+
+    /// Read the header every AIDL reply starts with and turn a thrown exception into an error.
+    /// `SecurityException` is a permission problem; the others keep their code and message.
+    pub fn read_exception(&mut self) -> Result<(), ExecError> {
+        loop {
+            let code = self.read_i32()?;
+            match code {
+                0 => return Ok(()),
+                // Reply headers (strict mode, app ops) before the real result, sized including the size
+                EX_HAS_REPLY_HEADER | EX_HAS_NOTED_APPOPS_REPLY_HEADER => {
+                    let size = self.read_i32()?;
+                    self.take((size as usize).saturating_sub(4))?;
+                    if code == EX_HAS_REPLY_HEADER {
+                        return Ok(());
+                    }
+                }
+                EX_TRANSACTION_FAILED => {
+                    return Err(ExecError::NonZeroExit {
+                        code: Some(code),
+                        stderr: "Binder transaction failed in the service".to_string(),
+                    });
+                }
+                _ => {
+                    let message = self.read_string16().ok().flatten().unwrap_or_default();
+                    // Android 8+ follows with a remote stack trace, sized including the size
+                    if let Ok(size) = self.read_i32() {
+                        let _ = self.take((size.max(0) as usize).saturating_sub(4));
+                    }
+                    let detail = match code {
+                        EX_SERVICE_SPECIFIC => match self.read_i32() {
+                            Ok(error) => format!("{} (error {}): {}", exception_name(code), error, message),
+                            Err(_) => format!("{}: {}", exception_name(code), message),
+                        },
+                        _ => format!("{}: {}", exception_name(code), message),
+                    };
+                    return Err(match code {
+                        EX_SECURITY => ExecError::PermissionDenied(detail),
+                        _ => ExecError::NonZeroExit { code: Some(code), stderr: detail },
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// Java exception written for an exception code
+fn exception_name(code: i32) -> &'static str {
+    match code {
+        EX_SECURITY => "SecurityException",
+        -2 => "BadParcelableException",
+        -3 => "IllegalArgumentException",
+        -4 => "NullPointerException",
+        -5 => "IllegalStateException",
+        -6 => "NetworkOnMainThreadException",
+        -7 => "UnsupportedOperationException",
+        EX_SERVICE_SPECIFIC => "ServiceSpecificException",
+        -9 => "ParcelableException",
+        _ => "RemoteException",
+    }
+}
+
+/// Builds parcel data and prints it like `service call`, for tests that fake replies
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct ParcelWriter {
+    data: Vec<u8>,
+}
+
+#[cfg(test)]
+impl ParcelWriter {
+    /// A reply without exception
+    pub fn reply() -> Self {
+        let mut writer = Self::default();
+        writer.write_i32(0);
+        writer
+    }
+
+    pub fn write_i32(&mut self, value: i32) -> &mut Self {
+        self.data.extend_from_slice(&value.to_le_bytes());
+        self
+    }
+
+    pub fn write_string16(&mut self, value: Option<&str>) -> &mut Self {
+        let Some(value) = value else { return self.write_i32(-1) };
+        let units: Vec<u16> = value.encode_utf16().collect();
+        self.write_i32(units.len() as i32);
+        for unit in units.iter().chain([&0]) {
+            self.data.extend_from_slice(&unit.to_le_bytes());
+        }
+        while !self.data.len().is_multiple_of(4) {
+            self.data.push(0);
+        }
+        self
+    }
+
+    /// Up to 16 bytes fit on the `Result: Parcel(` line, longer data gets offsets
+    pub fn to_output(&self) -> String {
+        let line = |chunk: &[u8]| {
+            let words: Vec<String> = chunk
+                .chunks_exact(4)
+                .map(|w| format!("{:08x}", u32::from_le_bytes([w[0], w[1], w[2], w[3]])))
+                .collect();
+            let ascii: String =
+                chunk.iter().map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }).collect();
+            format!("{:<36}'{}'", words.join(" "), ascii)
+        };
+        if self.data.is_empty() {
+            return "Result: Parcel(NULL)\n".to_string();
+        }
+        if self.data.len() <= 16 {
+            return format!("Result: Parcel({})\n", line(&self.data));
+        }
+        let lines: Vec<String> = self
+            .data
+            .chunks(16)
+            .enumerate()
+            .map(|(i, chunk)| format!("  0x{:08x}: {}", i * 16, line(chunk)))
+            .collect();
+        format!("Result: Parcel(\n{})\n", lines.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_values() {
+        let mut parcel = Parcel::parse(
+            "Result: Parcel(\n  0x00000000: 00000000 00000001 00000005 00650048 '............H.e.'\n  \
+             0x00000010: 006c006c 0000006f ffffffff 89abcdef 'l.l.o...........'\n  \
+             0x00000020: 01234567                            'gE#.            ')\n",
+        )
+        .unwrap();
+        parcel.read_exception().unwrap();
+        assert!(parcel.read_bool().unwrap());
+        assert_eq!(parcel.read_string16().unwrap().as_deref(), Some("Hello"));
+        assert_eq!(parcel.read_string16().unwrap(), None);
+        assert_eq!(parcel.read_i64().unwrap(), 0x0123456789abcdef);
+        assert_eq!(parcel.remaining(), 0);
+        assert!(matches!(parcel.read_i32(), Err(ExecError::Parse(_))));
+
+        assert_eq!(Parcel::parse("Result: Parcel(NULL)\n").unwrap().remaining(), 0);
+        assert!(matches!(Parcel::parse("service: Service phone does not exist\n"), Err(ExecError::Parse(_))));
+    }
+
+    #[test]
+    fn test_errors() {
+        let security = "Result: Parcel(\n  0x00000000: ffffffff 00000012 00650053 00750063 '........S.e.c.u.'\n)";
+        assert!(matches!(Parcel::reply(security), Err(ExecError::PermissionDenied(_))));
+
+        let mut writer = ParcelWriter::default();
+        writer.write_i32(-5).write_string16(Some("Radio off")).write_i32(0);
+        match Parcel::reply(&writer.to_output()) {
+            Err(ExecError::NonZeroExit { code: Some(-5), stderr }) => {
+                assert_eq!(stderr, "IllegalStateException: Radio off")
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        let failed = "Result: Parcel(Error: 0xffffffffffffffb6 \"Not a data message\")\n";
+        assert!(matches!(Parcel::parse(failed), Err(ExecError::NonZeroExit { code: Some(-74), .. })));
+    }
+
+    #[test]
+    fn test_writer_round_trip() {
+        let mut writer = ParcelWriter::reply();
+        writer.write_i32(7).write_string16(Some("+15550100")).write_string16(None);
+        let output = writer.to_output();
+        assert!(output.starts_with("Result: Parcel(\n  0x00000000: 00000000 00000007"));
+
+        let mut parcel = Parcel::reply(&output).unwrap();
+        assert_eq!(parcel.read_i32().unwrap(), 7);
+        assert_eq!(parcel.read_string16().unwrap().as_deref(), Some("+15550100"));
+        assert_eq!(parcel.read_string16().unwrap(), None);
+        assert_eq!(ParcelWriter::reply().to_output(), "Result: Parcel(00000000                            '....')\n");
+    }
+}
@@ -0,0 +1,261 @@
+use async_trait::async_trait;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::shell::{ExecError, ShellCommand};
+use super::Backend;
+
+/// One recorded command invocation, stored as a line of JSON
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Recording {
+    /// ShellCommand variant, e.g. "GetBattery"
+    pub command: String,
+    /// Program followed by its arguments, secrets masked
+    pub argv: Vec<String>,
+    pub stdout: String,
+    /// Stderr, or the error message when the command did not run to completion
+    pub stderr: String,
+    pub exit_code: Option<i32>,
+    /// ExecError code when the command failed, e.g. "timeout"
+    pub error: Option<String>,
+    pub latency_ms: u64,
+}
+
+impl Recording {
+    fn new(command: &ShellCommand, result: &Result<String, ExecError>, latency: Duration) -> Self {
+        let mut recording = Recording {
+            command: variant_name(command),
+            argv: command.recorded_argv(),
+            stdout: String::new(),
+            stderr: String::new(),
+            exit_code: None,
+            error: None,
+            latency_ms: latency.as_millis() as u64,
+        };
+        match result {
+            Ok(stdout) => {
+                recording.stdout = stdout.clone();
+                recording.exit_code = Some(0);
+            }
+            Err(e) => {
+                recording.error = Some(e.code().to_string());
+                match e {
+                    ExecError::NonZeroExit { code, stderr } => {
+                        recording.exit_code = *code;
+                        recording.stderr = stderr.clone();
+                    }
+                    ExecError::PermissionDenied(detail) | ExecError::Parse(detail) => {
+                        recording.stderr = detail.clone();
+                    }
+                    other => recording.stderr = other.to_string(),
+                }
+            }
+        }
+        recording
+    }
+
+    /// Turn the recording back into the result the backend returned
+    fn result(&self) -> Result<String, ExecError> {
+        let Some(ref error) = self.error else {
+            return Ok(self.stdout.clone());
+        };
+        Err(match error.as_str() {
+            "not_found" => ExecError::NotFound { program: self.argv.first().cloned().unwrap_or_default() },
+            "non_zero_exit" => ExecError::NonZeroExit { code: self.exit_code, stderr: self.stderr.clone() },
+            "timeout" => ExecError::Timeout(Duration::from_millis(self.latency_ms)),
+            "permission_denied" => ExecError::PermissionDenied(self.stderr.clone()),
+            "parse_error" => ExecError::Parse(self.stderr.clone()),
+            _ => ExecError::Io(io::Error::other(self.stderr.clone())),
+        })
+    }
+}
+
+/// "DialNumber" for `DialNumber("+123")`
+fn variant_name(command: &ShellCommand) -> String {
+    let debug = format!("{:?}", command);
+    debug.split(['(', ' ', '{']).next().unwrap_or_default().to_string()
+}
+
+/// Wraps another backend and appends every invocation to a replay file
+pub struct RecordingBackend {
+    inner: Arc<dyn Backend>,
+    file: Mutex<File>,
+}
+
+impl RecordingBackend {
+    pub fn new(inner: Arc<dyn Backend>, path: &str) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        info!("Recording executor output to {}", path);
+        Ok(Self { inner, file: Mutex::new(file) })
+    }
+}
+
+#[async_trait]
+impl Backend for RecordingBackend {
+    async fn execute(&self, command: &ShellCommand) -> Result<String, ExecError> {
+        let started = Instant::now();
+        let result = self.inner.execute(command).await;
+        let recording = Recording::new(command, &result, started.elapsed());
+
+        // One line per invocation, written whole so a crash never leaves half a record
+        let mut line = serde_json::to_string(&recording).expect("recording serializes");
+        line.push('\n');
+        if let Err(e) = self.file.lock().unwrap().write_all(line.as_bytes()) {
+            warn!("Failed to write replay record: {}", e);
+        }
+        result
+    }
+}
+
+/// Serves recorded outputs back, so a bug report replays on any machine.
+/// Recordings for the same argv are served in order; the last one repeats.
+pub struct ReplayBackend {
+    by_argv: Mutex<HashMap<Vec<String>, VecDeque<Recording>>>,
+    /// Fallback for commands whose arguments differ from the recording
+    by_command: HashMap<String, Recording>,
+}
+
+impl ReplayBackend {
+    pub fn load(path: &str) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut recordings = Vec::new();
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let recording: Recording = serde_json::from_str(&line).map_err(|e| {
+                io::Error::new(io::ErrorKind::InvalidData, format!("{}:{}: {}", path, number + 1, e))
+            })?;
+            recordings.push(recording);
+        }
+        info!("Loaded {} recordings from {}", recordings.len(), path);
+        Ok(Self::from_recordings(recordings))
+    }
+
+    fn from_recordings(recordings: Vec<Recording>) -> Self {
+        let mut by_argv: HashMap<Vec<String>, VecDeque<Recording>> = HashMap::new();
+        let mut by_command = HashMap::new();
+        for recording in recordings {
+            by_command.insert(recording.command.clone(), recording.clone());
+            by_argv.entry(recording.argv.clone()).or_default().push_back(recording);
+        }
+        Self { by_argv: Mutex::new(by_argv), by_command }
+    }
+
+    fn next(&self, command: &ShellCommand) -> Option<Recording> {
+        let argv = command.recorded_argv();
+        let mut by_argv = self.by_argv.lock().unwrap();
+        if let Some(queue) = by_argv.get_mut(&argv) {
+            return if queue.len() > 1 { queue.pop_front() } else { queue.front().cloned() };
+        }
+        self.by_command.get(&variant_name(command)).cloned()
+    }
+}
+
+#[async_trait]
+impl Backend for ReplayBackend {
+    async fn execute(&self, command: &ShellCommand) -> Result<String, ExecError> {
+        let Some(recording) = self.next(command) else {
+            warn!("No recording for {:?}", command);
+            return Err(ExecError::NotFound { program: format!("{:?} (not in replay file)", command) });
+        };
+        // Keep the original timing so deadlines behave as they did on the phone
+        tokio::time::sleep(Duration::from_millis(recording.latency_ms)).await;
+        recording.result()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{WifiNetwork, WifiSecurity};
+    use crate::executor::simulator::SimulatedBackend;
+
+    fn temp_path(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("ohmyphone-{}-{}.jsonl", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().to_string()
+    }
+
+    #[tokio::test]
+    async fn test_record_then_replay() {
+        let path = temp_path("record");
+        let recorder = RecordingBackend::new(Arc::new(SimulatedBackend::new()), &path).unwrap();
+
+        let battery = recorder.execute(&ShellCommand::GetBattery).await.unwrap();
+        recorder.execute(&ShellCommand::EnableAirplaneMode).await.unwrap();
+        let dial_error = recorder.execute(&ShellCommand::DialNumber("+1234567890".into())).await.unwrap_err();
+        recorder.execute(&ShellCommand::DisableAirplaneMode).await.unwrap();
+
+        let replay = ReplayBackend::load(&path).unwrap();
+        assert_eq!(replay.execute(&ShellCommand::GetBattery).await.unwrap(), battery);
+        let replayed_error = replay.execute(&ShellCommand::DialNumber("+1234567890".into())).await.unwrap_err();
+        assert_eq!(replayed_error.code(), dial_error.code());
+        assert_eq!(replayed_error.to_string(), dial_error.to_string());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_replay_order_and_fallback() {
+        let record = |stdout: &str, command: ShellCommand| {
+            Recording::new(&command, &Ok(stdout.to_string()), Duration::ZERO)
+        };
+        let replay = ReplayBackend::from_recordings(vec![
+            record("0\n", ShellCommand::GetAirplaneMode),
+            record("1\n", ShellCommand::GetAirplaneMode),
+            record("ok", ShellCommand::DialNumber("+1111111".into())),
+        ]);
+
+        assert_eq!(replay.execute(&ShellCommand::GetAirplaneMode).await.unwrap(), "0\n");
+        assert_eq!(replay.execute(&ShellCommand::GetAirplaneMode).await.unwrap(), "1\n");
+        // Last recording repeats
+        assert_eq!(replay.execute(&ShellCommand::GetAirplaneMode).await.unwrap(), "1\n");
+        // Different number, same variant
+        assert_eq!(replay.execute(&ShellCommand::DialNumber("+2222222".into())).await.unwrap(), "ok");
+        // Never recorded
+        assert!(replay.execute(&ShellCommand::GetBattery).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_passphrases_are_not_recorded() {
+        let path = temp_path("passphrase");
+        let recorder = RecordingBackend::new(Arc::new(SimulatedBackend::new()), &path).unwrap();
+        let network = |passphrase: &str| WifiNetwork {
+            ssid: "Home".to_string(),
+            security: WifiSecurity::Wpa2,
+            passphrase: Some(passphrase.to_string()),
+        };
+        recorder.execute(&ShellCommand::ConnectWifi(network("hunter2-secret"))).await.unwrap();
+        recorder.execute(&ShellCommand::StartHotspot(network("hotspot-secret"))).await.unwrap();
+
+        let contents = std::fs::read_to_string(&path).unwrap();
+        assert!(!contents.contains("hunter2-secret"));
+        assert!(!contents.contains("hotspot-secret"));
+        assert!(contents.contains("\"Home\""));
+        // Matched without the passphrase, whatever it is
+        let replay = ReplayBackend::load(&path).unwrap();
+        assert_eq!(
+            replay.next(&ShellCommand::ConnectWifi(network("other"))).unwrap().argv,
+            ["cmd", "wifi", "connect-network", "Home", "wpa2", "***"]
+        );
+        assert_eq!(
+            replay.next(&ShellCommand::StartHotspot(network("other"))).unwrap().argv,
+            ["cmd", "wifi", "start-softap", "Home", "wpa2", "***"]
+        );
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_variant_name() {
+        assert_eq!(variant_name(&ShellCommand::GetBattery), "GetBattery");
+        assert_eq!(variant_name(&ShellCommand::DialNumber("+1".into())), "DialNumber");
+    }
+}
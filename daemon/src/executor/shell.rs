@@ -1,252 +1,452 @@
-use std::process::Command;
+use async_trait::async_trait;
+use log::warn;
+use std::fmt;
+use std::net::IpAddr;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::process::Command;
+use tokio::sync::Semaphore;
+
+use super::airplane::RadioSetting;
+use super::call_forwarding::ForwardingReason;
+use super::content::ContentQuery;
+use super::Backend;
+use crate::config::{ExecutorConfig, UsbFunction, WifiNetwork};
+
+/// Subscription the forwarding commands use when no SIM is selected
+pub const DEFAULT_SUB_ID: i32 = 1;
 
 /// Whitelisted shell commands - NO arbitrary execution
 #[derive(Debug)]
 pub enum ShellCommand {
     GetBattery,
     GetSignal,
+    #[allow(dead_code)] // Superseded by GetMobileDataConnection in /status
     GetDataState,
     GetDataStateLogcat,
     GetAirplaneMode,
     GetUptime,
     GetMobileDataConnection,
+    GetTelephonyProperties,
+    GetSubscriptions,
+    /// Read-only `content query` against one of the allowed providers
+    ContentQuery(ContentQuery),
     EnableData,
     DisableData,
+    /// Per-SIM mobile data switch, for a SIM that is not the default data SIM
+    SetSubscriptionData { sub_id: i32, enable: bool },
     EnableAirplaneMode,
     DisableAirplaneMode,
-    EnableCallForwarding(String),
-    DisableCallForwarding,
-    GetCallForwardingState,
+    GetAirplaneRadios(RadioSetting),
+    /// Comma-separated radios, built from `airplane::Radio` or read back from the phone
+    SetAirplaneRadios(RadioSetting, String),
+    /// Back to the unset default
+    ResetAirplaneRadios(RadioSetting),
+    /// `timer` is the no-reply delay in seconds, ignored for other reasons
+    EnableCallForwarding { reason: ForwardingReason, number: String, timer: Option<u8>, sub_id: i32 },
+    DisableCallForwarding { reason: ForwardingReason, sub_id: i32 },
+    /// Ask the network for the forwarding state; the answer arrives like a USSD reply
+    QueryCallForwarding { reason: ForwardingReason, sub_id: i32 },
     DialNumber(String),
+    /// Pick up the ringing call
+    AnswerCall,
+    /// Reject the ringing call or hang up the active one
+    EndCall,
+    /// USSD code, or the reply to an open USSD menu
+    SendUssd { code: String, sub_id: i32 },
+    /// Radio log lines about USSD and other MMI codes from this Unix ms on
+    GetMmiLog { since_ms: i64 },
+    /// Text split into SMS parts; one part is sent as a plain text message
+    SendSms { number: String, parts: Vec<String>, sub_id: i32 },
+    GetWifiStatus,
+    /// IPv4 address of wlan0, for builds that leave it out of `cmd wifi status`
+    GetWifiAddress,
+    EnableWifi,
+    DisableWifi,
+    /// Join one of the networks from the `[wifi]` config
+    ConnectWifi(WifiNetwork),
+    GetTetheringState,
+    /// Byte counters of every network interface
+    GetInterfaceCounters,
+    /// IPv4 neighbours, which on tethered interfaces are the clients
+    GetNeighbours,
+    /// Hotspot from the `[tethering]` config
+    StartHotspot(WifiNetwork),
+    StopHotspot,
+    StartUsbTethering(UsbFunction),
+    StopUsbTethering,
+    GetBluetoothState,
+    EnableBluetooth,
+    DisableBluetooth,
+    /// Interface the phone would send to this address over
+    GetRoute(IpAddr),
 }
 
 impl ShellCommand {
-    /// Execute the whitelisted command and return output
-    pub fn execute(&self) -> Result<String, String> {
-        let output = match self {
-            ShellCommand::GetBattery => {
-                Command::new("dumpsys")
-                    .arg("battery")
-                    .output()
+    /// Program and arguments this command runs
+    pub fn argv(&self) -> (&'static str, Vec<String>) {
+        let (program, args): (&'static str, &[&str]) = match self {
+            ShellCommand::GetBattery => ("dumpsys", &["battery"]),
+            ShellCommand::GetSignal => ("dumpsys", &["telephony.registry"]),
+            ShellCommand::GetDataState => ("settings", &["get", "global", "mobile_data"]),
+            // Only grab the last 100 lines for performance
+            ShellCommand::GetDataStateLogcat => ("logcat", &["-d", "-t", "100"]),
+            // Use dumpsys connectivity for robust mobile data detection
+            ShellCommand::GetMobileDataConnection => ("dumpsys", &["connectivity"]),
+            ShellCommand::GetAirplaneMode => ("settings", &["get", "global", "airplane_mode_on"]),
+            ShellCommand::GetUptime => ("cat", &["/proc/uptime"]),
+            // All properties; the gsm.* ones are picked out when parsing
+            ShellCommand::GetTelephonyProperties => ("getprop", &[]),
+            ShellCommand::GetSubscriptions => ("dumpsys", &["isub"]),
+            // content query --uri content://sms --projection _id:body --where type=1
+            ShellCommand::ContentQuery(query) => return ("content", query.args()),
+            ShellCommand::EnableData => ("svc", &["data", "enable"]),
+            ShellCommand::DisableData => ("svc", &["data", "disable"]),
+            ShellCommand::SetSubscriptionData { sub_id, enable } => {
+                // settings put global mobile_data<subId> 1
+                // Only one SIM carries data at a time, the switch applies when this one does
+                let key = format!("mobile_data{}", sub_id);
+                let value = if *enable { "1" } else { "0" };
+                return ("settings", to_args(&["put", "global", &key, value]));
             }
-            ShellCommand::GetSignal => {
-                Command::new("dumpsys")
-                    .arg("telephony.registry")
-                    .output()
+            // Use cmd connectivity for reliable airplane mode control
+            ShellCommand::EnableAirplaneMode => ("cmd", &["connectivity", "airplane-mode", "enable"]),
+            ShellCommand::DisableAirplaneMode => ("cmd", &["connectivity", "airplane-mode", "disable"]),
+            ShellCommand::GetAirplaneRadios(setting) => ("settings", &["get", "global", setting.key()]),
+            ShellCommand::SetAirplaneRadios(setting, radios) => {
+                return ("settings", to_args(&["put", "global", setting.key(), radios]));
             }
-            ShellCommand::GetDataState => {
-                // Try settings first
-                let out = Command::new("settings")
-                    .args(["get", "global", "mobile_data"])
-                    .output();
-                match out {
-                    Ok(ref o) if o.status.success() => {
-                        let s = String::from_utf8_lossy(&o.stdout);
-                        let trimmed = s.trim();
-                        if trimmed == "0" || trimmed == "1" {
-                            return Ok(s.into());
-                        }
-                    }
-                    _ => {}
-                }
-                // Fallback: use logcat
-                return ShellCommand::GetDataStateLogcat.execute();
+            ShellCommand::ResetAirplaneRadios(setting) => ("settings", &["delete", "global", setting.key()]),
+            ShellCommand::EnableCallForwarding { reason, number, timer, sub_id } => {
+                // service call phone 14 i32 <subId> s16 "*21*+1234567890#"
+                // The phone number and timer are already validated in the API layer
+                return mmi(*sub_id, &reason.enable_code(number, *timer));
             }
-            ShellCommand::GetMobileDataConnection => {
-                // Use dumpsys connectivity for robust mobile data detection
-                Command::new("dumpsys")
-                    .arg("connectivity")
-                    .output()
+            // service call phone 14 i32 <subId> s16 "#21#"
+            ShellCommand::DisableCallForwarding { reason, sub_id } => return mmi(*sub_id, &reason.disable_code()),
+            // service call phone 14 i32 <subId> s16 "*#21#"
+            ShellCommand::QueryCallForwarding { reason, sub_id } => return mmi(*sub_id, &reason.query_code()),
+            ShellCommand::DialNumber(number) => {
+                // am start -a android.intent.action.CALL -d tel:<number>
+                // Phone number is already validated in API layer
+                let tel_uri = format!("tel:{}", number);
+                return ("am", to_args(&["start", "-a", "android.intent.action.CALL", "-d", &tel_uri]));
             }
-            // --- End of impl ShellCommand ---
-            // --- End of impl ShellCommand ---
-
-            /// Parse if user mobile data is actually connected (not just IMS) from dumpsys connectivity output
-            pub fn parse_mobile_data_connected(output: &str) -> bool {
-                let mut in_mobile_block = false;
-                let mut is_connected = false;
-                let mut is_user_data = false;
-                for line in output.lines() {
-                    let l = line.trim();
-                    if l.starts_with("NetworkAgentInfo") && l.contains("MOBILE") {
-                        // New block, reset flags
-                        in_mobile_block = true;
-                        is_connected = l.contains("CONNECTED");
-                        is_user_data = false;
-                        // Check for extra: default/internet/ims on same line
-                        if l.contains("extra: ims") {
-                            is_user_data = false;
-                        } else if l.contains("extra: default") || l.contains("extra: internet") {
-                            is_user_data = true;
-                        }
-                    } else if in_mobile_block {
-                        // Look for extra: default/internet/ims in following lines
-                        if l.contains("extra: ims") {
-                            is_user_data = false;
-                        } else if l.contains("extra: default") || l.contains("extra: internet") {
-                            is_user_data = true;
-                        }
-                        // End of block: next NetworkAgentInfo or empty line
-                        if l.starts_with("NetworkAgentInfo") || l.is_empty() {
-                            if is_connected && is_user_data {
-                                return true;
-                            }
-                            in_mobile_block = false;
-                            is_connected = false;
-                            is_user_data = false;
-                        }
-                    }
-                }
-                is_connected && is_user_data
+            // Headset-style call keys act on the foreground call without touching the screen.
+            // ENDCALL with no call puts the device to sleep, so the API checks the call state first.
+            ShellCommand::AnswerCall => ("input", &["keyevent", "KEYCODE_CALL"]),
+            ShellCommand::EndCall => ("input", &["keyevent", "KEYCODE_ENDCALL"]),
+            // While a menu is open the network takes a short code as the reply.
+            // The code is already checked against the allowlist in the API layer
+            ShellCommand::SendUssd { code, sub_id } => return mmi(*sub_id, code),
+            ShellCommand::GetMmiLog { since_ms } => {
+                // The reply only reaches the dialer UI; GsmMmiCode logs it on the radio buffer
+                let since = format!("{}.{:03}", since_ms / 1000, since_ms % 1000);
+                return (
+                    "logcat",
+                    to_args(&["-d", "-b", "radio", "-v", "epoch", "-T", &since, "-s", "GsmMmiCode:D"]),
+                );
             }
-            ShellCommand::GetDataStateLogcat => {
-                // Parse logcat for MultiSimSettingController lines
-                // Only grab the last 100 lines for performance
-                let out = Command::new("logcat")
-                    .args(["-d", "-t", "100"])
-                    .output();
-                match out {
-                    Ok(o) if o.status.success() => {
-                        let log = String::from_utf8_lossy(&o.stdout);
-                        // Look for last MultiSimSettingController line
-                        let mut last_state = None;
-                        for line in log.lines().rev() {
-                            if line.contains("MultiSimSettingController") && line.contains("mobile_data") {
-                                // Example: I MultiSimSettingController: setMobileDataEnabled: enabled=true
-                                if let Some(enabled_idx) = line.find("enabled=") {
-                                    let val = &line[enabled_idx + 8..];
-                                    if val.starts_with("true") {
-                                        last_state = Some("1");
-                                        break;
-                                    } else if val.starts_with("false") {
-                                        last_state = Some("0");
-                                        break;
-                                    }
-                                }
-                            }
-                        }
-                        if let Some(state) = last_state {
-                            Ok(state.to_string())
-                        } else {
-                            Ok("unknown".to_string())
-                        }
+            ShellCommand::SendSms { number, parts, sub_id } => {
+                // ISms (Android 11+): 5 = sendTextForSubscriber, 8 = sendMultipartTextForSubscriber
+                // (subId, callingPkg, attributionTag, destAddr, scAddr, text or parts,
+                //  sentIntent(s), deliveryIntent(s), persistMessage, messageId)
+                // Without intents there is no callback; the framework persists the message
+                // to content://sms, where its sent state is read back.
+                // The number is already validated in the API layer
+                let sub_id = sub_id.to_string();
+                let mut args = to_args(&["call", "isms"]);
+                args.push(if parts.len() == 1 { "5" } else { "8" }.to_string());
+                args.extend(to_args(&["i32", &sub_id, "s16", "com.android.shell", "null", "s16", number, "null"]));
+                if let [text] = parts.as_slice() {
+                    args.extend(to_args(&["s16", text, "i32", "0", "i32", "0"]));
+                } else {
+                    // List<String>: count, then each part
+                    args.extend(to_args(&["i32", &parts.len().to_string()]));
+                    for part in parts {
+                        args.extend(to_args(&["s16", part]));
                     }
-                    Ok(o) => {
-                        let stderr = String::from_utf8_lossy(&o.stderr);
-                        Err(format!("logcat failed: {}", stderr))
-                    }
-                    Err(e) => Err(format!("logcat exec error: {}", e)),
+                    // null lists
+                    args.extend(to_args(&["i32", "-1", "i32", "-1"]));
                 }
+                args.extend(to_args(&["i32", "1", "i64", "0"]));
+                return ("service", args);
             }
-            ShellCommand::GetAirplaneMode => {
-                Command::new("settings")
-                    .args(["get", "global", "airplane_mode_on"])
-                    .output()
-            }
-            ShellCommand::GetUptime => {
-                Command::new("cat")
-                    .arg("/proc/uptime")
-                    .output()
-            }
-            ShellCommand::EnableData => {
-                Command::new("svc")
-                    .args(["data", "enable"])
-                    .output()
-            }
-            ShellCommand::DisableData => {
-                Command::new("svc")
-                    .args(["data", "disable"])
-                    .output()
+            ShellCommand::GetWifiStatus => ("cmd", &["wifi", "status"]),
+            ShellCommand::GetWifiAddress => ("ip", &["-4", "-o", "addr", "show", "wlan0"]),
+            ShellCommand::EnableWifi => ("svc", &["wifi", "enable"]),
+            ShellCommand::DisableWifi => ("svc", &["wifi", "disable"]),
+            ShellCommand::ConnectWifi(network) => {
+                // cmd wifi connect-network <ssid> open|owe|wpa2|wpa3 [<passphrase>] (Android 11+)
+                // Only networks from the config get here; the SSID is one argument, no shell involved
+                let mut args = to_args(&["wifi", "connect-network", &network.ssid, network.security.as_str()]);
+                args.extend(network.passphrase.clone());
+                return ("cmd", args);
             }
-            ShellCommand::EnableAirplaneMode => {
-                // Use cmd connectivity for reliable airplane mode control
-                Command::new("cmd")
-                    .args(["connectivity", "airplane-mode", "enable"])
-                    .output()
+            ShellCommand::GetTetheringState => ("dumpsys", &["tethering"]),
+            ShellCommand::GetInterfaceCounters => ("cat", &["/proc/net/dev"]),
+            ShellCommand::GetNeighbours => ("ip", &["-4", "neigh", "show"]),
+            ShellCommand::StartHotspot(network) => {
+                // cmd wifi start-softap <ssid> open|owe|wpa2|wpa3 [<passphrase>] (Android 11+).
+                // Tethering serves it like a hotspot started from quick settings.
+                let mut args = to_args(&["wifi", "start-softap", &network.ssid, network.security.as_str()]);
+                args.extend(network.passphrase.clone());
+                return ("cmd", args);
             }
-            ShellCommand::DisableAirplaneMode => {
-                // Use cmd connectivity for reliable airplane mode control
-                Command::new("cmd")
-                    .args(["connectivity", "airplane-mode", "disable"])
-                    .output()
+            ShellCommand::StopHotspot => ("cmd", &["wifi", "stop-softap"]),
+            ShellCommand::StartUsbTethering(function) => return ("svc", to_args(&["usb", "setFunctions", function.as_str()])),
+            // Without a function the port goes back to charging only, which ends tethering
+            ShellCommand::StopUsbTethering => ("svc", &["usb", "setFunctions"]),
+            ShellCommand::GetBluetoothState => ("dumpsys", &["bluetooth_manager"]),
+            // Android 13+ svc hands this to cmd bluetooth_manager, older releases call the adapter directly
+            ShellCommand::EnableBluetooth => ("svc", &["bluetooth", "enable"]),
+            ShellCommand::DisableBluetooth => ("svc", &["bluetooth", "disable"]),
+            ShellCommand::GetRoute(address) => return ("ip", to_args(&["route", "get", &address.to_string()])),
+        };
+        (program, to_args(args))
+    }
+
+    /// Program and arguments as written to replay files and matched on replay,
+    /// with Wi-Fi and hotspot passphrases masked since those files end up in bug reports
+    pub fn recorded_argv(&self) -> Vec<String> {
+        let (program, mut args) = self.argv();
+        if let ShellCommand::ConnectWifi(WifiNetwork { passphrase: Some(_), .. })
+        | ShellCommand::StartHotspot(WifiNetwork { passphrase: Some(_), .. }) = self
+        {
+            // The passphrase is the last argument
+            if let Some(passphrase) = args.last_mut() {
+                *passphrase = REDACTED.to_string();
             }
-            ShellCommand::EnableCallForwarding(number) => {
-                // service call phone 14 i32 1 s16 "*21*+1234567890#"
-                // The phone number is already validated in the API layer
-                let code = format!("*21*{}#", number);
-                Command::new("service")
-                    .args(["call", "phone", "14", "i32", "1", "s16", &code])
-                    .output()
+        }
+        std::iter::once(program.to_string()).chain(args).collect()
+    }
+}
+
+/// Stands in for a secret in recorded arguments
+const REDACTED: &str = "***";
+
+/// service call phone 14 i32 <subId> s16 <code> (handlePinMmiForSubscriber), as if dialed
+fn mmi(sub_id: i32, code: &str) -> (&'static str, Vec<String>) {
+    let sub_id = sub_id.to_string();
+    ("service", to_args(&["call", "phone", "14", "i32", &sub_id, "s16", code]))
+}
+
+fn to_args(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+/// Error returned when a whitelisted command could not produce output
+#[derive(Debug)]
+pub enum ExecError {
+    /// The binary does not exist on this system
+    NotFound { program: String },
+    /// The command ran but exited with a non-zero status
+    NonZeroExit { code: Option<i32>, stderr: String },
+    /// The command did not finish within its deadline and was killed
+    Timeout(Duration),
+    /// The daemon lacks the privileges to run the command (not root)
+    PermissionDenied(String),
+    /// The command succeeded but its output could not be understood
+    Parse(String),
+    /// Any other I/O failure while spawning or reading the command
+    Io(std::io::Error),
+}
+
+impl ExecError {
+    /// Machine-readable code returned to API clients
+    pub fn code(&self) -> &'static str {
+        match self {
+            ExecError::NotFound { .. } => "not_found",
+            ExecError::NonZeroExit { .. } => "non_zero_exit",
+            ExecError::Timeout(_) => "timeout",
+            ExecError::PermissionDenied(_) => "permission_denied",
+            ExecError::Parse(_) => "parse_error",
+            ExecError::Io(_) => "io_error",
+        }
+    }
+
+    /// Classify a spawn error for `program`
+    fn from_spawn(program: &str, error: std::io::Error) -> Self {
+        match error.kind() {
+            std::io::ErrorKind::NotFound => ExecError::NotFound { program: program.to_string() },
+            std::io::ErrorKind::PermissionDenied => ExecError::PermissionDenied(error.to_string()),
+            _ => ExecError::Io(error),
+        }
+    }
+
+    /// Classify a command that exited unsuccessfully.
+    /// Android tools report missing privileges on stderr rather than by exit code.
+    fn from_exit(code: Option<i32>, stderr: String) -> Self {
+        let lower = stderr.to_lowercase();
+        if lower.contains("permission denied")
+            || lower.contains("securityexception")
+            || lower.contains("not allowed")
+        {
+            ExecError::PermissionDenied(stderr.trim().to_string())
+        } else {
+            ExecError::NonZeroExit { code, stderr }
+        }
+    }
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecError::NotFound { program } => write!(f, "Command not found: {}", program),
+            ExecError::NonZeroExit { code: Some(code), stderr } => {
+                write!(f, "Command failed with exit code {}: {}", code, stderr.trim())
             }
-            ShellCommand::DisableCallForwarding => {
-                // service call phone 14 i32 1 s16 "#21#"
-                Command::new("service")
-                    .args(["call", "phone", "14", "i32", "1", "s16", "#21#"])
-                    .output()
+            ExecError::NonZeroExit { code: None, stderr } => {
+                write!(f, "Command killed by signal: {}", stderr.trim())
             }
-            ShellCommand::GetCallForwardingState => {
-                // Query call forwarding status
-                // service call phone 13 i32 1 i32 0
-                Command::new("service")
-                    .args(["call", "phone", "13", "i32", "1", "i32", "0"])
-                    .output()
+            ExecError::Timeout(after) => write!(f, "Command timed out after {}ms", after.as_millis()),
+            ExecError::PermissionDenied(detail) => {
+                write!(f, "Permission denied (is the daemon running as root?): {}", detail)
             }
-            ShellCommand::DialNumber(number) => {
-                // am start -a android.intent.action.CALL -d tel:<number>
-                // Phone number is already validated in API layer
-                let tel_uri = format!("tel:{}", number);
-                Command::new("am")
-                    .args(["start", "-a", "android.intent.action.CALL", "-d", &tel_uri])
-                    .output()
+            ExecError::Parse(detail) => write!(f, "Could not parse command output: {}", detail),
+            ExecError::Io(e) => write!(f, "Execution error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExecError {}
+
+/// Backend that runs whitelisted commands on the device through the shell
+pub struct ShellBackend {
+    timeout: Duration,
+    max_output_bytes: usize,
+    permits: Semaphore,
+}
+
+impl ShellBackend {
+    pub fn new(config: &ExecutorConfig) -> Self {
+        Self {
+            timeout: Duration::from_millis(config.timeout_ms),
+            max_output_bytes: config.max_output_bytes,
+            permits: Semaphore::new(config.max_concurrent.max(1)),
+        }
+    }
+
+    /// Spawn the command, bounded by the timeout, output cap and concurrency limit
+    async fn run(&self, command: &ShellCommand) -> Result<String, ExecError> {
+        let _permit = self.permits.acquire().await.expect("semaphore is never closed");
+
+        let (program, args) = command.argv();
+        // kill_on_drop makes sure a timed out child does not linger
+        let mut child = Command::new(program)
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .map_err(|e| ExecError::from_spawn(program, e))?;
+
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let cap = self.max_output_bytes;
+        let finished = async {
+            tokio::try_join!(read_capped(stdout, cap), read_capped(stderr, cap), child.wait())
+        };
+
+        let (stdout, stderr, status) = match tokio::time::timeout(self.timeout, finished).await {
+            Ok(result) => result.map_err(ExecError::Io)?,
+            Err(_) => {
+                warn!("{:?} timed out after {:?}", command, self.timeout);
+                return Err(ExecError::Timeout(self.timeout));
             }
         };
 
-        match output {
-            Ok(out) if out.status.success() => {
-                Ok(String::from_utf8_lossy(&out.stdout).to_string())
+        if status.success() {
+            Ok(String::from_utf8_lossy(&stdout).to_string())
+        } else {
+            let stderr = String::from_utf8_lossy(&stderr).to_string();
+            Err(ExecError::from_exit(status.code(), stderr))
+        }
+    }
+}
+
+#[async_trait]
+impl Backend for ShellBackend {
+    /// Execute the whitelisted command and return output
+    async fn execute(&self, command: &ShellCommand) -> Result<String, ExecError> {
+        match command {
+            ShellCommand::GetDataState => {
+                // Try settings first
+                if let Ok(s) = self.run(command).await {
+                    let trimmed = s.trim();
+                    if trimmed == "0" || trimmed == "1" {
+                        return Ok(s);
+                    }
+                }
+                // Fallback: use logcat
+                self.execute(&ShellCommand::GetDataStateLogcat).await
             }
-            Ok(out) => {
-                let stderr = String::from_utf8_lossy(&out.stderr);
-                Err(format!("Command failed: {}", stderr))
+            ShellCommand::GetDataStateLogcat => {
+                let log = self.run(command).await?;
+                parse_logcat_data_state(&log)
+                    .map(|state| state.to_string())
+                    .ok_or_else(|| ExecError::Parse("no mobile_data state in logcat".to_string()))
             }
-            Err(e) => Err(format!("Execution error: {}", e)),
+            _ => self.run(command).await,
         }
     }
 }
 
-/// Parse battery dumpsys output
-pub fn parse_battery(output: &str) -> (i32, bool) {
-    let mut level = -1;
-    let mut charging = false;
-
-    for line in output.lines() {
-        let line = line.trim();
-        if line.starts_with("level:") {
-            if let Some(val) = line.split(':').nth(1) {
-                level = val.trim().parse().unwrap_or(-1);
-            }
+/// Read a child pipe to the end, keeping at most `cap` bytes.
+/// The rest is drained so the child never blocks on a full pipe.
+async fn read_capped<R: AsyncRead + Unpin>(mut reader: R, cap: usize) -> std::io::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+    let mut truncated = false;
+    loop {
+        let n = reader.read(&mut chunk).await?;
+        if n == 0 {
+            break;
         }
-        if line.starts_with("status:") {
-            // status: 2 means charging, 3 means discharging, 5 means full
-            if let Some(val) = line.split(':').nth(1) {
-                let status = val.trim().parse::<i32>().unwrap_or(0);
-                charging = status == 2 || status == 5;
+        let room = cap.saturating_sub(buf.len());
+        truncated |= n > room;
+        buf.extend_from_slice(&chunk[..n.min(room)]);
+    }
+    if truncated {
+        warn!("Command output truncated to {} bytes", cap);
+    }
+    Ok(buf)
+}
+
+/// Parse mobile data state from logcat MultiSimSettingController lines.
+/// Returns "1" or "0", or None if no such line was logged.
+pub fn parse_logcat_data_state(log: &str) -> Option<&'static str> {
+    // Look for last MultiSimSettingController line
+    for line in log.lines().rev() {
+        if line.contains("MultiSimSettingController") && line.contains("mobile_data") {
+            // Example: I MultiSimSettingController: setMobileDataEnabled: enabled=true
+            if let Some(enabled_idx) = line.find("enabled=") {
+                let val = &line[enabled_idx + 8..];
+                if val.starts_with("true") {
+                    return Some("1");
+                } else if val.starts_with("false") {
+                    return Some("0");
+                }
             }
         }
     }
-
-    (level, charging)
+    None
 }
 
 /// Parse signal strength from telephony registry
 pub fn parse_signal(output: &str) -> i32 {
     for line in output.lines() {
         if line.contains("mSignalStrength") || line.contains("SignalStrength") {
-            // Look for dbm value (typically -50 to -120)
-            if let Some(pos) = line.find("rssi=") {
-                let rest = &line[pos + 5..];
-                if let Some(end) = rest.find(|c: char| !c.is_numeric() && c != '-') {
-                    if let Ok(dbm) = rest[..end].trim().parse::<i32>() {
+            // Look for dbm value (typically -50 to -120). A line can hold one
+            // rssi= per RAT, and modems report Integer.MAX_VALUE when unknown.
+            let mut rest = line;
+            while let Some(pos) = rest.find("rssi=") {
+                rest = &rest[pos + 5..];
+                let end = rest.find(|c: char| !c.is_numeric() && c != '-').unwrap_or(rest.len());
+                if let Ok(dbm) = rest[..end].trim().parse::<i32>() {
+                    if dbm != i32::MAX {
                         return dbm;
                     }
                 }
@@ -256,6 +456,39 @@ pub fn parse_signal(output: &str) -> i32 {
     -999 // Unknown
 }
 
+/// Parse if user mobile data is actually connected (not just IMS) from dumpsys connectivity output
+pub fn parse_mobile_data_connected(output: &str) -> bool {
+    let mut in_mobile_block = false;
+    let mut is_connected = false;
+    let mut is_user_data = false;
+    for line in output.lines() {
+        let l = line.trim();
+        let block_start = l.starts_with("NetworkAgentInfo");
+        // End of block: next NetworkAgentInfo or empty line
+        if in_mobile_block && (block_start || l.is_empty()) {
+            if is_connected && is_user_data {
+                return true;
+            }
+            in_mobile_block = false;
+        }
+        if block_start && l.contains("MOBILE") {
+            // New block, reset flags. DISCONNECTED agents linger after data is switched off.
+            in_mobile_block = true;
+            is_connected = l.contains("CONNECTED") && !l.contains("DISCONNECTED");
+            is_user_data = false;
+        }
+        if in_mobile_block {
+            // Look for extra: default/internet/ims on this or following lines
+            if l.contains("extra: ims") {
+                is_user_data = false;
+            } else if l.contains("extra: default") || l.contains("extra: internet") {
+                is_user_data = true;
+            }
+        }
+    }
+    in_mobile_block && is_connected && is_user_data
+}
+
 /// Parse uptime in seconds
 pub fn parse_uptime(output: &str) -> u64 {
     output
@@ -266,32 +499,30 @@ pub fn parse_uptime(output: &str) -> u64 {
         .unwrap_or(0)
 }
 
-/// Parse call forwarding state
-/// The service call output is complex, but we can check for specific indicators
-pub fn parse_call_forwarding(output: &str) -> bool {
-    // The output from "service call phone 13" is parcel data
-    // When call forwarding is active, the response contains non-zero status codes
-    // This is a simplified heuristic - may need adjustment based on device
-
-    // Look for "Result: Parcel" which indicates a response
-    if !output.contains("Result: Parcel") {
-        return false;
-    }
-
-    // If there's actual forwarding data, the parcel will be non-empty
-    // A simple heuristic: more than 50 chars of output suggests active forwarding
-    output.len() > 50
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::executor::fixtures::{expected_bool, expected_int, fixture_cases};
 
     #[test]
-    fn test_parse_battery() {
-        let output = "  level: 82\n  status: 3\n";
-        let (level, charging) = parse_battery(output);
-        assert_eq!(level, 82);
+    fn test_fixture_signal() {
+        for (name, output, expected) in fixture_cases("telephony_registry") {
+            assert_eq!(parse_signal(&output) as i64, expected_int(&expected, "signal_dbm"), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_fixture_mobile_data_connected() {
+        for (name, output, expected) in fixture_cases("connectivity") {
+            assert_eq!(parse_mobile_data_connected(&output), expected_bool(&expected, "connected"), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_fixture_uptime() {
+        for (name, output, expected) in fixture_cases("uptime") {
+            assert_eq!(parse_uptime(&output) as i64, expected_int(&expected, "uptime"), "{}", name);
+        }
     }
 
     #[test]
@@ -299,4 +530,47 @@ mod tests {
         let output = "12345.67 98765.43";
         assert_eq!(parse_uptime(output), 12345);
     }
+
+    #[tokio::test]
+    async fn test_read_capped_truncates() {
+        let data = vec![b'x'; 10_000];
+        let out = read_capped(&data[..], 100).await.unwrap();
+        assert_eq!(out.len(), 100);
+    }
+
+    #[test]
+    fn test_exec_error_classification() {
+        let err = ExecError::from_exit(Some(255), "java.lang.SecurityException: Permission Denial".into());
+        assert_eq!(err.code(), "permission_denied");
+        let err = ExecError::from_exit(Some(1), "Unknown command".into());
+        assert_eq!(err.code(), "non_zero_exit");
+        let err = ExecError::from_spawn("svc", std::io::ErrorKind::NotFound.into());
+        assert_eq!(err.code(), "not_found");
+    }
+
+    #[test]
+    fn test_parse_logcat_data_state() {
+        let log = "I MultiSimSettingController: mobile_data setMobileDataEnabled: enabled=false\n";
+        assert_eq!(parse_logcat_data_state(log), Some("0"));
+        assert_eq!(parse_logcat_data_state("unrelated line"), None);
+    }
+
+    #[tokio::test]
+    async fn test_shell_backend_missing_binary() {
+        // svc only exists on Android
+        let backend = ShellBackend::new(&ExecutorConfig::default());
+        if std::path::Path::new("/system/bin/svc").exists() {
+            return;
+        }
+        let err = backend.execute(&ShellCommand::EnableData).await.unwrap_err();
+        assert!(matches!(err, ExecError::NotFound { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_shell_backend_runs_command() {
+        // /proc/uptime exists on any Linux box, so this runs off-device too
+        let backend = ShellBackend::new(&ExecutorConfig::default());
+        let out = backend.execute(&ShellCommand::GetUptime).await.unwrap();
+        assert!(parse_uptime(&out) > 0);
+    }
 }
@@ -0,0 +1,797 @@
+use async_trait::async_trait;
+use log::info;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use super::airplane::{self, Radio, RadioSetting};
+use super::call_forwarding::ForwardingReason;
+use super::call_log::{CallLogEntry, CallType};
+use super::content::ContentUri;
+use super::shell::{ExecError, ShellCommand};
+use super::sms::{SmsKind, SmsMessage};
+use super::Backend;
+
+/// Mutable state of the simulated phone
+struct SimState {
+    battery_level: i32,
+    charging: bool,
+    signal_rssi: i32,
+    data_enabled: bool,
+    airplane_mode: bool,
+    /// Mobile data switch of the second SIM (subscription 2)
+    second_sim_data: bool,
+    /// Active forwarding: reason, number and no-reply timer
+    forwarding: Vec<(ForwardingReason, String, Option<u8>)>,
+    /// TelephonyManager.CALL_STATE_* of the first SIM
+    call_state: u8,
+    /// Caller while ringing
+    incoming_number: Option<String>,
+    /// Unix ms at which the current call rang or was dialed
+    call_started: Option<i64>,
+    /// Finished calls, oldest first
+    call_log: Vec<CallLogEntry>,
+    /// Received and sent messages, oldest first
+    messages: Vec<SmsMessage>,
+    /// Open USSD menu: 0 none, 1 main menu, 2 data packs
+    ussd_menu: u8,
+    /// GsmMmiCode radio log lines with their Unix ms
+    mmi_log: Vec<(i64, String)>,
+    /// Numbers dialed, the last one is the outgoing call
+    dialed: Vec<String>,
+    wifi_enabled: bool,
+    /// Network Wi-Fi is connected to
+    wifi_ssid: Option<String>,
+    hotspot: bool,
+    usb_tethering: bool,
+    bluetooth_enabled: bool,
+    /// airplane_mode_radios and airplane_mode_toggleable_radios, None while unset
+    airplane_radios: Option<String>,
+    airplane_toggleable: Option<String>,
+    /// Wi-Fi and Bluetooth before airplane mode switched them off
+    before_airplane: Option<(bool, bool)>,
+}
+
+/// Backend that pretends to be a phone, so the daemon runs on a dev box.
+/// Outputs mimic the real commands so the same parsers are exercised.
+pub struct SimulatedBackend {
+    started: Instant,
+    state: Mutex<SimState>,
+}
+
+impl SimulatedBackend {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            state: Mutex::new(SimState {
+                battery_level: 82,
+                charging: false,
+                signal_rssi: -93,
+                data_enabled: true,
+                airplane_mode: false,
+                second_sim_data: false,
+                forwarding: Vec::new(),
+                call_state: 0,
+                incoming_number: None,
+                call_started: None,
+                call_log: seed_call_log(),
+                messages: seed_inbox(),
+                ussd_menu: 0,
+                mmi_log: Vec::new(),
+                dialed: Vec::new(),
+                wifi_enabled: true,
+                wifi_ssid: Some("SimulatedWiFi".to_string()),
+                hotspot: false,
+                usb_tethering: false,
+                bluetooth_enabled: true,
+                airplane_radios: None,
+                airplane_toggleable: None,
+                before_airplane: None,
+            }),
+        }
+    }
+}
+
+impl SimulatedBackend {
+    /// Let a call come in on the first SIM
+    #[cfg(test)]
+    pub fn ring(&self, from: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.call_state = 1;
+        state.incoming_number = Some(from.to_string());
+        state.call_started = Some(chrono::Utc::now().timestamp_millis());
+    }
+
+    /// Deliver a text to the inbox, as if it came in over the network
+    #[cfg(test)]
+    pub fn receive_sms(&self, from: &str, body: &str) {
+        let mut state = self.state.lock().unwrap();
+        let now = chrono::Utc::now().timestamp_millis();
+        let id = state.messages.iter().map(|m| m.id).max().unwrap_or(0) + 1;
+        state.messages.push(SmsMessage {
+            id,
+            thread_id: Some(id),
+            address: Some(from.to_string()),
+            body: body.to_string(),
+            date: now,
+            date_sent: Some(now - 2_000),
+            read: false,
+            kind: SmsKind::Inbox,
+            status: Some(-1),
+            subscription_id: Some(1),
+        });
+    }
+}
+
+#[async_trait]
+impl Backend for SimulatedBackend {
+    async fn execute(&self, command: &ShellCommand) -> Result<String, ExecError> {
+        let mut state = self.state.lock().unwrap();
+
+        let output = match command {
+            ShellCommand::GetBattery => {
+                // status: 2 = charging, 3 = discharging
+                format!(
+                    "Current Battery Service state:\n  AC powered: {}\n  USB powered: false\n  Wireless powered: false\n  \
+                     Max charging current: {}\n  Charge counter: {}\n  status: {}\n  health: 2\n  present: true\n  \
+                     level: {}\n  scale: 100\n  voltage: 3921\n  temperature: 287\n  technology: Li-ion\n",
+                    state.charging,
+                    if state.charging { 2_000_000 } else { 0 },
+                    state.battery_level * 40_000,
+                    if state.charging { 2 } else { 3 },
+                    state.battery_level,
+                )
+            }
+            ShellCommand::GetSignal => {
+                let mut out = format!(
+                    "last known state:\n  Phone Id=0\n  mCallState={}\n  mCallIncomingNumber={}\n",
+                    state.call_state,
+                    state.incoming_number.as_deref().unwrap_or_default()
+                );
+                if state.airplane_mode {
+                    out.push_str(
+                        "  mServiceState={mVoiceRegState=3(POWER_OFF), mDataRegState=3(POWER_OFF), \
+                         mOperatorAlphaLong=null, getRilVoiceRadioTechnology=0(Unknown), \
+                         getRilDataRadioTechnology=0(Unknown), mNetworkRegistrationInfos=[]}\n",
+                    );
+                } else {
+                    out.push_str(
+                        "  mServiceState={mVoiceRegState=0(IN_SERVICE), mDataRegState=0(IN_SERVICE), \
+                         mOperatorAlphaLong=Simulated, getRilVoiceRadioTechnology=14(LTE), \
+                         getRilDataRadioTechnology=14(LTE), mNetworkRegistrationInfos=[NetworkRegistrationInfo{ \
+                         domain=PS transportType=WWAN registrationState=HOME accessNetworkTechnology=LTE \
+                         cellIdentity=CellIdentityLte:{ mCi=1234567 mPci=1 mTac=4321 mMcc=001 mMnc=01} nrState=NONE}]}\n",
+                    );
+                    out.push_str(&format!(
+                        "  mSignalStrength=SignalStrength:{{mCdma=Invalid,mGsm=Invalid,mWcdma=Invalid,mTdscdma=Invalid,\
+                         mLte=CellSignalStrengthLte: rssi={} rsrp={} rsrq=-13 rssnr=3 cqi=2147483647 ta=2147483647 level=2,\
+                         mNr=Invalid,primary=CellSignalStrengthLte,voice level=2,data level=2,isGsm=true}}\n",
+                        state.signal_rssi,
+                        state.signal_rssi - 19
+                    ));
+                }
+                let connected = state.data_enabled && !state.airplane_mode;
+                out.push_str(&format!(
+                    "  mUserMobileDataState={}\n  mDataConnectionState={}\n",
+                    state.data_enabled,
+                    if connected { 2 } else { 0 }
+                ));
+
+                // Second SIM, roaming on 3G without data
+                out.push_str("  Phone Id=1\n  mCallState=0\n  mCallIncomingNumber=\n");
+                if !state.airplane_mode {
+                    out.push_str(
+                        "  mServiceState={mVoiceRegState=0(IN_SERVICE), mDataRegState=0(IN_SERVICE), \
+                         mOperatorAlphaLong=Simulated Roaming, getRilVoiceRadioTechnology=3(UMTS), \
+                         getRilDataRadioTechnology=11(HSPAP), mNetworkRegistrationInfos=[NetworkRegistrationInfo{ \
+                         domain=CS transportType=WWAN registrationState=ROAMING accessNetworkTechnology=UMTS \
+                         cellIdentity=CellIdentityWcdma:{ mLac=77 mCid=9876 mMcc=001 mMnc=02} nrState=NONE}]}\n  \
+                         mSignalStrength=SignalStrength:{mCdma=Invalid,mGsm=Invalid,mWcdma=CellSignalStrengthWcdma: \
+                         ss=-89 ber=99 rscp=-101 ecno=-11 level=2,mTdscdma=Invalid,mLte=Invalid,mNr=Invalid,\
+                         primary=CellSignalStrengthWcdma}\n",
+                    );
+                }
+                out.push_str(&format!(
+                    "  mUserMobileDataState={}\n  mDataConnectionState=0\n",
+                    state.second_sim_data
+                ));
+                out
+            }
+            ShellCommand::GetSubscriptions => "SubscriptionController:\n defaultSubId=1\n defaultDataSubId=1\n \
+                 ActiveSubInfoList:\n  {id=1 iccId=8900****[8] simSlotIndex=0 displayName=Simulated carrierName=Simulated}\n  \
+                 {id=2 iccId=8900****[8] simSlotIndex=1 displayName=Simulated Roaming carrierName=Simulated Roaming}\n"
+                .to_string(),
+            // Filtering is left to the caller
+            ShellCommand::ContentQuery(query) => {
+                let rows: String = match query.uri {
+                    ContentUri::Sms => state.messages.iter().rev().enumerate().map(|(i, m)| sms_row(i, m)).collect(),
+                    ContentUri::CallLog => {
+                        state.call_log.iter().rev().enumerate().map(|(i, c)| call_log_row(i, c)).collect()
+                    }
+                };
+                if rows.is_empty() { "No result found.\n".to_string() } else { rows }
+            }
+            ShellCommand::GetTelephonyProperties => {
+                // Android clears the operator properties while the radio is off
+                let (network_type, alpha, numeric) =
+                    if state.airplane_mode { ("Unknown", "", "") } else { ("LTE", "Simulated", "00101") };
+                format!(
+                    "[gsm.network.type]: [{}]\n[gsm.operator.alpha]: [{}]\n[gsm.operator.isroaming]: [false]\n\
+                     [gsm.operator.numeric]: [{}]\n[gsm.sim.state]: [READY]\n[ro.product.model]: [Simulator]\n",
+                    network_type, alpha, numeric
+                )
+            }
+            ShellCommand::GetDataState | ShellCommand::GetDataStateLogcat => {
+                if state.data_enabled { "1" } else { "0" }.to_string()
+            }
+            ShellCommand::GetMobileDataConnection => {
+                let mobile = state.data_enabled && !state.airplane_mode;
+                // Wi-Fi wins the default network while both are up
+                let default = match (&state.wifi_ssid, mobile) {
+                    (Some(_), _) => "101",
+                    (None, true) => "100",
+                    (None, false) => "none",
+                };
+                let mut output = format!("Active default network: {}\n\nCurrent Networks:\n", default);
+                if mobile {
+                    output.push_str(
+                        "  NetworkAgentInfo{network{100}  ni{MOBILE[LTE] CONNECTED extra: internet}  \
+                         lp{{InterfaceName: rmnet_data2 LinkAddresses: [ 10.167.12.34/30 ]}}  nc{[ Transports: CELLULAR ]}}\n",
+                    );
+                }
+                if let Some(ssid) = &state.wifi_ssid {
+                    output.push_str(&format!(
+                        "  NetworkAgentInfo{{network{{101}}  ni{{WIFI CONNECTED extra: }}  \
+                         lp{{{{InterfaceName: wlan0 LinkAddresses: [ 192.168.232.2/24 ]}}}}  \
+                         nc{{[ Transports: WIFI SSID: \"{}\"]}}}}\n",
+                        ssid
+                    ));
+                }
+                output.push('\n');
+                output
+            }
+            ShellCommand::GetRoute(address) => {
+                // Tailscale hands out 100.64.0.0/10, most else goes out the default network
+                let interface = match address {
+                    IpAddr::V4(v4) if v4.is_loopback() => "lo",
+                    IpAddr::V6(v6) if v6.is_loopback() => "lo",
+                    IpAddr::V4(v4) if v4.octets()[0] == 100 && v4.octets()[1] & 0xc0 == 64 => "tun0",
+                    // TEST-NET-1 leaves through an interface the daemon has no name for
+                    IpAddr::V4(v4) if v4.octets()[..3] == [192, 0, 2] => "vendor0",
+                    _ if state.wifi_ssid.is_some() => "wlan0",
+                    _ if state.data_enabled && !state.airplane_mode => "rmnet_data2",
+                    _ => {
+                        return Err(ExecError::NonZeroExit {
+                            code: Some(2),
+                            stderr: "RTNETLINK answers: Network is unreachable".to_string(),
+                        })
+                    }
+                };
+                format!("{} dev {} table 1021 uid 0 \n    cache \n", address, interface)
+            }
+            ShellCommand::GetAirplaneMode => {
+                format!("{}\n", if state.airplane_mode { 1 } else { 0 })
+            }
+            ShellCommand::GetUptime => {
+                // Pretend the phone booted an hour before the daemon
+                let secs = 3600.0 + self.started.elapsed().as_secs_f64();
+                format!("{:.2} {:.2}\n", secs, secs * 4.0)
+            }
+            ShellCommand::EnableData => {
+                state.data_enabled = true;
+                String::new()
+            }
+            ShellCommand::DisableData => {
+                state.data_enabled = false;
+                String::new()
+            }
+            ShellCommand::SetSubscriptionData { sub_id, enable } => {
+                match sub_id {
+                    1 => state.data_enabled = *enable,
+                    2 => state.second_sim_data = *enable,
+                    _ => {}
+                }
+                String::new()
+            }
+            ShellCommand::EnableAirplaneMode => {
+                if !state.airplane_mode {
+                    // Like the real services, read the setting as airplane mode switches
+                    state.before_airplane = Some((state.wifi_enabled, state.bluetooth_enabled));
+                    if airplane::is_sensitive(state.airplane_radios.as_deref(), Radio::Wifi) {
+                        state.wifi_enabled = false;
+                        state.wifi_ssid = None;
+                    }
+                    if airplane::is_sensitive(state.airplane_radios.as_deref(), Radio::Bluetooth) {
+                        state.bluetooth_enabled = false;
+                    }
+                }
+                state.airplane_mode = true;
+                String::new()
+            }
+            ShellCommand::DisableAirplaneMode => {
+                if let Some((wifi, bluetooth)) = state.before_airplane.take() {
+                    if wifi && !state.wifi_enabled {
+                        state.wifi_enabled = true;
+                        state.wifi_ssid = Some("SimulatedWiFi".to_string());
+                    }
+                    state.bluetooth_enabled |= bluetooth;
+                }
+                state.airplane_mode = false;
+                String::new()
+            }
+            ShellCommand::GetAirplaneRadios(setting) => {
+                let value = match setting {
+                    RadioSetting::Radios => &state.airplane_radios,
+                    RadioSetting::Toggleable => &state.airplane_toggleable,
+                };
+                format!("{}\n", value.as_deref().unwrap_or("null"))
+            }
+            ShellCommand::SetAirplaneRadios(setting, radios) => {
+                match setting {
+                    RadioSetting::Radios => state.airplane_radios = Some(radios.clone()),
+                    RadioSetting::Toggleable => state.airplane_toggleable = Some(radios.clone()),
+                }
+                String::new()
+            }
+            ShellCommand::ResetAirplaneRadios(setting) => {
+                match setting {
+                    RadioSetting::Radios => state.airplane_radios = None,
+                    RadioSetting::Toggleable => state.airplane_toggleable = None,
+                }
+                "Deleted 1 rows\n".to_string()
+            }
+            ShellCommand::EnableCallForwarding { reason, number, timer, sub_id } => {
+                info!("[simulator] {:?} call forwarding to {} on subscription {}", reason, number, sub_id);
+                // The network refuses changes it cannot be reached for
+                let answer = if state.airplane_mode {
+                    "Connection problem or invalid MMI code."
+                } else {
+                    state.forwarding.retain(|(r, _, _)| r != reason);
+                    let timer = if *reason == ForwardingReason::NoReply { Some(timer.unwrap_or(20)) } else { None };
+                    state.forwarding.push((*reason, number.clone(), timer));
+                    "Registration was successful."
+                };
+                log_mmi(&mut state.mmi_log, "onSetComplete: ", answer);
+                "Result: Parcel(00000000 00000001   '........')\n".to_string()
+            }
+            ShellCommand::DisableCallForwarding { reason, sub_id } => {
+                info!("[simulator] {:?} call forwarding disabled on subscription {}", reason, sub_id);
+                state.forwarding.retain(|(r, _, _)| r != reason);
+                log_mmi(&mut state.mmi_log, "onSetComplete: ", "Service has been disabled.");
+                "Result: Parcel(00000000 00000001   '........')\n".to_string()
+            }
+            ShellCommand::QueryCallForwarding { reason, .. } => {
+                let answer = match state.forwarding.iter().find(|(r, _, _)| r == reason) {
+                    Some((_, number, Some(timer))) => format!("Voice: {} after {} seconds\nData: Not forwarded", number, timer),
+                    Some((_, number, None)) => format!("Voice: {}\nData: Not forwarded", number),
+                    None => "Service has been disabled.".to_string(),
+                };
+                log_mmi(&mut state.mmi_log, "onQueryCfComplete: ", &answer);
+                "Result: Parcel(00000000 00000001   '........')\n".to_string()
+            }
+            ShellCommand::DialNumber(number) => {
+                if state.airplane_mode {
+                    return Err(ExecError::NonZeroExit {
+                        code: Some(1),
+                        stderr: "no service (airplane mode)".to_string(),
+                    });
+                }
+                info!("[simulator] dialing {}", number);
+                state.dialed.push(number.clone());
+                if state.call_state == 0 {
+                    state.call_state = 2;
+                    state.call_started = Some(chrono::Utc::now().timestamp_millis());
+                }
+                format!("Starting: Intent {{ act=android.intent.action.CALL dat=tel:{} }}\n", number)
+            }
+            // Key events print nothing and do nothing without a matching call
+            ShellCommand::AnswerCall => {
+                if state.call_state == 1 {
+                    state.call_state = 2;
+                }
+                String::new()
+            }
+            ShellCommand::EndCall => {
+                if state.call_state != 0 {
+                    let now = chrono::Utc::now().timestamp_millis();
+                    let date = state.call_started.take().unwrap_or(now);
+                    let incoming = state.incoming_number.take();
+                    let (kind, number, duration) = match (state.call_state, incoming) {
+                        (1, number) => (CallType::Rejected, number, 0),
+                        (_, Some(number)) => (CallType::Incoming, Some(number), (now - date) / 1000),
+                        (_, None) => (CallType::Outgoing, state.dialed.last().cloned(), (now - date) / 1000),
+                    };
+                    let id = state.call_log.iter().map(|c| c.id).max().unwrap_or(0) + 1;
+                    state.call_log.push(CallLogEntry {
+                        id,
+                        number,
+                        name: None,
+                        date,
+                        duration,
+                        kind,
+                        subscription_id: Some(1),
+                        slot: None,
+                        new: false,
+                    });
+                    state.call_state = 0;
+                }
+                String::new()
+            }
+            ShellCommand::SendUssd { code, sub_id } => {
+                info!("[simulator] USSD {} on subscription {}", code, sub_id);
+                let (reply, menu) = match (state.ussd_menu, code.as_str()) {
+                    (1, "1") | (0, "*100#") => (Some("Balance: $5.20, valid until 31/12."), 0),
+                    (1, "2") => (Some("Data packs\n1. 1 GB for $5\n2. 5 GB for $15"), 2),
+                    (2, "1" | "2") => (Some("Data pack activated."), 0),
+                    (0, "*123#") => (Some("Simulated Menu\n1. Balance\n2. Data packs"), 1),
+                    (0, _) => (None, 0),
+                    _ => (Some("Invalid choice."), 0),
+                };
+                state.ussd_menu = menu;
+                match reply {
+                    Some(reply) => {
+                        log_mmi(&mut state.mmi_log, "onUssdFinished: ussdMessage=", reply);
+                        let state_name = if menu > 0 { "PENDING" } else { "COMPLETE" };
+                        let mmi = format!("GsmMmiCode {{State={} action=1}}", state_name);
+                        log_mmi(&mut state.mmi_log, "onUssdFinished: ", &mmi);
+                    }
+                    None => log_mmi(&mut state.mmi_log, "onUssdFinishedError", ""),
+                }
+                "Result: Parcel(00000000 00000001   '........')\n".to_string()
+            }
+            ShellCommand::GetMmiLog { since_ms } => state
+                .mmi_log
+                .iter()
+                .filter(|(at, _)| at >= since_ms)
+                .map(|(at, line)| format!("{}.{:03}  4242  4242 D GsmMmiCode: {}\n", at / 1000, at % 1000, line))
+                .collect(),
+            ShellCommand::SendSms { number, parts, sub_id } => {
+                // Persisted like the framework does, failed when there is no radio
+                let now = chrono::Utc::now().timestamp_millis();
+                let id = state.messages.iter().map(|m| m.id).max().unwrap_or(0) + 1;
+                info!("[simulator] SMS to {} in {} part(s) on subscription {}", number, parts.len(), sub_id);
+                let kind = if state.airplane_mode { SmsKind::Failed } else { SmsKind::Sent };
+                state.messages.push(SmsMessage {
+                    id,
+                    thread_id: Some(id),
+                    address: Some(number.clone()),
+                    body: parts.concat(),
+                    date: now,
+                    date_sent: None,
+                    read: true,
+                    kind,
+                    status: Some(-1),
+                    subscription_id: Some(*sub_id),
+                });
+                "Result: Parcel(00000000    '....')\n".to_string()
+            }
+            ShellCommand::GetWifiStatus => {
+                let mut output = format!("Wifi is {}\n", if state.wifi_enabled { "enabled" } else { "disabled" });
+                match &state.wifi_ssid {
+                    Some(ssid) => output.push_str(&format!(
+                        "Wifi is connected to \"{}\"\nWifiInfo: SSID: {}, BSSID: 02:15:b2:00:01:00, Supplicant state: COMPLETED, RSSI: -61, Link speed: 72Mbps, Frequency: 2437MHz, Net ID: 0\n",
+                        ssid, ssid
+                    )),
+                    None => output.push_str("Wifi is not connected\n"),
+                }
+                output
+            }
+            ShellCommand::GetWifiAddress => match state.wifi_ssid {
+                Some(_) => "30: wlan0    inet 192.168.232.2/24 brd 192.168.232.255 scope global wlan0\n".to_string(),
+                None => String::new(),
+            },
+            ShellCommand::EnableWifi => {
+                state.wifi_enabled = true;
+                String::new()
+            }
+            ShellCommand::DisableWifi => {
+                state.wifi_enabled = false;
+                state.wifi_ssid = None;
+                String::new()
+            }
+            ShellCommand::ConnectWifi(network) => {
+                if !state.wifi_enabled {
+                    return Err(ExecError::NonZeroExit { code: Some(255), stderr: "Wifi is disabled".to_string() });
+                }
+                info!("[simulator] joining Wi-Fi {}", network.ssid);
+                state.wifi_ssid = Some(network.ssid.clone());
+                "Connection initiated\n".to_string()
+            }
+            ShellCommand::GetTetheringState => {
+                let mut tether_state = String::new();
+                if state.hotspot {
+                    tether_state.push_str("    wlan1 - TetheredState - lastError = 0\n");
+                }
+                if state.usb_tethering {
+                    tether_state.push_str("    rndis0 - TetheredState - lastError = 0\n");
+                }
+                let upstream = if tether_state.is_empty() { "null" } else { "[rmnet_data2]" };
+                format!(
+                    "Tethering:\n  Tether state:\n{}  Upstream wanted: {}\n  Current upstream interface(s): {}\n",
+                    tether_state,
+                    !tether_state.is_empty(),
+                    upstream
+                )
+            }
+            ShellCommand::GetInterfaceCounters => {
+                // Some traffic for every second the daemon has run
+                let secs = self.started.elapsed().as_secs();
+                let mut output = "Inter-|   Receive                                                |  Transmit\n \
+                     face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed\n"
+                    .to_string();
+                let mut interface = |name: &str, rx: u64, tx: u64| {
+                    output.push_str(&format!("{:>6}: {} 0 0 0 0 0 0 0 {} 0 0 0 0 0 0 0\n", name, rx, tx))
+                };
+                interface("lo", 8456, 8456);
+                interface("rmnet_data2", 1_200_000 + secs * 9_000, 150_000 + secs * 1_500);
+                if state.hotspot {
+                    interface("wlan1", 20_000 + secs * 1_000, 100_000 + secs * 8_000);
+                }
+                if state.usb_tethering {
+                    interface("rndis0", 10_000 + secs * 500, 50_000 + secs * 4_000);
+                }
+                output
+            }
+            ShellCommand::GetNeighbours => {
+                let mut output = "10.167.12.1 dev rmnet_data2  NOARP\n".to_string();
+                if state.hotspot {
+                    output.push_str("192.168.43.120 dev wlan1 lladdr 5a:11:22:33:44:55 REACHABLE\n");
+                }
+                if state.usb_tethering {
+                    output.push_str("192.168.42.35 dev rndis0 lladdr 02:8e:61:7c:00:01 STALE\n");
+                }
+                output
+            }
+            ShellCommand::StartHotspot(network) => {
+                info!("[simulator] hotspot {} started", network.ssid);
+                state.hotspot = true;
+                String::new()
+            }
+            ShellCommand::StopHotspot => {
+                state.hotspot = false;
+                String::new()
+            }
+            ShellCommand::StartUsbTethering(_) => {
+                state.usb_tethering = true;
+                String::new()
+            }
+            ShellCommand::StopUsbTethering => {
+                state.usb_tethering = false;
+                String::new()
+            }
+            ShellCommand::GetBluetoothState => {
+                let mut output = format!(
+                    "Bluetooth Status\n  enabled: {}\n  state: {}\n  name: Simulated Phone\n\n",
+                    state.bluetooth_enabled,
+                    if state.bluetooth_enabled { "ON" } else { "OFF" }
+                );
+                // The adapter service only dumps its devices while it runs
+                if state.bluetooth_enabled {
+                    output.push_str(
+                        "AdapterProperties\n  State: ON\n  Bonded devices:\n    \
+                         F4:4E:FC:11:22:33 [BR/EDR][ 0x240404 ] Simulated Car\n    \
+                         0C:A6:94:44:55:66 [ DUAL ][ 0x240418 ] Simulated Buds\n\n\
+                         Profile: A2dpService\n  mDevice: F4:4E:FC:11:22:33\n    \
+                         StateMachine: name=A2dpStateMachine state=Connected\n",
+                    );
+                }
+                output
+            }
+            ShellCommand::EnableBluetooth => {
+                state.bluetooth_enabled = true;
+                String::new()
+            }
+            ShellCommand::DisableBluetooth => {
+                state.bluetooth_enabled = false;
+                String::new()
+            }
+        };
+
+        Ok(output)
+    }
+}
+
+/// A few messages so /sms has something to page through
+fn seed_inbox() -> Vec<SmsMessage> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let message = |id: i64, address: &str, body: &str, minutes_ago: i64, read: bool| SmsMessage {
+        id,
+        thread_id: Some(id),
+        address: Some(address.to_string()),
+        body: body.to_string(),
+        date: now - minutes_ago * 60_000,
+        date_sent: Some(now - minutes_ago * 60_000 - 2_000),
+        read,
+        kind: SmsKind::Inbox,
+        status: Some(-1),
+        subscription_id: Some(1),
+    };
+    vec![
+        message(1, "Simulated", "Welcome to Simulated Mobile.", 3 * 24 * 60, true),
+        message(2, "+15550100", "Hi, are we still on for Friday?", 26 * 60, true),
+        message(3, "Simulated", "You have used 80% of your data.", 90, false),
+        message(4, "SimBank", "Your verification code is 123456.", 5, false),
+    ]
+}
+
+/// Log an MMI event the way GsmMmiCode does: a multi-line text becomes
+/// several lines with the same time
+fn log_mmi(log: &mut Vec<(i64, String)>, event: &str, text: &str) {
+    let now = chrono::Utc::now().timestamp_millis();
+    let mut lines = text.lines();
+    log.push((now, format!("{}{}", event, lines.next().unwrap_or_default())));
+    log.extend(lines.map(|line| (now, line.to_string())));
+}
+
+/// A missed call and some history so /calls has something to show
+fn seed_call_log() -> Vec<CallLogEntry> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let call = |id, number: &str, minutes_ago: i64, duration, kind, subscription_id| CallLogEntry {
+        id,
+        number: Some(number.to_string()),
+        name: None,
+        date: now - minutes_ago * 60_000,
+        duration,
+        kind,
+        subscription_id: Some(subscription_id),
+        slot: None,
+        new: kind == CallType::Missed,
+    };
+    vec![
+        call(1, "+15550100", 2 * 24 * 60, 312, CallType::Incoming, 1),
+        call(2, "+15550123", 30 * 60, 45, CallType::Outgoing, 2),
+        call(3, "+15550100", 40, 0, CallType::Missed, 1),
+    ]
+}
+
+/// One call log row in projection order, with the row end columns
+fn call_log_row(index: usize, call: &CallLogEntry) -> String {
+    format!(
+        "Row: {} _id={}, number={}, date={}, duration={}, type={}, subscription_id={}, new={}, name={}, _id={}, date={}\n",
+        index,
+        call.id,
+        call.number.as_deref().unwrap_or_default(),
+        call.date,
+        call.duration,
+        call.kind.code(),
+        call.subscription_id.unwrap_or(-1),
+        if call.new { 1 } else { 0 },
+        call.name.as_deref().unwrap_or("NULL"),
+        call.id,
+        call.date
+    )
+}
+
+/// One `content query` row in projection order, with the row end columns
+fn sms_row(index: usize, message: &SmsMessage) -> String {
+    format!(
+        "Row: {} _id={}, thread_id={}, address={}, date={}, date_sent={}, read={}, type={}, status={}, sub_id={}, body={}, _id={}, date={}\n",
+        index,
+        message.id,
+        message.thread_id.unwrap_or_default(),
+        message.address.as_deref().unwrap_or("NULL"),
+        message.date,
+        message.date_sent.unwrap_or_default(),
+        if message.read { 1 } else { 0 },
+        match message.kind {
+            SmsKind::Inbox => 1,
+            SmsKind::Sent => 2,
+            _ => 5,
+        },
+        message.status.unwrap_or(-1),
+        message.subscription_id.unwrap_or(-1),
+        message.body,
+        message.id,
+        message.date
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::call_log::{self, CallLogFilter};
+    use crate::executor::sms::{self, InboxFilter};
+    use crate::executor::{battery, shell, telephony};
+
+    async fn connected(sim: &SimulatedBackend) -> bool {
+        let out = sim.execute(&ShellCommand::GetMobileDataConnection).await.unwrap();
+        shell::parse_mobile_data_connected(&out)
+    }
+
+    fn forwarding(sim: &SimulatedBackend) -> bool {
+        !sim.state.lock().unwrap().forwarding.is_empty()
+    }
+
+    #[tokio::test]
+    async fn test_data_toggle_changes_status() {
+        let sim = SimulatedBackend::new();
+        assert!(connected(&sim).await);
+        sim.execute(&ShellCommand::DisableData).await.unwrap();
+        assert!(!connected(&sim).await);
+        sim.execute(&ShellCommand::EnableData).await.unwrap();
+        assert!(connected(&sim).await);
+    }
+
+    #[tokio::test]
+    async fn test_airplane_mode_drops_data_and_signal() {
+        let sim = SimulatedBackend::new();
+        sim.execute(&ShellCommand::EnableAirplaneMode).await.unwrap();
+
+        let airplane = sim.execute(&ShellCommand::GetAirplaneMode).await.unwrap();
+        assert_eq!(airplane.trim(), "1");
+        let data = sim.execute(&ShellCommand::GetMobileDataConnection).await.unwrap();
+        assert!(!shell::parse_mobile_data_connected(&data));
+        let signal = sim.execute(&ShellCommand::GetSignal).await.unwrap();
+        assert_eq!(shell::parse_signal(&signal), -999);
+        assert_eq!(telephony::parse_signal_info(&signal), None);
+        assert!(sim.execute(&ShellCommand::DialNumber("+1234567890".into())).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_forwarding_and_dial_are_recorded() {
+        let sim = SimulatedBackend::new();
+        assert!(!forwarding(&sim));
+        let number = "+1234567890".to_string();
+        let reason = ForwardingReason::Unconditional;
+        sim.execute(&ShellCommand::EnableCallForwarding { reason, number, timer: None, sub_id: 1 }).await.unwrap();
+        assert!(forwarding(&sim));
+        sim.execute(&ShellCommand::DisableCallForwarding { reason, sub_id: 1 }).await.unwrap();
+        assert!(!forwarding(&sim));
+
+        sim.execute(&ShellCommand::DialNumber("+1234567890".into())).await.unwrap();
+        assert_eq!(sim.state.lock().unwrap().dialed, vec!["+1234567890"]);
+    }
+
+    #[tokio::test]
+    async fn test_battery_parses() {
+        let sim = SimulatedBackend::new();
+        let out = sim.execute(&ShellCommand::GetBattery).await.unwrap();
+        let info = battery::parse_battery_info(&out).unwrap();
+        assert_eq!((info.level, info.charging), (82, false));
+        assert_eq!(info.temperature_c, Some(28.7));
+    }
+
+    #[tokio::test]
+    async fn test_inbox_parses() {
+        let sim = SimulatedBackend::new();
+        let query = InboxFilter::default().query();
+        let out = sim.execute(&ShellCommand::ContentQuery(query)).await.unwrap();
+        let messages = sms::parse_messages(&out);
+        assert_eq!(messages.len(), 4);
+        assert_eq!(messages[0].id, 4);
+        assert_eq!(messages[0].body, "Your verification code is 123456.");
+    }
+
+    #[tokio::test]
+    async fn test_sent_sms_is_persisted() {
+        let sim = SimulatedBackend::new();
+        let parts = sms::split_parts(&"a".repeat(200));
+        let out = sim.execute(&ShellCommand::SendSms { number: "+15550100".into(), parts, sub_id: 1 }).await.unwrap();
+        assert!(sms::parse_send_result(&out).is_ok());
+
+        let out = sim.execute(&ShellCommand::ContentQuery(sms::outgoing_query(0))).await.unwrap();
+        let sent = sms::parse_messages(&out).remove(0);
+        assert_eq!(sent.body.len(), 200);
+        assert_eq!(sms::SendStatus::of(&sent), sms::SendStatus::Sent);
+    }
+
+    #[tokio::test]
+    async fn test_ended_calls_are_logged() {
+        let sim = SimulatedBackend::new();
+        let query = ShellCommand::ContentQuery(CallLogFilter::default().query());
+        let seeded = call_log::parse_calls(&sim.execute(&query).await.unwrap());
+        assert_eq!(seeded[0].kind, CallType::Missed);
+
+        sim.ring("+15550100");
+        sim.execute(&ShellCommand::EndCall).await.unwrap();
+        sim.execute(&ShellCommand::DialNumber("+15550123".into())).await.unwrap();
+        sim.execute(&ShellCommand::EndCall).await.unwrap();
+        // No call, nothing to log
+        sim.execute(&ShellCommand::EndCall).await.unwrap();
+
+        let calls = call_log::parse_calls(&sim.execute(&query).await.unwrap());
+        assert_eq!(calls.len(), seeded.len() + 2);
+        assert_eq!((calls[0].kind, calls[0].number.as_deref()), (CallType::Outgoing, Some("+15550123")));
+        assert_eq!((calls[1].kind, calls[1].number.as_deref()), (CallType::Rejected, Some("+15550100")));
+    }
+}
@@ -0,0 +1,286 @@
+//! SMS read through the `content://sms` provider, and splitting texts for sending
+
+use serde::Serialize;
+
+use super::content::{self, ContentQuery, ContentUri};
+use super::parcel::Parcel;
+use super::shell::ExecError;
+
+/// Body last: it is free text and may hold ", " or newlines
+const COLUMNS: &[&str] =
+    &["_id", "thread_id", "address", "date", "date_sent", "read", "type", "status", "sub_id", "body"];
+
+/// Telephony.Sms.MESSAGE_TYPE_INBOX
+const MESSAGE_TYPE_INBOX: i32 = 1;
+
+/// Longest text accepted for sending, in parts
+pub const MAX_PARTS: usize = 10;
+
+/// GSM 03.38 default alphabet; anything else is sent as UCS-2
+const GSM_BASIC: &str = "@£$¥èéùìòÇ\nØø\rÅåΔ_ΦΓΛΩΠΨΣΘΞÆæßÉ !\"#¤%&'()*+,-./0123456789:;<=>?\
+                         ¡ABCDEFGHIJKLMNOPQRSTUVWXYZÄÖÑÜ§¿abcdefghijklmnopqrstuvwxyzäöñüà";
+/// Extension table characters, two septets each
+const GSM_EXTENDED: &str = "^{}\\[~]|€\x0c";
+
+/// Telephony.Sms.MESSAGE_TYPE_*
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmsKind {
+    Inbox,
+    Sent,
+    Draft,
+    Outbox,
+    Failed,
+    Queued,
+    Unknown,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SmsMessage {
+    pub id: i64,
+    pub thread_id: Option<i64>,
+    /// Sender number, or a name for carrier and service messages
+    pub address: Option<String>,
+    pub body: String,
+    /// Unix ms at which the phone received (or queued) the message
+    pub date: i64,
+    /// Unix ms at which the sender's SMSC sent it, if the network reported it
+    pub date_sent: Option<i64>,
+    pub read: bool,
+    pub kind: SmsKind,
+    /// Delivery report status (Telephony.Sms.STATUS_*), -1 when none was requested
+    #[serde(skip)]
+    pub status: Option<i32>,
+    /// Subscription the message arrived on
+    pub subscription_id: Option<i32>,
+}
+
+/// Which inbox messages to read
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct InboxFilter {
+    /// Only messages received after this Unix ms
+    pub since: Option<i64>,
+    pub unread_only: bool,
+}
+
+impl InboxFilter {
+    /// Newest first. The selection holds only numbers formatted here.
+    pub fn query(&self) -> ContentQuery {
+        let mut selection = format!("type={}", MESSAGE_TYPE_INBOX);
+        if let Some(since) = self.since {
+            selection.push_str(&format!(" AND date>{}", since));
+        }
+        if self.unread_only {
+            selection.push_str(" AND read=0");
+        }
+        sms_query(selection, "date DESC")
+    }
+
+    /// Whether a parsed message passes the filter, for backends that ignore the selection
+    pub fn matches(&self, message: &SmsMessage) -> bool {
+        message.kind == SmsKind::Inbox
+            && self.since.is_none_or(|since| message.date > since)
+            && (!self.unread_only || !message.read)
+    }
+}
+
+/// Messages the phone sent or tried to send from `since` (Unix ms) on
+pub fn outgoing_query(since: i64) -> ContentQuery {
+    sms_query(format!("type<>{} AND date>={}", MESSAGE_TYPE_INBOX, since), "date DESC")
+}
+
+/// Inbox messages with an ID above `after_id`, oldest first. IDs only grow,
+/// so they make a cursor that survives clock changes.
+pub fn received_after_query(after_id: i64) -> ContentQuery {
+    sms_query(format!("type={} AND _id>{}", MESSAGE_TYPE_INBOX, after_id), "_id ASC")
+}
+
+fn sms_query(selection: String, sort: &'static str) -> ContentQuery {
+    ContentQuery {
+        uri: ContentUri::Sms,
+        projection: COLUMNS,
+        selection: Some(selection),
+        sort: Some(sort),
+    }
+}
+
+/// Parse the output of an SMS query. Rows without an id or date are skipped.
+pub fn parse_messages(output: &str) -> Vec<SmsMessage> {
+    content::parse_rows(output, COLUMNS)
+        .into_iter()
+        .filter_map(|row| {
+            let number = |key: &str| row.get(key).and_then(|v| v.parse::<i64>().ok());
+            let kind = match number("type") {
+                Some(1) => SmsKind::Inbox,
+                Some(2) => SmsKind::Sent,
+                Some(3) => SmsKind::Draft,
+                Some(4) => SmsKind::Outbox,
+                Some(5) => SmsKind::Failed,
+                Some(6) => SmsKind::Queued,
+                _ => SmsKind::Unknown,
+            };
+            Some(SmsMessage {
+                id: number("_id")?,
+                thread_id: number("thread_id"),
+                address: row.get("address").filter(|a| !a.is_empty()).cloned(),
+                body: row.get("body").cloned().unwrap_or_default(),
+                date: number("date")?,
+                // 0 when the network did not send a timestamp
+                date_sent: number("date_sent").filter(|&d| d > 0),
+                read: number("read") == Some(1),
+                kind,
+                status: number("status").map(|s| s as i32),
+                // -1 on phones that do not track the subscription
+                subscription_id: number("sub_id").filter(|&id| id >= 0).map(|id| id as i32),
+            })
+        })
+        .collect()
+}
+
+/// How far a sent message got
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SendStatus {
+    /// Not in the provider yet
+    Pending,
+    /// Waiting for the radio
+    Sending,
+    /// Accepted by the SMSC
+    Sent,
+    /// The recipient's phone confirmed it
+    Delivered,
+    Failed,
+}
+
+impl SendStatus {
+    pub fn of(message: &SmsMessage) -> Self {
+        // Telephony.Sms.STATUS_COMPLETE = 0, STATUS_FAILED = 64 and up
+        match (message.kind, message.status) {
+            (SmsKind::Failed, _) | (_, Some(64..)) => SendStatus::Failed,
+            (SmsKind::Sent, Some(0)) => SendStatus::Delivered,
+            (SmsKind::Sent, _) => SendStatus::Sent,
+            _ => SendStatus::Sending,
+        }
+    }
+}
+
+/// Split a text into SMS parts: 160 GSM characters (153 per part when
+/// concatenated), or 70 UTF-16 units (67) for anything outside the GSM alphabet
+pub fn split_parts(text: &str) -> Vec<String> {
+    let gsm = text.chars().all(|c| GSM_BASIC.contains(c) || GSM_EXTENDED.contains(c));
+    let cost = |c: char| match (gsm, GSM_EXTENDED.contains(c)) {
+        (true, true) => 2,
+        (true, false) => 1,
+        (false, _) => c.len_utf16(),
+    };
+    let (single, per_part) = if gsm { (160, 153) } else { (70, 67) };
+    if text.chars().map(cost).sum::<usize>() <= single {
+        return vec![text.to_string()];
+    }
+
+    // Characters are never split, so a part may end a unit short
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut used = 0;
+    for c in text.chars() {
+        if used + cost(c) > per_part {
+            parts.push(std::mem::take(&mut part));
+            used = 0;
+        }
+        part.push(c);
+        used += cost(c);
+    }
+    parts.push(part);
+    parts
+}
+
+/// Check the reply of `service call isms`: the send methods return nothing, so
+/// only the exception header matters
+pub fn parse_send_result(output: &str) -> Result<(), ExecError> {
+    Parcel::reply(output).map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::fixtures::{assert_fields, expected_int, fixture_cases};
+
+    #[test]
+    fn test_filter() {
+        let filter = InboxFilter { since: Some(1709300000000), unread_only: true };
+        assert_eq!(filter.query().selection.as_deref(), Some("type=1 AND date>1709300000000 AND read=0"));
+
+        let message = SmsMessage {
+            id: 1,
+            thread_id: None,
+            address: None,
+            body: String::new(),
+            date: 1709375512345,
+            date_sent: None,
+            read: false,
+            kind: SmsKind::Inbox,
+            status: None,
+            subscription_id: None,
+        };
+        assert!(filter.matches(&message));
+        assert!(!filter.matches(&SmsMessage { read: true, ..message.clone() }));
+        assert!(!filter.matches(&SmsMessage { kind: SmsKind::Sent, ..message.clone() }));
+        assert!(!filter.matches(&SmsMessage { date: 1709200000000, ..message }));
+    }
+
+    #[test]
+    fn test_split_parts() {
+        assert_eq!(split_parts("Hello"), vec!["Hello"]);
+        assert_eq!(split_parts(&"a".repeat(160)).len(), 1);
+
+        let parts = split_parts(&"a".repeat(161));
+        assert_eq!(parts.iter().map(|p| p.len()).collect::<Vec<_>>(), vec![153, 8]);
+
+        // Extension characters take two septets
+        assert_eq!(split_parts(&"€".repeat(80)).len(), 1);
+        assert_eq!(split_parts(&"€".repeat(81)).len(), 2);
+
+        // Outside the GSM alphabet: 70 UTF-16 units, then 67 per part
+        assert_eq!(split_parts(&"ж".repeat(70)).len(), 1);
+        let parts = split_parts(&"ж".repeat(71));
+        assert_eq!(parts.iter().map(|p| p.chars().count()).collect::<Vec<_>>(), vec![67, 4]);
+        // A surrogate pair is never cut in half
+        assert_eq!(split_parts(&format!("{}😀", "ж".repeat(68))).len(), 1);
+        let parts = split_parts(&format!("{}😀xxxx", "ж".repeat(66)));
+        assert_eq!(parts, vec!["ж".repeat(66), "😀xxxx".to_string()]);
+    }
+
+    #[test]
+    fn test_send_status() {
+        let message = parse_messages(
+            "Row: 0 _id=9, thread_id=3, address=+15550100, date=1709375512345, date_sent=0, read=1, type=2, status=-1, sub_id=1, \
+             body=Hi, _id=9, date=1709375512345\n",
+        )
+        .remove(0);
+        assert_eq!(SendStatus::of(&message), SendStatus::Sent);
+        assert_eq!(SendStatus::of(&SmsMessage { status: Some(0), ..message.clone() }), SendStatus::Delivered);
+        assert_eq!(SendStatus::of(&SmsMessage { kind: SmsKind::Outbox, ..message.clone() }), SendStatus::Sending);
+        assert_eq!(SendStatus::of(&SmsMessage { kind: SmsKind::Failed, ..message }), SendStatus::Failed);
+    }
+
+    #[test]
+    fn test_parse_send_result() {
+        assert!(parse_send_result("Result: Parcel(00000000    '....')\n").is_ok());
+        let error = "Result: Parcel(\n  0x00000000: ffffffff 00000012 00650053 00750063 '........S.e.c.u.'\n)";
+        assert!(matches!(parse_send_result(error), Err(ExecError::PermissionDenied(_))));
+        assert!(matches!(parse_send_result("service: Service isms does not exist"), Err(ExecError::Parse(_))));
+    }
+
+    #[test]
+    fn test_fixture_sms() {
+        for (name, output, expected) in fixture_cases("sms") {
+            let messages = parse_messages(&output);
+            assert_eq!(messages.len() as i64, expected_int(&expected, "count"), "{}", name);
+
+            let Some(details) = expected.get("messages") else { continue };
+            for (i, table) in details.as_array().unwrap().iter().enumerate() {
+                assert_fields(&format!("{}[{}]", name, i), &messages[i], table.as_table().unwrap());
+            }
+        }
+    }
+}
@@ -0,0 +1,742 @@
+//! Parsers for `dumpsys telephony.registry`
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Radio access technology a signal measurement belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum Rat {
+    Gsm,
+    Wcdma,
+    Lte,
+    Nr,
+}
+
+/// Signal measurement of the serving cell
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SignalInfo {
+    pub rat: Rat,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rsrp: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rsrq: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sinr: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rssi: Option<i32>,
+    /// WCDMA received signal code power
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rscp: Option<i32>,
+    /// WCDMA Ec/No
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ecno: Option<i32>,
+    /// Signal bars, 0 (none) to 4 (great)
+    pub level: u8,
+}
+
+/// CellInfo.UNAVAILABLE, printed for values the modem did not report
+const UNAVAILABLE: i32 = i32::MAX;
+
+/// AOSP default level thresholds (CarrierConfigManager)
+const LTE_RSRP_THRESHOLDS: [i32; 4] = [-128, -118, -108, -98];
+const NR_SSRSRP_THRESHOLDS: [i32; 4] = [-110, -90, -80, -65];
+const WCDMA_RSCP_THRESHOLDS: [i32; 4] = [-115, -105, -95, -85];
+const GSM_RSSI_THRESHOLDS: [i32; 4] = [-107, -103, -97, -89];
+
+/// Parse the serving cell's signal from the first `mSignalStrength=` line
+pub fn parse_signal_info(output: &str) -> Option<SignalInfo> {
+    let line = output
+        .lines()
+        .map(str::trim)
+        .find(|l| l.starts_with("mSignalStrength="))?;
+    parse_signal_strength(line.trim_start_matches("mSignalStrength="))
+}
+
+/// Parse one `SignalStrength` value as printed by SignalStrength.toString()
+pub fn parse_signal_strength(value: &str) -> Option<SignalInfo> {
+    if value.starts_with("SignalStrength:{") {
+        parse_modern(value)
+    } else {
+        parse_legacy(value.trim_start_matches("SignalStrength:"))
+    }
+}
+
+/// Android 10+: `SignalStrength:{mCdma=Invalid,mGsm=...,mLte=CellSignalStrengthLte: rssi=-65 ...,primary=...}`
+fn parse_modern(value: &str) -> Option<SignalInfo> {
+    let blocks = [("mNr=", Rat::Nr), ("mLte=", Rat::Lte), ("mWcdma=", Rat::Wcdma), ("mGsm=", Rat::Gsm)];
+    let cells: Vec<SignalInfo> = blocks
+        .into_iter()
+        .filter_map(|(key, rat)| segment(value, key).and_then(|s| parse_cell(rat, s)))
+        .collect();
+
+    let primary = value
+        .split("primary=CellSignalStrength")
+        .nth(1)
+        .map(|rest| rest.split(|c: char| !c.is_ascii_alphabetic()).next().unwrap_or_default());
+    let primary_rat = match primary {
+        Some("Nr") => Some(Rat::Nr),
+        Some("Lte") => Some(Rat::Lte),
+        Some("Wcdma") => Some(Rat::Wcdma),
+        Some("Gsm") => Some(Rat::Gsm),
+        _ => None,
+    };
+
+    // Prefer the RAT Android marks as primary, otherwise the newest valid one
+    primary_rat
+        .and_then(|rat| cells.iter().find(|c| c.rat == rat))
+        .or_else(|| cells.first())
+        .cloned()
+}
+
+/// Text of one `mXxx=` entry, up to the next entry
+fn segment<'a>(value: &'a str, key: &str) -> Option<&'a str> {
+    let start = value.find(key)? + key.len();
+    let rest = &value[start..];
+    let end = [",mCdma=", ",mGsm=", ",mWcdma=", ",mTdscdma=", ",mLte=", ",mNr=", ",primary="]
+        .iter()
+        .filter_map(|k| rest.find(k))
+        .min()
+        .unwrap_or(rest.len());
+    let segment = &rest[..end];
+    (!segment.starts_with("Invalid")).then_some(segment)
+}
+
+/// Integer `key=value` or `key = value` from a cell segment, skipping UNAVAILABLE
+fn field(segment: &str, key: &str) -> Option<i32> {
+    let normalized = segment.replace(" = ", "=");
+    normalized
+        .split(|c: char| c.is_whitespace() || c == '{' || c == ',')
+        .filter_map(|token| token.split_once('='))
+        .find(|(k, _)| *k == key)
+        .and_then(|(_, v)| v.trim_end_matches('}').parse::<i32>().ok())
+        .filter(|&v| v != UNAVAILABLE)
+}
+
+fn level_from(value: Option<i32>, thresholds: [i32; 4]) -> u8 {
+    match value {
+        Some(v) => thresholds.iter().filter(|&&t| v >= t).count() as u8,
+        None => 0,
+    }
+}
+
+fn parse_cell(rat: Rat, segment: &str) -> Option<SignalInfo> {
+    let reported_level = field(segment, "level")
+        .or_else(|| field(segment, "mLevel"))
+        .map(|l| l.clamp(0, 4) as u8);
+
+    let info = match rat {
+        Rat::Lte => {
+            let rsrp = field(segment, "rsrp");
+            SignalInfo {
+                rat,
+                rsrp,
+                rsrq: field(segment, "rsrq"),
+                sinr: field(segment, "rssnr"),
+                rssi: field(segment, "rssi"),
+                rscp: None,
+                ecno: None,
+                level: reported_level.unwrap_or_else(|| level_from(rsrp, LTE_RSRP_THRESHOLDS)),
+            }
+        }
+        Rat::Nr => {
+            let rsrp = field(segment, "ssRsrp").or_else(|| field(segment, "csiRsrp"));
+            SignalInfo {
+                rat,
+                rsrp,
+                rsrq: field(segment, "ssRsrq").or_else(|| field(segment, "csiRsrq")),
+                sinr: field(segment, "ssSinr").or_else(|| field(segment, "csiSinr")),
+                rssi: None,
+                rscp: None,
+                ecno: None,
+                level: reported_level.unwrap_or_else(|| level_from(rsrp, NR_SSRSRP_THRESHOLDS)),
+            }
+        }
+        Rat::Wcdma => {
+            let rscp = field(segment, "rscp");
+            SignalInfo {
+                rat,
+                rsrp: None,
+                rsrq: None,
+                sinr: None,
+                rssi: field(segment, "ss"),
+                rscp,
+                ecno: field(segment, "ecno"),
+                level: reported_level.unwrap_or_else(|| level_from(rscp, WCDMA_RSCP_THRESHOLDS)),
+            }
+        }
+        Rat::Gsm => {
+            let rssi = field(segment, "rssi");
+            SignalInfo {
+                rat,
+                rsrp: None,
+                rsrq: None,
+                sinr: None,
+                rssi,
+                rscp: None,
+                ecno: None,
+                level: reported_level.unwrap_or_else(|| level_from(rssi, GSM_RSSI_THRESHOLDS)),
+            }
+        }
+    };
+
+    // A segment with no usable measurement at all is as good as Invalid
+    let measured = [info.rsrp, info.rsrq, info.sinr, info.rssi, info.rscp].iter().any(Option::is_some);
+    measured.then_some(info)
+}
+
+/// Android 9 and older: space separated values
+/// `99 0 -120 -160 -120 -1 -1 20 -97 -9 114 2147483647 2147483647 gsm|lte ...`
+/// (gsm asu, gsm ber, cdma dbm, cdma ecio, evdo dbm, evdo ecio, evdo snr,
+/// lte asu, lte rsrp, lte rsrq, lte rssnr in 0.1 dB, lte cqi, lte ta)
+fn parse_legacy(value: &str) -> Option<SignalInfo> {
+    let numbers: Vec<i32> = value
+        .split_whitespace()
+        .map_while(|t| t.parse::<i32>().ok())
+        .collect();
+    if numbers.len() < 11 {
+        return None;
+    }
+    let valid = |v: i32| (v != UNAVAILABLE && v != -1).then_some(v);
+
+    let lte_asu = numbers[7];
+    let lte_rsrp = valid(numbers[8]).filter(|&v| (-140..=-44).contains(&v));
+    if let Some(rsrp) = lte_rsrp {
+        // The device's own LTE RSRP thresholds follow the level mode, if printed
+        let thresholds = value
+            .split('[')
+            .nth(1)
+            .and_then(|t| t.split(']').next())
+            .map(|t| t.split(',').filter_map(|v| v.trim().parse::<i32>().ok()).collect::<Vec<_>>())
+            .and_then(|t| <[i32; 4]>::try_from(t).ok())
+            .unwrap_or(LTE_RSRP_THRESHOLDS);
+        return Some(SignalInfo {
+            rat: Rat::Lte,
+            rsrp: Some(rsrp),
+            rsrq: valid(numbers[9]),
+            sinr: valid(numbers[10]).map(|v| v / 10),
+            rssi: (0..=31).contains(&lte_asu).then_some(-113 + 2 * lte_asu),
+            rscp: None,
+            ecno: None,
+            level: level_from(Some(rsrp), thresholds),
+        });
+    }
+
+    // GSM/WCDMA share the first pair: asu 0-31, 99 = unknown
+    let gsm_asu = numbers[0];
+    (0..=31).contains(&gsm_asu).then(|| {
+        let rssi = -113 + 2 * gsm_asu;
+        SignalInfo {
+            rat: Rat::Gsm,
+            rsrp: None,
+            rsrq: None,
+            sinr: None,
+            rssi: Some(rssi),
+            rscp: None,
+            ecno: None,
+            level: level_from(Some(rssi), GSM_RSSI_THRESHOLDS),
+        }
+    })
+}
+
+/// ServiceState.STATE_* of one registration domain
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RegState {
+    InService,
+    OutOfService,
+    EmergencyOnly,
+    PowerOff,
+    Unknown,
+}
+
+impl RegState {
+    fn from_code(code: &str) -> Self {
+        match code.split('(').next().unwrap_or_default().trim() {
+            "0" => RegState::InService,
+            "1" => RegState::OutOfService,
+            "2" => RegState::EmergencyOnly,
+            "3" => RegState::PowerOff,
+            _ => RegState::Unknown,
+        }
+    }
+}
+
+/// Which network the SIM is registered on
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct NetworkInfo {
+    pub operator: Option<String>,
+    pub mcc: Option<String>,
+    pub mnc: Option<String>,
+    pub voice_registration: RegState,
+    pub data_registration: RegState,
+    pub roaming: bool,
+    /// RIL radio technology name, e.g. "GPRS", "HSPAP", "LTE", or "NR" while
+    /// an NSA 5G leg is connected
+    pub data_network_type: Option<String>,
+    pub voice_network_type: Option<String>,
+    /// "2G" to "5G", from the data network type (voice when there is no data)
+    pub generation: Option<&'static str>,
+    /// Serving cell: CI (LTE), NCI (NR) or CID (GSM/WCDMA)
+    pub cell_id: Option<u64>,
+    /// TAC (LTE/NR) or LAC (GSM/WCDMA)
+    pub area_code: Option<u32>,
+}
+
+/// The `last known state` block of each phone (SIM slot), in slot order.
+/// Single-SIM builds that print no `Phone Id=` line yield one section.
+pub fn phone_sections(output: &str) -> Vec<&str> {
+    let state = output.split("local logs:").next().unwrap_or_default();
+    let mut starts: Vec<usize> = state
+        .match_indices("Phone Id=")
+        .map(|(pos, _)| pos)
+        .collect();
+    if starts.is_empty() {
+        starts.push(0);
+    }
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| &state[start..starts.get(i + 1).copied().unwrap_or(state.len())])
+        .collect()
+}
+
+/// `gsm.*` system properties from `getprop` output (`[key]: [value]` lines)
+pub fn parse_gsm_properties(output: &str) -> BTreeMap<String, String> {
+    output
+        .lines()
+        .filter_map(|line| line.trim().split_once("]: ["))
+        .filter_map(|(key, value)| {
+            let key = key.strip_prefix('[')?;
+            let value = value.strip_suffix(']')?;
+            key.starts_with("gsm.").then(|| (key.to_string(), value.to_string()))
+        })
+        .collect()
+}
+
+/// Value of a per-slot property: dual-SIM phones comma separate one value per slot
+fn slot_property<'a>(properties: &'a BTreeMap<String, String>, key: &str, slot: usize) -> Option<&'a str> {
+    properties
+        .get(key)?
+        .split(',')
+        .nth(slot)
+        .map(str::trim)
+        .filter(|v| !v.is_empty() && *v != "Unknown")
+}
+
+/// Value of `key=` in a dumpsys line, up to the first of `terminators`
+fn entry<'a>(text: &'a str, key: &str, terminators: &[char]) -> Option<&'a str> {
+    let needle = format!("{}=", key);
+    let mut offset = 0;
+    while let Some(pos) = text[offset..].find(&needle) {
+        let start = offset + pos;
+        // Skip matches inside a longer key, e.g. mOperatorAlphaLongRaw
+        let boundary = text[..start].chars().last().is_none_or(|c| !c.is_ascii_alphanumeric());
+        if boundary {
+            let rest = &text[start + needle.len()..];
+            let end = rest.find(terminators).unwrap_or(rest.len());
+            return Some(rest[..end].trim()).filter(|v| !v.is_empty() && *v != "null");
+        }
+        offset = start + needle.len();
+    }
+    None
+}
+
+/// "LTE" from `getRilDataRadioTechnology=14(LTE)`
+fn radio_technology(service_state: &str, key: &str) -> Option<String> {
+    let value = entry(service_state, key, &[','])?;
+    let name = value.split_once('(')?.1.split(')').next()?;
+    (name != "Unknown").then(|| name.to_string())
+}
+
+fn generation(network_type: &str) -> Option<&'static str> {
+    match network_type {
+        "GPRS" | "EDGE" | "GSM" | "IS95A" | "IS95B" | "1xRTT" | "CDMA" => Some("2G"),
+        "UMTS" | "HSDPA" | "HSUPA" | "HSPA" | "HSPAP" | "TD_SCDMA" | "EVDO_0" | "EVDO_A" | "EVDO_B"
+        | "EHRPD" | "WCDMA" => Some("3G"),
+        "LTE" | "LTE_CA" => Some("4G"),
+        "NR" => Some("5G"),
+        _ => None,
+    }
+}
+
+/// Registration details of one phone section, with `gsm.*` properties of
+/// the same slot filling in what the registry leaves out
+pub fn parse_network_info(
+    section: &str,
+    properties: &BTreeMap<String, String>,
+    slot: usize,
+) -> Option<NetworkInfo> {
+    let line = section
+        .lines()
+        .map(str::trim)
+        .find(|l| l.starts_with("mServiceState="))?
+        .trim_start_matches("mServiceState=");
+
+    let mut info = if line.starts_with('{') {
+        parse_modern_service_state(line)
+    } else {
+        parse_legacy_service_state(line)?
+    };
+
+    let numeric = slot_property(properties, "gsm.operator.numeric", slot).filter(|n| n.len() >= 5);
+    if info.mcc.is_none() {
+        if let Some(numeric) = numeric {
+            info.mcc = Some(numeric[..3].to_string());
+            info.mnc = Some(numeric[3..].to_string());
+        }
+    }
+    if info.operator.is_none() {
+        info.operator = slot_property(properties, "gsm.operator.alpha", slot).map(str::to_string);
+    }
+    if info.data_network_type.is_none() {
+        info.data_network_type = slot_property(properties, "gsm.network.type", slot).map(str::to_string);
+    }
+    info.roaming |= slot_property(properties, "gsm.operator.isroaming", slot) == Some("true");
+    info.generation = info
+        .data_network_type
+        .as_deref()
+        .and_then(generation)
+        .or_else(|| info.voice_network_type.as_deref().and_then(generation));
+    Some(info)
+}
+
+/// Android 10+: `{mVoiceRegState=0(IN_SERVICE), mDataRegState=..., mNetworkRegistrationInfos=[...]}`
+fn parse_modern_service_state(line: &str) -> NetworkInfo {
+    let registration = |key| entry(line, key, &[',']).map(RegState::from_code).unwrap_or(RegState::Unknown);
+    let operator = ["mOperatorAlphaLong", "mDataOperatorAlphaLong", "mVoiceOperatorAlphaLong"]
+        .iter()
+        .find_map(|key| entry(line, key, &[',']))
+        .map(str::to_string);
+
+    // Prefer the packet-switched registration, which is what data uses
+    let registrations: Vec<&str> = line.split("NetworkRegistrationInfo{").skip(1).collect();
+    let serving = registrations
+        .iter()
+        .find(|r| r.contains("domain=PS"))
+        .or_else(|| registrations.first())
+        .copied()
+        .unwrap_or_default();
+    let cell = serving.split("cellIdentity=").nth(1).unwrap_or_default();
+    let cell_field = |key| entry(cell, key, &[' ', '}']);
+    let roaming = registrations.iter().any(|r| r.contains("registrationState=ROAMING"))
+        || entry(line, "mIsDataRoamingFromRegistration", &[',']) == Some("true");
+
+    let mut data_network_type = radio_technology(line, "getRilDataRadioTechnology")
+        .or_else(|| radio_technology(line, "mRilDataRadioTechnology"));
+    // NSA 5G keeps reporting LTE as the data RAT, with the NR leg in nrState
+    if serving.contains("nrState=CONNECTED") {
+        data_network_type = Some("NR".to_string());
+    }
+
+    NetworkInfo {
+        operator,
+        mcc: cell_field("mMcc").map(str::to_string),
+        mnc: cell_field("mMnc").map(str::to_string),
+        voice_registration: registration("mVoiceRegState"),
+        data_registration: registration("mDataRegState"),
+        roaming,
+        data_network_type,
+        voice_network_type: radio_technology(line, "getRilVoiceRadioTechnology")
+            .or_else(|| radio_technology(line, "mRilVoiceRadioTechnology")),
+        generation: None,
+        cell_id: ["mCi", "mNci", "mCid"].iter().find_map(|key| cell_field(key)?.parse().ok()),
+        area_code: ["mTac", "mLac"].iter().find_map(|key| cell_field(key)?.parse().ok()),
+    }
+}
+
+/// Android 9 and older: `0 0 voice home data home T-Mobile TMO 310260 T-Mobile TMO 310260  LTE LTE CSS ...`
+/// Operator names may contain spaces and cannot be told apart from the short
+/// name, so the name is left to `gsm.operator.alpha`.
+fn parse_legacy_service_state(line: &str) -> Option<NetworkInfo> {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    if tokens.len() < 6 || tokens[2] != "voice" || tokens[4] != "data" {
+        return None;
+    }
+    let numeric = tokens
+        .iter()
+        .find(|t| (5..=6).contains(&t.len()) && t.chars().all(|c| c.is_ascii_digit()));
+    let rats: Vec<&str> = line
+        .split(" CSS")
+        .next()
+        .unwrap_or_default()
+        .split_whitespace()
+        .rev()
+        .take(2)
+        .collect();
+
+    Some(NetworkInfo {
+        operator: None,
+        mcc: numeric.map(|n| n[..3].to_string()),
+        mnc: numeric.map(|n| n[3..].to_string()),
+        voice_registration: RegState::from_code(tokens[0]),
+        data_registration: RegState::from_code(tokens[1]),
+        roaming: tokens[3] == "roaming" || tokens[5] == "roaming",
+        data_network_type: rats.first().filter(|r| generation(r).is_some()).map(|r| r.to_string()),
+        voice_network_type: rats.get(1).filter(|r| generation(r).is_some()).map(|r| r.to_string()),
+        generation: None,
+        cell_id: None,
+        area_code: None,
+    })
+}
+
+/// Mobile data as seen by one phone section: the user's switch for that SIM
+/// (`mUserMobileDataState`) and whether a data connection is up
+/// (`mDataConnectionState=2`, TelephonyManager.DATA_CONNECTED)
+pub fn parse_data_state(section: &str) -> (Option<bool>, Option<bool>) {
+    let value = |key: &str| {
+        section
+            .lines()
+            .map(str::trim)
+            .find_map(|l| l.strip_prefix(key)?.strip_prefix('='))
+    };
+    let enabled = value("mUserMobileDataState").and_then(|v| v.parse().ok());
+    let connected = value("mDataConnectionState").map(|v| v == "2");
+    (enabled, connected)
+}
+
+/// TelephonyManager.CALL_STATE_*, ordered from least to most busy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CallState {
+    Idle,
+    Ringing,
+    /// Dialing, active or on hold
+    Offhook,
+}
+
+/// Call state of one phone section
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallReading {
+    pub state: CallState,
+    /// Caller of a ringing call; kept by some builds until the call ends
+    pub incoming_number: Option<String>,
+}
+
+/// `mCallState` (0 idle, 1 ringing, 2 offhook) and `mCallIncomingNumber` of one phone section
+pub fn parse_call_state(section: &str) -> Option<CallReading> {
+    let value = |key: &str| {
+        section
+            .lines()
+            .map(str::trim)
+            .find_map(|l| l.strip_prefix(key)?.strip_prefix('='))
+    };
+    let state = match value("mCallState")? {
+        "0" => CallState::Idle,
+        "1" => CallState::Ringing,
+        "2" => CallState::Offhook,
+        _ => return None,
+    };
+    let incoming_number = value("mCallIncomingNumber").filter(|n| !n.is_empty()).map(str::to_string);
+    Some(CallReading { state, incoming_number })
+}
+
+/// One active SIM subscription from `dumpsys isub`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Subscription {
+    /// Subscription ID, stable for a SIM card across reboots
+    pub id: i32,
+    /// Logical slot (phone ID) the SIM sits in
+    pub slot: usize,
+    pub display_name: Option<String>,
+    pub carrier_name: Option<String>,
+}
+
+/// Active subscriptions and which one carries mobile data
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Subscriptions {
+    /// Sorted by slot
+    pub active: Vec<Subscription>,
+    pub default_data: Option<i32>,
+}
+
+impl Subscriptions {
+    pub fn by_slot(&self, slot: usize) -> Option<&Subscription> {
+        self.active.iter().find(|s| s.slot == slot)
+    }
+
+    pub fn by_id(&self, id: i32) -> Option<&Subscription> {
+        self.active.iter().find(|s| s.id == id)
+    }
+}
+
+/// `key=value` pairs of one subscription record. Values may contain spaces
+/// (`displayName=Jio 4G`), so a word without `=` continues the previous value.
+fn record_fields(record: &str) -> BTreeMap<&str, String> {
+    let mut fields = BTreeMap::new();
+    let mut current: Option<&str> = None;
+    for word in record.trim_matches(|c| c == '{' || c == '}' || c == '[' || c == ']').split(' ') {
+        match word.split_once('=') {
+            Some((key, value)) if !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric()) => {
+                fields.insert(key, value.to_string());
+                current = Some(key);
+            }
+            _ => {
+                if let Some(value) = current.and_then(|key| fields.get_mut(key)) {
+                    value.push(' ');
+                    value.push_str(word);
+                }
+            }
+        }
+    }
+    fields
+}
+
+/// Parse `dumpsys isub`: SubscriptionController (Android 13 and older) prints
+/// `{id=1 ... simSlotIndex=0 ...}` records, SubscriptionManagerService
+/// (Android 14+) `[SubscriptionInfoInternal: id=1 ... simSlotIndex=0 ...]`.
+/// Inactive subscriptions have simSlotIndex=-1 and are left out.
+pub fn parse_subscriptions(output: &str) -> Subscriptions {
+    let mut subscriptions = Subscriptions::default();
+    for line in output.lines().map(str::trim) {
+        if let Some(id) = line.strip_prefix("defaultDataSubId=") {
+            subscriptions.default_data = id.trim().parse().ok().filter(|&id: &i32| id >= 0);
+            continue;
+        }
+        if !line.contains("simSlotIndex=") {
+            continue;
+        }
+        let record = line.trim_start_matches("[SubscriptionInfoInternal:");
+        let fields = record_fields(record);
+        let id = fields.get("id").and_then(|v| v.parse::<i32>().ok());
+        let slot = fields.get("simSlotIndex").and_then(|v| v.parse::<i64>().ok());
+        let (Some(id), Some(slot)) = (id, slot) else { continue };
+        if slot < 0 || subscriptions.by_id(id).is_some() {
+            continue;
+        }
+        let name = |key| fields.get(key).map(|v| v.trim().to_string()).filter(|v| !v.is_empty());
+        subscriptions.active.push(Subscription {
+            id,
+            slot: slot as usize,
+            display_name: name("displayName"),
+            carrier_name: name("carrierName"),
+        });
+    }
+    subscriptions.active.sort_by_key(|s| s.slot);
+    subscriptions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::fixtures::{expected_int, fixture_cases};
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_parse_lte() {
+        let line = "mSignalStrength=SignalStrength:{mCdma=Invalid,mGsm=Invalid,mWcdma=Invalid,mTdscdma=Invalid,mLte=CellSignalStrengthLte: rssi=-65 rsrp=-94 rsrq=-11 rssnr=12 cqi=2147483647 ta=2147483647 level=3,mNr=Invalid,primary=CellSignalStrengthLte}";
+        let info = parse_signal_info(line).unwrap();
+        assert_eq!(info.rat, Rat::Lte);
+        assert_eq!(info.rsrp, Some(-94));
+        assert_eq!(info.sinr, Some(12));
+        assert_eq!(info.level, 3);
+    }
+
+    #[test]
+    fn test_level_computed_when_missing() {
+        let line = "mSignalStrength=SignalStrength:{mGsm=CellSignalStrengthGsm: rssi=-101 ber=99,mLte=Invalid}";
+        let info = parse_signal_info(line).unwrap();
+        assert_eq!(info.rat, Rat::Gsm);
+        assert_eq!(info.level, 2);
+    }
+
+    #[test]
+    fn test_no_signal() {
+        assert_eq!(parse_signal_info("mServiceState=1 0 voice home data home"), None);
+        let line = "mSignalStrength=SignalStrength:{mCdma=Invalid,mGsm=Invalid,mWcdma=Invalid,mTdscdma=Invalid,mLte=Invalid,mNr=Invalid,primary=CellSignalStrengthCdma}";
+        assert_eq!(parse_signal_info(line), None);
+    }
+
+    #[test]
+    fn test_fixture_signal_info() {
+        for (name, output, expected) in fixture_cases("telephony_registry") {
+            let parsed = parse_signal_info(&output).map(|info| serde_json::to_value(info).unwrap());
+            let expected = expected.get("signal").map(|signal| serde_json::to_value(signal).unwrap());
+            assert_eq!(parsed, expected, "{}", name);
+        }
+    }
+
+    /// Parsed value as JSON with nulls dropped, since expected.toml cannot hold them
+    fn without_nulls(info: &NetworkInfo) -> serde_json::Value {
+        let mut value = serde_json::to_value(info).unwrap();
+        value.as_object_mut().unwrap().retain(|_, v| !v.is_null());
+        value
+    }
+
+    #[test]
+    fn test_fixture_network_info() {
+        for (name, output, expected) in fixture_cases("telephony_registry") {
+            let section = phone_sections(&output)[0];
+            let parsed = parse_network_info(section, &BTreeMap::new(), 0).unwrap();
+            assert_eq!(without_nulls(&parsed), serde_json::to_value(&expected["network"]).unwrap(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_fixture_network_info_with_properties() {
+        for (name, output, expected) in fixture_cases("getprop") {
+            let properties = parse_gsm_properties(&output);
+            assert_eq!(properties.len() as i64, expected_int(&expected, "count"), "{}", name);
+
+            let registry_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/telephony_registry").join(&name);
+            let registry = std::fs::read_to_string(registry_path).unwrap();
+            let parsed = parse_network_info(phone_sections(&registry)[0], &properties, 0).unwrap();
+            assert_eq!(without_nulls(&parsed), serde_json::to_value(&expected["network"]).unwrap(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_phone_sections() {
+        let output = "last known state:\n  Phone Id=0\n  mCallState=0\n  Phone Id=1\n  mCallState=2\nlocal logs:\n  Phone Id=5\n";
+        let sections = phone_sections(output);
+        assert_eq!(sections.len(), 2);
+        assert!(sections[1].contains("mCallState=2"));
+        assert_eq!(phone_sections("mServiceState=0 0 voice home data home").len(), 1);
+    }
+
+    #[test]
+    fn test_power_off_registration() {
+        let section = "mServiceState={mVoiceRegState=3(POWER_OFF), mDataRegState=3(POWER_OFF), mOperatorAlphaLong=null, getRilDataRadioTechnology=0(Unknown)}";
+        let info = parse_network_info(section, &BTreeMap::new(), 0).unwrap();
+        assert_eq!(info.voice_registration, RegState::PowerOff);
+        assert_eq!(info.operator, None);
+        assert_eq!(info.data_network_type, None);
+        assert_eq!(info.generation, None);
+    }
+
+    #[test]
+    fn test_fixture_call_state() {
+        for (name, output, expected) in fixture_cases("telephony_registry") {
+            let reading = parse_call_state(phone_sections(&output)[0]).unwrap();
+            assert_eq!(serde_json::to_value(reading.state).unwrap(), serde_json::to_value(&expected["call_state"]).unwrap(), "{}", name);
+            let number = expected.get("incoming_number").and_then(|n| n.as_str());
+            assert_eq!(reading.incoming_number.as_deref(), number, "{}", name);
+        }
+        assert_eq!(parse_call_state("  mCallState=2\n  mCallIncomingNumber=\n").unwrap().state, CallState::Offhook);
+        assert_eq!(parse_call_state("  mServiceState=0 0 voice home data home\n"), None);
+    }
+
+    #[test]
+    fn test_fixture_subscriptions() {
+        for (name, output, expected) in fixture_cases("isub") {
+            let parsed = parse_subscriptions(&output);
+            let active = serde_json::to_value(&parsed.active).unwrap();
+            assert_eq!(active, serde_json::to_value(&expected["active"]).unwrap(), "{}", name);
+            assert_eq!(parsed.default_data.map(i64::from), expected.get("default_data").and_then(|v| v.as_integer()), "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_fixture_data_state_per_phone() {
+        let output = std::fs::read_to_string(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("fixtures/telephony_registry/dual_sim.txt"),
+        )
+        .unwrap();
+        let sections = phone_sections(&output);
+        assert_eq!(sections.len(), 2);
+        assert_eq!(parse_data_state(sections[0]), (Some(true), Some(true)));
+        assert_eq!(parse_data_state(sections[1]), (Some(false), Some(false)));
+        assert_eq!(parse_signal_info(sections[1]).unwrap().rat, Rat::Wcdma);
+        assert!(parse_network_info(sections[1], &BTreeMap::new(), 1).unwrap().roaming);
+    }
+}
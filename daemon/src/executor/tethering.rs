@@ -0,0 +1,182 @@
+//! Parsers for `dumpsys tethering`, `/proc/net/dev` and `ip neigh`, which
+//! together tell what is tethered, how much it moved and who is connected
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// What an interface tethers over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TetherKind {
+    /// Wi-Fi hotspot
+    Hotspot,
+    Usb,
+    Bluetooth,
+    Ethernet,
+    /// Wi-Fi Direct and anything else
+    Other,
+}
+
+impl TetherKind {
+    /// Guess from the interface name, following the AOSP tetherable* patterns.
+    /// Android 13 puts the hotspot on a bridge (`ap_br_wlan1`) and USB on NCM.
+    pub fn from_interface(name: &str) -> Self {
+        if name.starts_with("p2p") {
+            TetherKind::Other
+        } else if ["wlan", "swlan", "softap", "ap_br_"].iter().any(|prefix| name.starts_with(prefix)) {
+            TetherKind::Hotspot
+        } else if ["rndis", "ncm", "usb"].iter().any(|prefix| name.starts_with(prefix)) {
+            TetherKind::Usb
+        } else if name.starts_with("bt-pan") {
+            TetherKind::Bluetooth
+        } else if name.starts_with("eth") {
+            TetherKind::Ethernet
+        } else {
+            TetherKind::Other
+        }
+    }
+}
+
+/// Tethered interfaces and the upstream they share, from `dumpsys tethering`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TetherState {
+    pub tethered: Vec<(String, TetherKind)>,
+    /// Interfaces carrying the tethered traffic, e.g. `rmnet_data2`
+    pub upstream: Vec<String>,
+}
+
+/// Parse `dumpsys tethering` (Android 11+, the tethering module). Only the
+/// `Tether state:` section is read; the log below it repeats state names.
+pub fn parse_tether_state(output: &str) -> Option<TetherState> {
+    let mut lines = output.lines();
+    let header = lines.find(|line| line.trim() == "Tether state:")?;
+    let indent = header.len() - header.trim_start().len();
+
+    let mut state = TetherState::default();
+    for line in lines {
+        if line.len() - line.trim_start().len() <= indent {
+            break;
+        }
+        // "wlan1 - TetheredState - lastError = 0"
+        let mut parts = line.trim().split(" - ");
+        if let (Some(name), Some("TetheredState")) = (parts.next(), parts.next()) {
+            state.tethered.push((name.to_string(), TetherKind::from_interface(name)));
+        }
+    }
+
+    // "Current upstream interface(s): [rmnet1, rmnet1_clat]", or null
+    if let Some(upstream) = output.lines().find_map(|line| line.trim().strip_prefix("Current upstream interface(s): ")) {
+        state.upstream = upstream
+            .trim_start_matches('[')
+            .trim_end_matches(']')
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty() && *name != "null")
+            .map(String::from)
+            .collect();
+    }
+    Some(state)
+}
+
+/// Byte counters of one interface since it came up
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct InterfaceCounters {
+    /// Received by the phone, i.e. uploaded by tethered clients
+    pub rx_bytes: u64,
+    /// Sent by the phone, i.e. downloaded by tethered clients
+    pub tx_bytes: u64,
+}
+
+/// Parse `/proc/net/dev`: after the two header lines, `iface: rx_bytes rx_packets ... tx_bytes ...`
+pub fn parse_interface_counters(output: &str) -> BTreeMap<String, InterfaceCounters> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (name, counters) = line.split_once(':')?;
+            let counters: Vec<u64> = counters.split_whitespace().map(|c| c.parse().ok()).collect::<Option<_>>()?;
+            // 8 receive columns, then transmit
+            let (rx_bytes, tx_bytes) = (*counters.first()?, *counters.get(8)?);
+            Some((name.trim().to_string(), InterfaceCounters { rx_bytes, tx_bytes }))
+        })
+        .collect()
+}
+
+/// A device on a tethered interface
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TetheringClient {
+    pub ip: String,
+    pub mac: String,
+    pub interface: String,
+}
+
+/// Parse `ip -4 neigh show`. Entries the kernel could not resolve are no clients;
+/// STALE ones are kept, a client that went quiet stays listed for a few minutes.
+pub fn parse_neighbours(output: &str) -> Vec<TetheringClient> {
+    output
+        .lines()
+        .filter_map(|line| {
+            // "192.168.43.120 dev wlan1 lladdr 5a:11:22:33:44:55 REACHABLE"
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let value = |key: &str| words.iter().position(|w| *w == key).and_then(|i| words.get(i + 1)).copied();
+            let state = words.last()?;
+            if matches!(*state, "FAILED" | "INCOMPLETE" | "NOARP") {
+                return None;
+            }
+            Some(TetheringClient {
+                ip: words.first()?.to_string(),
+                mac: value("lladdr")?.to_string(),
+                interface: value("dev")?.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::fixtures::fixture_cases;
+
+    #[test]
+    fn test_fixture_tether_state() {
+        for (name, output, expected) in fixture_cases("tethering") {
+            let state = parse_tether_state(&output).unwrap();
+            let tethered: Vec<String> = state
+                .tethered
+                .iter()
+                .map(|(iface, kind)| format!("{}:{}", iface, serde_json::to_value(kind).unwrap().as_str().unwrap()))
+                .collect();
+            let strings = |key: &str| -> Vec<String> {
+                expected[key].as_array().unwrap().iter().map(|v| v.as_str().unwrap().to_string()).collect()
+            };
+            assert_eq!(tethered, strings("tethered"), "{}", name);
+            assert_eq!(state.upstream, strings("upstream"), "{}", name);
+        }
+        assert_eq!(parse_tether_state("Can't find service: tethering\n"), None);
+    }
+
+    #[test]
+    fn test_parse_interface_counters() {
+        let output = "Inter-|   Receive                                                |  Transmit\n \
+            face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed\n    \
+            lo:    8456      92    0    0    0     0          0         0     8456      92    0    0    0     0       0          0\n \
+            wlan1: 48213312  40211    0    0    0     0          0         0 912223411  70123    0    0    0     0       0          0\n";
+        let counters = parse_interface_counters(output);
+        assert_eq!(counters.len(), 2);
+        assert_eq!(counters["wlan1"], InterfaceCounters { rx_bytes: 48213312, tx_bytes: 912223411 });
+    }
+
+    #[test]
+    fn test_parse_neighbours() {
+        let output = "192.168.43.120 dev wlan1 lladdr 5a:11:22:33:44:55 REACHABLE\n\
+                      192.168.43.77 dev wlan1 lladdr 9c:aa:bb:cc:dd:ee STALE\n\
+                      192.168.43.9 dev wlan1  FAILED\n\
+                      10.167.12.1 dev rmnet_data2  NOARP\n";
+        let clients = parse_neighbours(output);
+        assert_eq!(clients.len(), 2);
+        assert_eq!(clients[0], TetheringClient {
+            ip: "192.168.43.120".to_string(),
+            mac: "5a:11:22:33:44:55".to_string(),
+            interface: "wlan1".to_string(),
+        });
+    }
+}
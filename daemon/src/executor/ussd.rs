@@ -0,0 +1,137 @@
+//! USSD and other MMI codes, and the network's replies as logged by `GsmMmiCode`
+//! in the radio log
+
+/// Longest code or menu reply accepted, in characters
+const MAX_LENGTH: usize = 32;
+
+/// What the network answered to a USSD code or menu reply
+#[derive(Debug, Clone, PartialEq)]
+pub enum UssdOutcome {
+    Reply {
+        message: String,
+        /// The network shows a menu and waits for a reply in the same session
+        awaiting_reply: bool,
+    },
+    Failed,
+}
+
+/// `*100#`, `*123*1#`, `#999#`: starts with * or #, ends with #
+pub fn is_valid_code(code: &str) -> bool {
+    (2..=MAX_LENGTH).contains(&code.len())
+        && code.starts_with(['*', '#'])
+        && code.ends_with('#')
+        && code.chars().all(|c| c.is_ascii_digit() || c == '*' || c == '#')
+}
+
+/// A menu choice such as `1` or `00`
+pub fn is_valid_reply(reply: &str) -> bool {
+    (1..=MAX_LENGTH).contains(&reply.len()) && reply.chars().all(|c| c.is_ascii_digit() || c == '*' || c == '#')
+}
+
+/// Find the last USSD result in `logcat -v epoch -s GsmMmiCode` output.
+/// Whether the network waits for an answer shows in the `State=` of the MMI
+/// code logged after the message: a request stays PENDING.
+pub fn parse_ussd_log(output: &str) -> Option<UssdOutcome> {
+    let mut outcome = None;
+    for (event, text) in log_entries(output) {
+        match event {
+            "onUssdFinished" => {
+                if let Some(message) = text.strip_prefix("ussdMessage=") {
+                    outcome = Some(UssdOutcome::Reply { message: message.to_string(), awaiting_reply: false });
+                } else if text.starts_with("no network provided message") {
+                    outcome = Some(UssdOutcome::Reply { message: String::new(), awaiting_reply: false });
+                } else if let Some((_, state)) = text.split_once("State=") {
+                    if let Some(UssdOutcome::Reply { ref mut awaiting_reply, .. }) = outcome {
+                        *awaiting_reply = state.starts_with("PENDING");
+                    }
+                }
+            }
+            "onUssdFinishedError" => outcome = Some(UssdOutcome::Failed),
+            _ => {}
+        }
+    }
+    outcome
+}
+
+/// Find the network's answer to the last supplementary service code (call
+/// forwarding and the like), as the text the dialer would show
+pub fn parse_service_result(output: &str) -> Option<String> {
+    log_entries(output)
+        .into_iter()
+        .filter(|(event, _)| matches!(*event, "onSetComplete" | "onQueryCfComplete" | "onQueryComplete"))
+        .map(|(_, text)| text)
+        .next_back()
+}
+
+/// Events logged by GsmMmiCode, as (event, text) in log order.
+///
+/// logcat prints a multi-line message as several lines with the same
+/// timestamp, pid and tid, so those lines are joined back into the text.
+fn log_entries(output: &str) -> Vec<(&str, String)> {
+    let mut entries: Vec<(&str, String)> = Vec::new();
+    let mut last_header = None;
+    for line in output.lines() {
+        let Some((header, message)) = split_line(line) else { continue };
+        let event = message.split([':', ' ']).next().unwrap_or_default();
+        if !event.starts_with("on") && last_header == Some(header) {
+            if let Some((_, text)) = entries.last_mut() {
+                text.push('\n');
+                text.push_str(message);
+                continue;
+            }
+        }
+        let text = message[event.len()..].trim_start_matches(':').trim_start();
+        entries.push((event, text.to_string()));
+        last_header = Some(header);
+    }
+    entries
+}
+
+/// Split `1709375512.345  1234  1290 D GsmMmiCode: text` into the header
+/// (time, pid and tid) and the message text
+fn split_line(line: &str) -> Option<(&str, &str)> {
+    let (prefix, message) = line.trim_end_matches('\r').split_once(" GsmMmiCode: ")?;
+    // Drop the level letter
+    let (header, _) = prefix.trim_end().rsplit_once(char::is_whitespace)?;
+    Some((header.trim(), message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::fixtures::fixture_cases;
+
+    #[test]
+    fn test_code_validation() {
+        assert!(is_valid_code("*100#"));
+        assert!(is_valid_code("*123*1#"));
+        assert!(is_valid_code("#999#"));
+        assert!(!is_valid_code("100"));
+        assert!(!is_valid_code("*100"));
+        assert!(!is_valid_code("*100#; reboot"));
+        assert!(!is_valid_code("#"));
+
+        assert!(is_valid_reply("1"));
+        assert!(is_valid_reply("00"));
+        assert!(!is_valid_reply(""));
+        assert!(!is_valid_reply("yes"));
+    }
+
+    #[test]
+    fn test_fixture_ussd() {
+        for (name, output, expected) in fixture_cases("ussd") {
+            let outcome = parse_ussd_log(&output);
+            let expected_outcome = match expected.get("outcome").and_then(|o| o.as_str()) {
+                None => None,
+                Some("failed") => Some(UssdOutcome::Failed),
+                Some(_) => Some(UssdOutcome::Reply {
+                    message: expected["message"].as_str().unwrap().to_string(),
+                    awaiting_reply: expected["awaiting_reply"].as_bool().unwrap(),
+                }),
+            };
+            assert_eq!(outcome, expected_outcome, "{}", name);
+            let service = expected.get("service_result").and_then(|r| r.as_str());
+            assert_eq!(parse_service_result(&output).as_deref(), service, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Parsers for `cmd wifi status` and the address of the Wi-Fi interface
+
+use serde::Serialize;
+
+/// WifiManager.INVALID_RSSI, reported before the first measurement
+const INVALID_RSSI: i32 = -127;
+
+/// Wi-Fi state and the current connection
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WifiStatus {
+    pub enabled: bool,
+    pub connected: bool,
+    pub ssid: Option<String>,
+    /// Access point the phone is associated with
+    pub bssid: Option<String>,
+    /// dBm
+    pub rssi: Option<i32>,
+    pub link_speed_mbps: Option<i32>,
+    pub frequency_mhz: Option<i32>,
+    /// IPv4 address of the Wi-Fi interface
+    pub ip: Option<String>,
+}
+
+/// Parse `cmd wifi status` (Android 11+). Fields of the connection come from the
+/// `WifiInfo:` line, whose layout grew over the releases, so each one is optional.
+pub fn parse_wifi_status(output: &str) -> Option<WifiStatus> {
+    let lines: Vec<&str> = output.lines().map(str::trim).collect();
+    let enabled = lines.iter().find_map(|line| match *line {
+        "Wifi is enabled" => Some(true),
+        "Wifi is disabled" => Some(false),
+        _ => None,
+    })?;
+    let connected_to = lines.iter().find_map(|line| line.strip_prefix("Wifi is connected to "));
+    let mut status = WifiStatus {
+        enabled,
+        connected: connected_to.is_some(),
+        ssid: connected_to.map(unquote).filter(|ssid| !ssid.is_empty() && *ssid != "<unknown ssid>").map(String::from),
+        bssid: None,
+        rssi: None,
+        link_speed_mbps: None,
+        frequency_mhz: None,
+        ip: None,
+    };
+    if !status.connected {
+        return Some(status);
+    }
+
+    let Some(info) = lines.iter().find_map(|line| line.strip_prefix("WifiInfo: ")) else { return Some(status) };
+    // "SSID: HomeNet, BSSID: 3c:84:6a:11:22:33, ..., RSSI: -58, Link speed: 390Mbps, ..."
+    for field in info.split(", ") {
+        let Some((key, value)) = field.split_once(": ") else { continue };
+        match key {
+            "SSID" if status.ssid.is_none() => status.ssid = Some(unquote(value).to_string()),
+            "BSSID" if value != "02:00:00:00:00:00" => status.bssid = Some(value.to_string()),
+            "RSSI" => status.rssi = value.parse().ok().filter(|&rssi| rssi != INVALID_RSSI),
+            "Link speed" => status.link_speed_mbps = number(value, "Mbps"),
+            "Frequency" => status.frequency_mhz = number(value, "MHz"),
+            // An InetAddress, printed as "/192.168.1.23"
+            "IP" => status.ip = Some(value.trim_start_matches('/').to_string()).filter(|ip| !ip.is_empty()),
+            _ => {}
+        }
+    }
+    Some(status)
+}
+
+/// The IPv4 address in `ip -4 -o addr show wlan0` output
+pub fn parse_interface_address(output: &str) -> Option<String> {
+    let mut words = output.split_whitespace();
+    words.find(|word| *word == "inet")?;
+    let address = words.next()?;
+    Some(address.split('/').next().unwrap_or(address).to_string())
+}
+
+fn unquote(ssid: &str) -> &str {
+    ssid.strip_prefix('"').and_then(|s| s.strip_suffix('"')).unwrap_or(ssid)
+}
+
+/// "390Mbps" -> 390, unknown values are -1
+fn number(value: &str, unit: &str) -> Option<i32> {
+    value.trim_end_matches(unit).parse().ok().filter(|&n| n >= 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::fixtures::{assert_fields, fixture_cases};
+
+    #[test]
+    fn test_parse_interface_address() {
+        let output = "23: wlan0    inet 192.168.1.23/24 brd 192.168.1.255 scope global wlan0\\       valid_lft forever preferred_lft forever\n";
+        assert_eq!(parse_interface_address(output).as_deref(), Some("192.168.1.23"));
+        assert_eq!(parse_interface_address(""), None);
+    }
+
+    #[test]
+    fn test_fixture_wifi() {
+        for (name, output, expected) in fixture_cases("wifi") {
+            assert_fields(&name, &parse_wifi_status(&output).unwrap(), &expected);
+        }
+    }
+}
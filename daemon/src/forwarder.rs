@@ -0,0 +1,291 @@
+use chrono::Utc;
+use log::{debug, info, warn};
+use regex_lite::Regex;
+use serde::Serialize;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::auth::AuthService;
+use crate::config::ForwarderConfig;
+use crate::executor::shell::{ExecError, ShellCommand};
+use crate::executor::sms::{self, InboxFilter, SmsKind, SmsMessage};
+use crate::executor::Backend;
+use crate::webhook::{self, WebhookUrl};
+
+/// Events kept for subscribers that fall behind; they catch up from the provider after that
+const CHANNEL_CAPACITY: usize = 64;
+
+/// A received text as pushed to subscribers and the webhook
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SmsEvent {
+    #[serde(flatten)]
+    pub message: SmsMessage,
+    /// One-time code found in the body
+    pub otp: Option<String>,
+}
+
+/// Finds one-time codes with the configured patterns, first match wins
+pub struct OtpExtractor {
+    patterns: Vec<Regex>,
+}
+
+impl OtpExtractor {
+    pub fn new(patterns: &[String]) -> Result<Self, regex_lite::Error> {
+        let patterns = patterns.iter().map(|p| Regex::new(p)).collect::<Result<_, _>>()?;
+        Ok(Self { patterns })
+    }
+
+    /// The code without separators, "123-456" becomes "123456"
+    pub fn extract(&self, body: &str) -> Option<String> {
+        self.patterns.iter().find_map(|pattern| {
+            let captures = pattern.captures(body)?;
+            let code = captures.get(1).or_else(|| captures.get(0))?.as_str();
+            Some(code.chars().filter(|c| !matches!(c, '-' | ' ')).collect())
+        })
+    }
+}
+
+/// Watches the SMS provider and forwards every received text, at least once.
+/// The ID of the last text handed to the webhook is persisted, so texts that
+/// arrive while the daemon is down are forwarded when it comes back.
+pub struct SmsForwarder {
+    backend: Arc<dyn Backend>,
+    auth: Arc<AuthService>,
+    config: ForwarderConfig,
+    otp: OtpExtractor,
+    webhook: Option<WebhookUrl>,
+    /// ID of the last forwarded text, None until known
+    cursor: Mutex<Option<i64>>,
+    events: broadcast::Sender<SmsEvent>,
+}
+
+impl SmsForwarder {
+    pub fn new(backend: Arc<dyn Backend>, auth: Arc<AuthService>, config: ForwarderConfig) -> Result<Self, String> {
+        let otp = OtpExtractor::new(&config.otp_patterns).map_err(|e| format!("invalid otp_patterns: {}", e))?;
+        let webhook = config.webhook_url.as_deref().map(WebhookUrl::parse).transpose()?;
+        let cursor = match fs::read_to_string(&config.cursor_file) {
+            Ok(contents) => Some(contents.trim().parse().map_err(|_| {
+                format!("{} does not hold an SMS ID: {:?}", config.cursor_file, contents.trim())
+            })?),
+            Err(_) => None,
+        };
+        let (events, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Ok(Self { backend, auth, config, otp, webhook, cursor: Mutex::new(cursor), events })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.config.enabled
+    }
+
+    /// Spawn the polling loop
+    pub fn start(self: &Arc<Self>) {
+        if let Some(ref url) = self.webhook {
+            info!("Forwarding received SMS to {}", url);
+        }
+        let forwarder = Arc::clone(self);
+        let interval = Duration::from_secs(self.config.poll_secs.max(1));
+        actix_web::rt::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                // The first poll only notes the newest text, so later clients get what follows it
+                let started = forwarder.cursor.lock().unwrap().is_some();
+                if started && !forwarder.has_listeners() {
+                    continue;
+                }
+                if let Err(e) = forwarder.poll().await {
+                    warn!("Checking for new SMS failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Whether anything takes the texts: a webhook, or a connected /sms/events
+    /// client. The provider is left alone otherwise, and the texts that arrive
+    /// meanwhile go out on the next poll after a client connects.
+    fn has_listeners(&self) -> bool {
+        self.webhook.is_some() || self.events.receiver_count() > 0
+    }
+
+    /// Live events. Subscribe before reading the backlog so nothing falls in between.
+    pub fn subscribe(&self) -> broadcast::Receiver<SmsEvent> {
+        self.events.subscribe()
+    }
+
+    /// Received texts with an ID above `after_id`, oldest first
+    pub async fn received_after(&self, after_id: i64) -> Result<Vec<SmsEvent>, ExecError> {
+        let output = self.backend.execute(&ShellCommand::ContentQuery(sms::received_after_query(after_id))).await?;
+        // Backends that ignore the selection or sort order still give the right answer
+        let mut messages: Vec<SmsMessage> = sms::parse_messages(&output)
+            .into_iter()
+            .filter(|m| m.kind == SmsKind::Inbox && m.id > after_id)
+            .collect();
+        messages.sort_by_key(|m| m.id);
+        Ok(messages.into_iter().map(|message| self.event(message)).collect())
+    }
+
+    fn event(&self, message: SmsMessage) -> SmsEvent {
+        let otp = self.otp.extract(&message.body);
+        SmsEvent { message, otp }
+    }
+
+    /// Forward texts that arrived since the last poll. Returns how many were forwarded.
+    /// A webhook failure stops the round; the same text is retried on the next poll.
+    pub async fn poll(&self) -> Result<usize, ExecError> {
+        let cursor = *self.cursor.lock().unwrap();
+        let Some(cursor) = cursor else {
+            // First run: start after the newest text instead of forwarding the whole inbox
+            let output = self.backend.execute(&ShellCommand::ContentQuery(InboxFilter::default().query())).await?;
+            let newest = sms::parse_messages(&output).iter().map(|m| m.id).max().unwrap_or(0);
+            info!("Forwarding SMS received after ID {}", newest);
+            self.advance(newest);
+            return Ok(0);
+        };
+
+        let mut forwarded = 0;
+        for event in self.received_after(cursor).await? {
+            debug!("New SMS {} from {:?}", event.message.id, event.message.address);
+            // No subscribers is not an error
+            let _ = self.events.send(event.clone());
+            if let Some(ref url) = self.webhook {
+                if let Err(e) = self.post_webhook(url, &event).await {
+                    warn!("Webhook {} rejected SMS {}, retrying on the next poll: {}", url, event.message.id, e);
+                    break;
+                }
+            }
+            self.advance(event.message.id);
+            forwarded += 1;
+        }
+        Ok(forwarded)
+    }
+
+    async fn post_webhook(&self, url: &WebhookUrl, event: &SmsEvent) -> std::io::Result<()> {
+        let body = serde_json::to_vec(event).expect("event serializes");
+        // Signed like client requests, so the receiver can use the same check
+        let timestamp = Utc::now().timestamp_millis().to_string();
+        let headers = [("X-Auth", self.auth.sign(&body, &timestamp)), ("X-Time", timestamp)];
+        let timeout = Duration::from_millis(self.config.webhook_timeout_ms);
+        match webhook::post_json(url, &body, &headers, timeout).await? {
+            200..=299 => Ok(()),
+            status => Err(std::io::Error::other(format!("HTTP {}", status))),
+        }
+    }
+
+    fn advance(&self, id: i64) {
+        *self.cursor.lock().unwrap() = Some(id);
+        // Write then rename, so a crash never leaves a truncated cursor
+        let tmp = format!("{}.tmp", self.config.cursor_file);
+        if let Err(e) = fs::write(&tmp, id.to_string()).and_then(|_| fs::rename(&tmp, &self.config.cursor_file)) {
+            warn!("Failed to save SMS cursor to {}: {}", self.config.cursor_file, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::simulator::SimulatedBackend;
+
+    fn config(name: &str) -> ForwarderConfig {
+        let path = std::env::temp_dir().join(format!("ohmyphone-{}-{}.cursor", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        ForwarderConfig { cursor_file: path.to_string_lossy().to_string(), ..Default::default() }
+    }
+
+    fn forwarder(backend: &Arc<SimulatedBackend>, config: &ForwarderConfig) -> SmsForwarder {
+        let auth = Arc::new(AuthService::new("test-secret".to_string(), 30));
+        SmsForwarder::new(backend.clone(), auth, config.clone()).unwrap()
+    }
+
+    #[test]
+    fn test_otp_extractor() {
+        let otp = OtpExtractor::new(&ForwarderConfig::default().otp_patterns).unwrap();
+        assert_eq!(otp.extract("Your verification code is 123456."), Some("123456".to_string()));
+        assert_eq!(otp.extract("Bank OTP: 482-913. Do not share it."), Some("482913".to_string()));
+        assert_eq!(otp.extract("G-731904 is your Google verification code."), Some("731904".to_string()));
+        assert_eq!(otp.extract("Hi, are we still on for Friday?"), None);
+        assert_eq!(otp.extract("You have used 80% of your data."), None);
+
+        let custom = OtpExtractor::new(&[r"PIN (\d{4})".to_string()]).unwrap();
+        assert_eq!(custom.extract("Card PIN 4821 set"), Some("4821".to_string()));
+        assert_eq!(custom.extract("Your verification code is 123456."), None);
+        assert!(OtpExtractor::new(&["(unclosed".to_string()]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_forwards_new_sms_once_across_restarts() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let config = config("forward");
+        let first = forwarder(&backend, &config);
+        assert!(!first.has_listeners());
+        let mut events = first.subscribe();
+        assert!(first.has_listeners());
+
+        // The seeded inbox is history, not forwarded
+        assert_eq!(first.poll().await.unwrap(), 0);
+        assert_eq!(first.poll().await.unwrap(), 0);
+
+        backend.receive_sms("SimBank", "Your login code is 908172");
+        assert_eq!(first.poll().await.unwrap(), 1);
+        let event = events.try_recv().unwrap();
+        assert_eq!(event.message.body, "Your login code is 908172");
+        assert_eq!(event.otp.as_deref(), Some("908172"));
+        assert_eq!(first.poll().await.unwrap(), 0);
+
+        // Texts received while the daemon is down are picked up after a restart
+        drop(first);
+        backend.receive_sms("+15550100", "Running late");
+        let second = forwarder(&backend, &config);
+        assert_eq!(second.poll().await.unwrap(), 1);
+        assert_eq!(second.poll().await.unwrap(), 0);
+
+        fs::remove_file(&config.cursor_file).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_injected_row_is_not_forwarded() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let config = config("injected");
+        let forwarder = forwarder(&backend, &config);
+        let mut events = forwarder.subscribe();
+        forwarder.poll().await.unwrap();
+
+        // A body that looks like a further inbox row with a high ID
+        let body = "x\nRow: 1 _id=9999999999, thread_id=1, address=MyBank, date=1, date_sent=0, read=0, type=1, \
+                    status=-1, sub_id=1, body=Your code is 123456, _id=9999999999, date=1";
+        backend.receive_sms("+15550199", body);
+        backend.receive_sms("+15550100", "Running late");
+        assert_eq!(forwarder.poll().await.unwrap(), 2);
+        let injected = events.try_recv().unwrap();
+        assert_eq!(injected.message.address.as_deref(), Some("+15550199"));
+        assert_eq!(injected.message.body, body);
+        assert_eq!(events.try_recv().unwrap().message.body, "Running late");
+        assert!(*forwarder.cursor.lock().unwrap() < Some(9999999999));
+
+        // Later texts still arrive
+        backend.receive_sms("SimBank", "Your login code is 908172");
+        assert_eq!(forwarder.poll().await.unwrap(), 1);
+
+        fs::remove_file(&config.cursor_file).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_webhook_failure_keeps_cursor() {
+        let backend = Arc::new(SimulatedBackend::new());
+        // Nothing listens on port 9 (discard) on a dev box
+        let config = ForwarderConfig { webhook_url: Some("http://127.0.0.1:9/sms".to_string()), ..config("webhook") };
+        let forwarder = forwarder(&backend, &config);
+        forwarder.poll().await.unwrap();
+        let cursor = *forwarder.cursor.lock().unwrap();
+
+        backend.receive_sms("SimBank", "Your code is 1234");
+        assert_eq!(forwarder.poll().await.unwrap(), 0);
+        assert_eq!(*forwarder.cursor.lock().unwrap(), cursor);
+        assert_eq!(forwarder.received_after(cursor.unwrap()).await.unwrap().len(), 1);
+
+        fs::remove_file(&config.cursor_file).unwrap();
+    }
+}
@@ -0,0 +1,349 @@
+//! Keeping radio changes from cutting off the connection that asked for them.
+//! A change that would take down the network a request came in on is refused,
+//! unless the caller asks for it to be reverted after a timeout; the revert is
+//! cancelled when the client confirms it can still reach the daemon.
+
+use chrono::Utc;
+use log::{error, info, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::AbortHandle;
+
+use crate::config::LockoutConfig;
+use crate::executor::airplane::Radio;
+use crate::executor::connectivity::{self, Link, Transport};
+use crate::executor::shell::{ExecError, ShellCommand};
+use crate::executor::tethering::TetherKind;
+use crate::executor::Backend;
+
+/// How a client reaches the daemon
+#[derive(Debug, Clone, PartialEq)]
+pub struct ControlPath {
+    /// Interface the request came in on
+    pub arrival: Link,
+    /// Network carrying it: the interface's own, or for a tunnel the default
+    /// network it rides on. None when that could not be told, including an
+    /// interface whose name gives no clue.
+    pub carrier: Option<Transport>,
+}
+
+impl fmt::Display for ControlPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let carrier = self.carrier.map_or("an unknown network", Transport::name);
+        if self.arrival.transport == Transport::Vpn {
+            write!(f, "{} over {}", self.arrival.interface, carrier)
+        } else {
+            write!(f, "{} on {}", self.arrival.interface, carrier)
+        }
+    }
+}
+
+/// A radio change a request asks for
+#[derive(Debug, Clone, PartialEq)]
+pub enum RadioChange {
+    /// `default_data` is false for a SIM whose data switch applies only once it carries data
+    Data { enable: bool, default_data: bool },
+    Airplane { enable: bool, keep: Vec<Radio> },
+    Bluetooth { enable: bool },
+    /// `join` is the network to switch to when Wi-Fi is already connected to another one
+    Wifi { enable: bool, join: Option<String> },
+    /// `kind` is `Hotspot` or `Usb`, the two POST /tethering switches
+    Tethering { kind: TetherKind, enable: bool },
+}
+
+impl RadioChange {
+    /// Whether the change takes down networks of this transport
+    pub fn cuts(&self, transport: Transport) -> bool {
+        match self {
+            RadioChange::Data { enable, default_data } => !enable && *default_data && transport == Transport::Cellular,
+            RadioChange::Airplane { enable, keep } => {
+                *enable
+                    && match transport {
+                        Transport::Cellular => true,
+                        Transport::Wifi => !keep.contains(&Radio::Wifi),
+                        Transport::Bluetooth => !keep.contains(&Radio::Bluetooth),
+                        _ => false,
+                    }
+            }
+            RadioChange::Bluetooth { enable } => !enable && transport == Transport::Bluetooth,
+            // Leaving a network for another drops the association, at least for a while
+            RadioChange::Wifi { enable, join } => (!enable || join.is_some()) && transport == Transport::Wifi,
+            // Stopping the hotspot drops its clients, and many phones drop
+            // their own Wi-Fi connection to start it
+            RadioChange::Tethering { kind: TetherKind::Hotspot, .. } => transport == Transport::Wifi,
+            RadioChange::Tethering { enable, .. } => !enable && transport == Transport::Usb,
+        }
+    }
+
+    /// Whether the change takes anything down at all
+    fn cuts_any(&self) -> bool {
+        match self {
+            RadioChange::Data { enable, default_data } => !enable && *default_data,
+            RadioChange::Airplane { enable, .. } => *enable,
+            RadioChange::Bluetooth { enable } => !enable,
+            RadioChange::Wifi { enable, join } => !enable || join.is_some(),
+            RadioChange::Tethering { kind: TetherKind::Hotspot, .. } => true,
+            RadioChange::Tethering { enable, .. } => !enable,
+        }
+    }
+
+    fn describe(&self) -> &'static str {
+        match self {
+            RadioChange::Data { enable: true, .. } => "Enabling mobile data",
+            RadioChange::Data { enable: false, .. } => "Disabling mobile data",
+            RadioChange::Airplane { enable: true, .. } => "Enabling airplane mode",
+            RadioChange::Airplane { enable: false, .. } => "Disabling airplane mode",
+            RadioChange::Bluetooth { enable: true } => "Enabling Bluetooth",
+            RadioChange::Bluetooth { enable: false } => "Disabling Bluetooth",
+            RadioChange::Wifi { join: Some(_), .. } => "Switching to another Wi-Fi network",
+            RadioChange::Wifi { enable: true, .. } => "Enabling Wi-Fi",
+            RadioChange::Wifi { enable: false, .. } => "Disabling Wi-Fi",
+            RadioChange::Tethering { kind: TetherKind::Hotspot, enable: true } => "Starting the Wi-Fi hotspot",
+            RadioChange::Tethering { kind: TetherKind::Hotspot, enable: false } => "Stopping the Wi-Fi hotspot",
+            RadioChange::Tethering { enable: true, .. } => "Starting USB tethering",
+            RadioChange::Tethering { enable: false, .. } => "Stopping USB tethering",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum LockoutError {
+    InvalidRequest(String),
+    /// The change would cut off the request's own connection, or its path is unknown
+    WouldLockOut { change: &'static str, path: Option<ControlPath> },
+    /// No pending revert with this ID; it was confirmed or has already run
+    UnknownRevert(u64),
+}
+
+impl LockoutError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            LockoutError::InvalidRequest(_) => "invalid_request",
+            LockoutError::WouldLockOut { .. } => "would_lock_out",
+            LockoutError::UnknownRevert(_) => "not_found",
+        }
+    }
+}
+
+impl fmt::Display for LockoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LockoutError::InvalidRequest(detail) => write!(f, "{}", detail),
+            LockoutError::WouldLockOut { change, path: Some(path) } if path.carrier.is_some() => write!(
+                f,
+                "{} would cut off this connection ({}); set revert_after_secs to do it anyway",
+                change, path
+            ),
+            LockoutError::WouldLockOut { change, .. } => write!(
+                f,
+                "{} might cut off this connection, whose network could not be told; \
+                 set revert_after_secs to do it anyway",
+                change
+            ),
+            LockoutError::UnknownRevert(id) => write!(f, "No pending revert with ID {}", id),
+        }
+    }
+}
+
+/// A change that is undone unless confirmed in time
+#[derive(Debug, Clone, Serialize)]
+pub struct PendingRevert {
+    pub id: u64,
+    /// Unix ms at which the inverse command runs
+    pub revert_at: i64,
+}
+
+/// Checks radio changes against the path of the request, and runs the reverts
+/// of changes that went ahead on a timeout
+pub struct LockoutGuard {
+    backend: Arc<dyn Backend>,
+    config: LockoutConfig,
+    pending: Mutex<HashMap<u64, AbortHandle>>,
+    next_id: AtomicU64,
+}
+
+impl LockoutGuard {
+    pub fn new(backend: Arc<dyn Backend>, config: LockoutConfig) -> Self {
+        Self { backend, config, pending: Mutex::new(HashMap::new()), next_id: AtomicU64::new(1) }
+    }
+
+    /// Work out which network a client at `peer` reaches the daemon over
+    pub async fn control_path(&self, peer: IpAddr) -> Result<ControlPath, ExecError> {
+        let output = self.backend.execute(&ShellCommand::GetRoute(peer)).await?;
+        let arrival = connectivity::parse_route(&output)
+            .ok_or_else(|| ExecError::Parse(format!("no interface in ip route get {}", peer)))?;
+        let carrier = match arrival.transport {
+            // Tailscale and other tunnels ride on the default network
+            Transport::Vpn => {
+                let output = self.backend.execute(&ShellCommand::GetMobileDataConnection).await?;
+                connectivity::parse_default_network(&output).map(|link| link.transport)
+            }
+            transport => Some(transport),
+        };
+        let carrier = carrier.filter(|&transport| !matches!(transport, Transport::Vpn | Transport::Other));
+        Ok(ControlPath { arrival, carrier })
+    }
+
+    /// Check a change before it is made. Returns how long to wait before
+    /// reverting it, when the request asked for a revert.
+    pub async fn check(
+        &self,
+        peer: Option<IpAddr>,
+        change: &RadioChange,
+        revert_after_secs: Option<u64>,
+    ) -> Result<Option<Duration>, LockoutError> {
+        let revert = match revert_after_secs {
+            Some(secs) if secs == 0 || secs > self.config.max_revert_secs => {
+                return Err(LockoutError::InvalidRequest(format!(
+                    "revert_after_secs must be between 1 and {}",
+                    self.config.max_revert_secs
+                )));
+            }
+            secs => secs.map(Duration::from_secs),
+        };
+        if revert.is_some() || !self.config.enabled || !change.cuts_any() {
+            return Ok(revert);
+        }
+
+        let path = match peer.map(|peer| peer.to_canonical()) {
+            Some(peer) => match self.control_path(peer).await {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    warn!("Failed to trace the connection from {}: {}", peer, e);
+                    None
+                }
+            },
+            None => None,
+        };
+        // A path that cannot be told is treated as one the change cuts
+        match path.as_ref().and_then(|path| path.carrier) {
+            Some(carrier) if !change.cuts(carrier) => Ok(None),
+            _ => Err(LockoutError::WouldLockOut { change: change.describe(), path }),
+        }
+    }
+
+    /// Run `inverse` after `after`, unless the revert is confirmed first
+    pub fn schedule(self: &Arc<Self>, inverse: ShellCommand, after: Duration) -> PendingRevert {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let revert_at = Utc::now().timestamp_millis() + after.as_millis() as i64;
+        // Counted from now rather than from when the task first runs
+        let deadline = tokio::time::Instant::now() + after;
+        info!("Reverting with {:?} in {}s unless revert {} is confirmed", inverse, after.as_secs(), id);
+
+        let guard = Arc::clone(self);
+        let mut pending = self.pending.lock().unwrap();
+        let task = actix_web::rt::spawn(async move {
+            tokio::time::sleep_until(deadline).await;
+            guard.pending.lock().unwrap().remove(&id);
+            warn!("Revert {} was not confirmed, running {:?}", id, inverse);
+            if let Err(e) = guard.backend.execute(&inverse).await {
+                error!("Revert {} failed: {}", id, e);
+            }
+        });
+        pending.insert(id, task.abort_handle());
+        PendingRevert { id, revert_at }
+    }
+
+    /// Cancel a pending revert, now that the client has shown it can reach the daemon
+    pub fn confirm(&self, id: u64) -> Result<(), LockoutError> {
+        let task = self.pending.lock().unwrap().remove(&id).ok_or(LockoutError::UnknownRevert(id))?;
+        task.abort();
+        info!("Revert {} confirmed, keeping the change", id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::simulator::SimulatedBackend;
+
+    fn guard(backend: Arc<SimulatedBackend>) -> Arc<LockoutGuard> {
+        Arc::new(LockoutGuard::new(backend, LockoutConfig::default()))
+    }
+
+    #[tokio::test]
+    async fn test_check_against_control_path() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let guard = guard(backend.clone());
+        let tailscale: IpAddr = "100.101.1.2".parse().unwrap();
+        let data_off = RadioChange::Data { enable: false, default_data: true };
+        let airplane = RadioChange::Airplane { enable: true, keep: vec![Radio::Wifi] };
+
+        // Tailscale over Wi-Fi: mobile data and airplane mode keeping Wi-Fi are safe
+        let path = guard.control_path(tailscale).await.unwrap();
+        assert_eq!((path.arrival.interface.as_str(), path.carrier), ("tun0", Some(Transport::Wifi)));
+        assert_eq!(guard.check(Some(tailscale), &data_off, None).await.unwrap(), None);
+        assert_eq!(guard.check(Some(tailscale), &airplane, None).await.unwrap(), None);
+        let everything = RadioChange::Airplane { enable: true, keep: vec![] };
+        assert_eq!(guard.check(Some(tailscale), &everything, None).await.unwrap_err().code(), "would_lock_out");
+        let wifi_off = RadioChange::Wifi { enable: false, join: None };
+        assert_eq!(guard.check(Some(tailscale), &wifi_off, None).await.unwrap_err().code(), "would_lock_out");
+        let switch = RadioChange::Wifi { enable: true, join: Some("Office".to_string()) };
+        let refused = guard.check(Some(tailscale), &switch, None).await.unwrap_err();
+        assert!(refused.to_string().starts_with("Switching to another Wi-Fi network would cut off this connection"));
+        let wifi_on = RadioChange::Wifi { enable: true, join: None };
+        assert_eq!(guard.check(Some(tailscale), &wifi_on, None).await.unwrap(), None);
+        // Many phones drop their Wi-Fi connection to start the hotspot
+        let hotspot_on = RadioChange::Tethering { kind: TetherKind::Hotspot, enable: true };
+        let refused = guard.check(Some(tailscale), &hotspot_on, None).await.unwrap_err();
+        assert!(refused.to_string().starts_with("Starting the Wi-Fi hotspot would cut off this connection"));
+        let usb_off = RadioChange::Tethering { kind: TetherKind::Usb, enable: false };
+        assert_eq!(guard.check(Some(tailscale), &usb_off, None).await.unwrap(), None);
+
+        // Tailscale over cellular once Wi-Fi is off
+        backend.execute(&ShellCommand::DisableWifi).await.unwrap();
+        let refused = guard.check(Some(tailscale), &data_off, None).await.unwrap_err();
+        assert_eq!(refused.to_string(), "Disabling mobile data would cut off this connection (tun0 over cellular); \
+                                         set revert_after_secs to do it anyway");
+        let revert = guard.check(Some(tailscale), &data_off, Some(60)).await.unwrap();
+        assert_eq!(revert, Some(Duration::from_secs(60)));
+        assert_eq!(guard.check(Some(tailscale), &data_off, Some(0)).await.unwrap_err().code(), "invalid_request");
+
+        // Local clients and changes that cut nothing are never refused
+        let local = Some("::ffff:127.0.0.1".parse().unwrap());
+        assert_eq!(guard.check(local, &data_off, None).await.unwrap(), None);
+        let data_on = RadioChange::Data { enable: true, default_data: true };
+        assert_eq!(guard.check(None, &data_on, None).await.unwrap(), None);
+        // An unknown path is refused
+        assert!(guard.check(None, &data_off, None).await.is_err());
+        let vendor: IpAddr = "192.0.2.7".parse().unwrap();
+        let path = guard.control_path(vendor).await.unwrap();
+        assert_eq!((path.arrival.interface.as_str(), path.carrier), ("vendor0", None));
+        let refused = guard.check(Some(vendor), &data_off, None).await.unwrap_err();
+        assert!(refused.to_string().contains("could not be told"));
+        assert_eq!(guard.check(Some(vendor), &data_on, None).await.unwrap(), None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_unconfirmed_change_is_reverted() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let guard = guard(backend.clone());
+        let data = || async { backend.execute(&ShellCommand::GetMobileDataConnection).await.unwrap() };
+
+        // Reverts are spawned onto the actix arbiter's local set
+        let local = tokio::task::LocalSet::new();
+        local
+            .run_until(async {
+                backend.execute(&ShellCommand::DisableData).await.unwrap();
+                let confirmed = guard.schedule(ShellCommand::EnableData, Duration::from_secs(50));
+                let reverted = guard.schedule(ShellCommand::EnableData, Duration::from_secs(100));
+                guard.confirm(confirmed.id).unwrap();
+                assert_eq!(guard.confirm(confirmed.id).unwrap_err().code(), "not_found");
+
+                tokio::time::advance(Duration::from_secs(99)).await;
+                assert!(!crate::executor::shell::parse_mobile_data_connected(&data().await));
+                tokio::time::advance(Duration::from_secs(1)).await;
+                // The timer has fired, let the revert run
+                tokio::task::yield_now().await;
+                assert!(crate::executor::shell::parse_mobile_data_connected(&data().await));
+                assert!(guard.confirm(reverted.id).is_err());
+            })
+            .await;
+    }
+}
@@ -1,7 +1,13 @@
 mod auth;
+mod calls;
 mod config;
 mod api;
 mod executor;
+mod forwarder;
+mod lockout;
+mod sampler;
+mod ussd;
+mod webhook;
 
 use actix_web::{middleware, web, App, HttpServer};
 use std::sync::Arc;
@@ -35,18 +41,88 @@ async fn main() -> std::io::Result<()> {
         config.security.timestamp_window,
     ));
 
+    // Select the command backend (real shell, simulator or replay)
+    let backend = executor::from_config(&config.executor).unwrap_or_else(|e| {
+        error!("Failed to set up command backend: {}", e);
+        std::process::exit(1);
+    });
+    info!("Using {:?} command backend", config.executor.backend);
+
+    // USSD codes from the allowlist, with menu sessions
+    let ussd = Arc::new(ussd::UssdService::new(backend.clone(), config.ussd.clone()));
+
+    // Sample status in the background; take a first sample before serving
+    let sampler = Arc::new(sampler::StatusSampler::new(backend.clone(), ussd.clone(), config.sampler.clone()));
+    sampler.refresh_all().await;
+    sampler.start();
+
+    let sms_outbox = Arc::new(api::sms::SmsOutbox::new());
+
+    // Push received SMS to /sms/events subscribers and the webhook
+    let forwarder = forwarder::SmsForwarder::new(backend.clone(), auth_service.clone(), config.forwarder.clone())
+        .unwrap_or_else(|e| {
+            error!("Invalid [forwarder] config: {}", e);
+            std::process::exit(1);
+        });
+    let forwarder = Arc::new(forwarder);
+    if forwarder.enabled() {
+        forwarder.start();
+    }
+
+    // Networks POST /wifi may join, and the hotspot POST /tethering starts
+    if let Err(e) = config.wifi.validate() {
+        error!("Invalid [wifi] config: {}", e);
+        std::process::exit(1);
+    }
+    if let Some(Err(e)) = config.tethering.hotspot.as_ref().map(config::WifiNetwork::validate) {
+        error!("Invalid [tethering.hotspot] config: {}", e);
+        std::process::exit(1);
+    }
+    let wifi_config = Arc::new(config.wifi.clone());
+
+    // Radio changes that would cut off the request's own connection
+    let lockout = Arc::new(lockout::LockoutGuard::new(backend.clone(), config.lockout.clone()));
+    let tethering_config = Arc::new(config.tethering.clone());
+
     let bind_addr = format!("{}:{}", config.server.bind_address, config.server.port);
 
     // Start HTTP server
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(auth_service.clone()))
+            .app_data(web::Data::new(backend.clone()))
+            .app_data(web::Data::new(sampler.clone()))
+            .app_data(web::Data::new(sms_outbox.clone()))
+            .app_data(web::Data::new(forwarder.clone()))
+            .app_data(web::Data::new(ussd.clone()))
+            .app_data(web::Data::new(wifi_config.clone()))
+            .app_data(web::Data::new(tethering_config.clone()))
+            .app_data(web::Data::new(lockout.clone()))
             .wrap(middleware::Logger::default())
             .route("/status", web::get().to(api::status::get_status))
             .route("/radio/data", web::post().to(api::radio::toggle_data))
             .route("/radio/airplane", web::post().to(api::radio::toggle_airplane_mode))
+            .route("/radio/bluetooth", web::post().to(api::radio::toggle_bluetooth))
+            .route("/radio/confirm", web::post().to(api::radio::confirm_change))
             .route("/call/forward", web::post().to(api::call::set_call_forwarding))
+            .route("/call/forward", web::get().to(api::call::get_call_forwarding))
             .route("/call/dial", web::post().to(api::call::dial_call))
+            .route("/call/answer", web::post().to(api::call::answer_call))
+            .route("/call/reject", web::post().to(api::call::reject_call))
+            .route("/call/hangup", web::post().to(api::call::hangup_call))
+            .route("/call/events", web::get().to(api::call::call_events))
+            .route("/calls", web::get().to(api::call::list_calls))
+            .route("/network", web::get().to(api::network::get_network))
+            .route("/sms", web::get().to(api::sms::list_sms))
+            .route("/sms/events", web::get().to(api::sms::sms_events))
+            .route("/sms/send", web::post().to(api::sms::send_sms))
+            .route("/sms/send/{id}", web::get().to(api::sms::get_send_status))
+            .route("/ussd", web::post().to(api::ussd::run_ussd))
+            .route("/ussd/{session_id}", web::post().to(api::ussd::reply_ussd))
+            .route("/wifi", web::get().to(api::wifi::get_wifi))
+            .route("/wifi", web::post().to(api::wifi::set_wifi))
+            .route("/tethering", web::get().to(api::tethering::get_tethering))
+            .route("/tethering", web::post().to(api::tethering::set_tethering))
     })
     .bind(&bind_addr)?
     .run()
@@ -0,0 +1,600 @@
+use chrono::Utc;
+use log::{debug, warn};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+use crate::calls::{CallEvent, CallStatus, CallTracker};
+use crate::config::SamplerConfig;
+use crate::executor::airplane::RadioStatus;
+use crate::executor::battery::{self, BatteryInfo};
+use crate::executor::bluetooth::{self, BluetoothStatus};
+use crate::executor::call_forwarding::{ForwardingReason, ForwardingState};
+use crate::executor::shell::{self, ExecError, ShellCommand, DEFAULT_SUB_ID};
+use crate::executor::telephony::{self, NetworkInfo, RegState, SignalInfo, Subscriptions};
+use crate::executor::wifi;
+use crate::executor::{self, Backend};
+use crate::ussd::{UssdError, UssdService};
+
+/// Call events kept for /call/events subscribers that fall behind
+const CALL_EVENT_CAPACITY: usize = 16;
+
+/// One group of /status fields, refreshed on its own interval
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Probe {
+    Battery,
+    Signal,
+    Data,
+    Airplane,
+    Uptime,
+    CallForwarding,
+    Subscriptions,
+    Bluetooth,
+    Wifi,
+    Cell,
+    Call,
+}
+
+impl Probe {
+    pub const ALL: [Probe; 11] = [
+        Probe::Battery,
+        Probe::Signal,
+        Probe::Data,
+        Probe::Airplane,
+        Probe::Uptime,
+        Probe::CallForwarding,
+        Probe::Subscriptions,
+        Probe::Bluetooth,
+        Probe::Wifi,
+        Probe::Cell,
+        Probe::Call,
+    ];
+
+    /// Command whose output the probe parses, None for call forwarding
+    fn command(self) -> Option<ShellCommand> {
+        let command = match self {
+            Probe::Battery => ShellCommand::GetBattery,
+            Probe::Signal => ShellCommand::GetSignal,
+            // Use robust mobile data detection
+            Probe::Data => ShellCommand::GetMobileDataConnection,
+            Probe::Airplane => ShellCommand::GetAirplaneMode,
+            Probe::Uptime => ShellCommand::GetUptime,
+            // Interrogated with the network instead, see `refresh_call_forwarding`
+            Probe::CallForwarding => return None,
+            Probe::Subscriptions => ShellCommand::GetSubscriptions,
+            Probe::Bluetooth => ShellCommand::GetBluetoothState,
+            Probe::Wifi => ShellCommand::GetWifiStatus,
+            // The service state of each phone (POWER_OFF while the radio is off) and
+            // mCallState are in telephony.registry too, filled by the same fetch as Signal
+            Probe::Cell | Probe::Call => ShellCommand::GetSignal,
+        };
+        Some(command)
+    }
+
+    /// The probe whose refresh also fills this one, as both read the same
+    /// command output. Such probes get no refresh loop of their own.
+    fn filled_by(self) -> Option<Probe> {
+        match self {
+            Probe::Cell | Probe::Call => Some(Probe::Signal),
+            _ => None,
+        }
+    }
+
+    /// Deadline for one run. telephony.registry and the Bluetooth dump are the slow
+    /// ones on low-end phones, the rest should be near instant. Forwarding
+    /// interrogations wait for the network as long as `[ussd]` allows.
+    fn deadline(self) -> Duration {
+        match self {
+            Probe::Signal | Probe::Bluetooth | Probe::Cell | Probe::Call => Duration::from_secs(3),
+            Probe::Battery | Probe::Data | Probe::Subscriptions | Probe::CallForwarding | Probe::Wifi => {
+                Duration::from_secs(2)
+            }
+            Probe::Airplane | Probe::Uptime => Duration::from_secs(1),
+        }
+    }
+
+    fn interval(self, config: &SamplerConfig) -> Duration {
+        let secs = match self {
+            Probe::Battery => config.battery_secs,
+            // One telephony.registry fetch serves all three; `start` runs it more
+            // often while /call/events has clients
+            Probe::Signal | Probe::Cell | Probe::Call => config.signal_secs,
+            Probe::Data => config.data_secs,
+            Probe::Airplane => config.airplane_secs,
+            Probe::Uptime => config.uptime_secs,
+            Probe::CallForwarding => config.call_forwarding_secs,
+            Probe::Subscriptions => config.subscriptions_secs,
+            Probe::Bluetooth => config.bluetooth_secs,
+            Probe::Wifi => config.wifi_secs,
+        };
+        Duration::from_secs(secs.max(1))
+    }
+
+    /// Names of the /status fields this probe fills
+    pub fn fields(self) -> &'static [&'static str] {
+        match self {
+            Probe::Battery => &["battery", "charging", "battery_info"],
+            Probe::Signal => &["signal_dbm", "signal", "sims"],
+            Probe::Data => &["data_enabled"],
+            Probe::Airplane => &["airplane_mode"],
+            Probe::Uptime => &["uptime"],
+            Probe::CallForwarding => &["call_forwarding_active", "sims"],
+            Probe::Subscriptions => &["sims"],
+            Probe::Bluetooth => &["bluetooth", "radios"],
+            Probe::Wifi | Probe::Cell => &["radios"],
+            Probe::Call => &["call"],
+        }
+    }
+}
+
+/// Latest sample of one probe
+#[derive(Debug, Clone)]
+pub struct Sample<T> {
+    pub value: Option<T>,
+    /// Unix ms at which `value` was sampled
+    pub sampled_at: Option<i64>,
+    /// The most recent attempt failed, so `value` comes from an earlier one
+    pub failed: bool,
+}
+
+impl<T> Default for Sample<T> {
+    fn default() -> Self {
+        Self { value: None, sampled_at: None, failed: false }
+    }
+}
+
+impl<T> Sample<T> {
+    fn update(&mut self, probe: Probe, result: Result<T, ExecError>, now: i64) -> Result<(), ExecError> {
+        match result {
+            Ok(value) => {
+                self.value = Some(value);
+                self.sampled_at = Some(now);
+                self.failed = false;
+                Ok(())
+            }
+            Err(e) => {
+                warn!("Status probe {:?} failed: {}", probe, e);
+                self.failed = true;
+                Err(e)
+            }
+        }
+    }
+}
+
+/// What telephony.registry says about one phone (SIM slot)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PhoneReading {
+    /// Legacy `rssi=` value, or the serving cell's RSSI when that is missing
+    pub dbm: Option<i32>,
+    pub signal: Option<SignalInfo>,
+    pub network: Option<NetworkInfo>,
+    pub data_enabled: Option<bool>,
+    pub data_connected: Option<bool>,
+}
+
+impl PhoneReading {
+    fn parse(section: &str, slot: usize) -> Self {
+        let signal = telephony::parse_signal_info(section);
+        let dbm = Some(shell::parse_signal(section))
+            .filter(|&dbm| dbm != -999)
+            .or_else(|| signal.as_ref().and_then(|s| s.rssi));
+        let (data_enabled, data_connected) = telephony::parse_data_state(section);
+        Self {
+            dbm,
+            signal,
+            network: telephony::parse_network_info(section, &Default::default(), slot),
+            data_enabled,
+            data_connected,
+        }
+    }
+}
+
+/// Latest value of every /status field
+#[derive(Debug, Clone, Default)]
+pub struct Snapshot {
+    pub battery: Sample<BatteryInfo>,
+    /// Only kept with `raw_battery = true`
+    pub raw_battery: Option<String>,
+    /// One reading per phone section, in slot order
+    pub phones: Sample<Vec<PhoneReading>>,
+    pub data: Sample<bool>,
+    pub airplane: Sample<bool>,
+    pub uptime: Sample<u64>,
+    /// What the network reports for every reason, by subscription ID
+    pub call_forwarding: Sample<BTreeMap<i32, Vec<ForwardingState>>>,
+    pub subscriptions: Sample<Subscriptions>,
+    pub bluetooth: Sample<BluetoothStatus>,
+    /// Wi-Fi is enabled
+    pub wifi: Sample<bool>,
+    /// The cellular radio of any phone is powered
+    pub cell: Sample<bool>,
+    pub call: Sample<CallStatus>,
+}
+
+impl Snapshot {
+    /// Which radios the latest samples saw on
+    pub fn radios(&self) -> RadioStatus {
+        RadioStatus {
+            cell: self.cell.value,
+            wifi: self.wifi.value,
+            bluetooth: self.bluetooth.value.as_ref().map(|bluetooth| bluetooth.enabled),
+        }
+    }
+}
+
+/// Parse probe output, treating a parser's "unknown" answer as a parse failure
+fn parsed<T>(
+    output: Result<String, ExecError>,
+    parse: impl FnOnce(&str) -> Option<T>,
+) -> Result<T, ExecError> {
+    let output = output?;
+    parse(&output).ok_or_else(|| ExecError::Parse("unrecognised output".to_string()))
+}
+
+/// Samples status in the background so /status polls do not fork processes
+pub struct StatusSampler {
+    backend: Arc<dyn Backend>,
+    /// Forwarding interrogations share the modem with USSD sessions
+    ussd: Arc<UssdService>,
+    config: SamplerConfig,
+    snapshot: Mutex<Snapshot>,
+    calls: Mutex<CallTracker>,
+    call_events: broadcast::Sender<CallEvent>,
+}
+
+impl StatusSampler {
+    pub fn new(backend: Arc<dyn Backend>, ussd: Arc<UssdService>, config: SamplerConfig) -> Self {
+        let (call_events, _) = broadcast::channel(CALL_EVENT_CAPACITY);
+        Self {
+            backend,
+            ussd,
+            config,
+            snapshot: Mutex::new(Snapshot::default()),
+            calls: Mutex::new(CallTracker::new(Utc::now().timestamp_millis())),
+            call_events,
+        }
+    }
+
+    /// Spawn one refresh loop per probe, each on its own interval. The signal
+    /// loop ticks every `call_events_secs` but only samples on those ticks
+    /// while /call/events has clients, so calls are noticed sooner just then.
+    pub fn start(self: &Arc<Self>) {
+        for probe in Probe::ALL.into_iter().filter(|probe| probe.filled_by().is_none()) {
+            let sampler = Arc::clone(self);
+            let interval = probe.interval(&self.config);
+            let tick = match probe {
+                Probe::Signal => interval.min(Duration::from_secs(self.config.call_events_secs.max(1))),
+                _ => interval,
+            };
+            let ticks_per_sample = (interval.as_secs() / tick.as_secs()).max(1);
+            actix_web::rt::spawn(async move {
+                let mut ticker = tokio::time::interval(tick);
+                ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+                for ticks in 0u64.. {
+                    ticker.tick().await;
+                    if ticks % ticks_per_sample == 0 || sampler.calls_watched() {
+                        let _ = sampler.refresh(probe).await;
+                    }
+                }
+            });
+        }
+    }
+
+    /// Whether a /call/events client is connected
+    fn calls_watched(&self) -> bool {
+        self.call_events.receiver_count() > 0
+    }
+
+    /// Run one probe now and store the result. A failure is logged and kept
+    /// in the snapshot as well, so most callers can ignore the error.
+    pub async fn refresh(&self, probe: Probe) -> Result<(), ExecError> {
+        debug!("Sampling {:?}", probe);
+        let Some(command) = probe.command() else { return self.refresh_call_forwarding().await };
+        let output = executor::execute_within(self.backend.as_ref(), &command, probe.deadline()).await;
+        let now = Utc::now().timestamp_millis();
+        let mut snapshot = self.snapshot.lock().unwrap();
+
+        match probe {
+            Probe::Battery => {
+                if let (true, Ok(raw)) = (self.config.raw_battery, &output) {
+                    snapshot.raw_battery = Some(raw.clone());
+                }
+                let result = parsed(output, battery::parse_battery_info);
+                snapshot.battery.update(probe, result, now)
+            }
+            Probe::Signal | Probe::Cell | Probe::Call => {
+                let output = match output {
+                    Ok(output) => output,
+                    Err(e) => {
+                        warn!("Status probe {:?} failed: {}", probe, e);
+                        snapshot.phones.failed = true;
+                        snapshot.cell.failed = true;
+                        snapshot.call.failed = true;
+                        return Err(e);
+                    }
+                };
+                let phones = parsed(Ok(output.clone()), |o| {
+                    let phones: Vec<PhoneReading> = telephony::phone_sections(o)
+                        .iter()
+                        .enumerate()
+                        .map(|(slot, section)| PhoneReading::parse(section, slot))
+                        .collect();
+                    // No signal on any SIM (airplane mode, no coverage) is a reading too,
+                    // only output without a service state or signal line is not
+                    let recognised =
+                        phones.iter().any(|p| p.network.is_some() || p.dbm.is_some() || p.signal.is_some());
+                    recognised.then_some(phones)
+                });
+                let cell = parsed(Ok(output.clone()), |o| {
+                    let states: Vec<RegState> = telephony::phone_sections(o)
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(slot, section)| telephony::parse_network_info(section, &Default::default(), slot))
+                        .map(|network| network.voice_registration)
+                        .collect();
+                    (!states.is_empty()).then(|| states.iter().any(|&state| state != RegState::PowerOff))
+                });
+                let call = parsed(Ok(output), |o| {
+                    let readings: Vec<_> =
+                        telephony::phone_sections(o).into_iter().filter_map(telephony::parse_call_state).collect();
+                    (!readings.is_empty()).then_some(readings)
+                })
+                .map(|readings| {
+                    let mut calls = self.calls.lock().unwrap();
+                    for event in calls.observe(&readings, now) {
+                        debug!("Call {:?} -> {:?}", event.from, event.to);
+                        // No subscribers is not an error
+                        let _ = self.call_events.send(event);
+                    }
+                    calls.status().clone()
+                });
+                let phones = snapshot.phones.update(Probe::Signal, phones, now);
+                let cell = snapshot.cell.update(Probe::Cell, cell, now);
+                let call = snapshot.call.update(Probe::Call, call, now);
+                match probe {
+                    Probe::Cell => cell,
+                    Probe::Call => call,
+                    _ => phones,
+                }
+            }
+            Probe::Data => {
+                let result = parsed(output, |o| Some(shell::parse_mobile_data_connected(o)));
+                snapshot.data.update(probe, result, now)
+            }
+            Probe::Airplane => {
+                let result = parsed(output, |o| match o.trim() {
+                    "1" => Some(true),
+                    "0" => Some(false),
+                    _ => None,
+                });
+                snapshot.airplane.update(probe, result, now)
+            }
+            Probe::Uptime => {
+                let result = parsed(output, |o| Some(shell::parse_uptime(o)).filter(|&secs| secs > 0));
+                snapshot.uptime.update(probe, result, now)
+            }
+            Probe::CallForwarding => unreachable!("call forwarding has no command"),
+            Probe::Subscriptions => {
+                let result = output.map(|o| telephony::parse_subscriptions(&o));
+                snapshot.subscriptions.update(probe, result, now)
+            }
+            Probe::Bluetooth => {
+                let result = parsed(output, bluetooth::parse_bluetooth_status);
+                snapshot.bluetooth.update(probe, result, now)
+            }
+            Probe::Wifi => {
+                let result = parsed(output, |o| wifi::parse_wifi_status(o).map(|status| status.enabled));
+                snapshot.wifi.update(probe, result, now)
+            }
+        }
+    }
+
+    /// Ask the network for every forwarding reason on every SIM, the way GET
+    /// /call/forward does. Each answer is a round trip of a few seconds.
+    async fn refresh_call_forwarding(&self) -> Result<(), ExecError> {
+        let result = self.interrogate_forwarding().await;
+        let now = Utc::now().timestamp_millis();
+        self.snapshot.lock().unwrap().call_forwarding.update(Probe::CallForwarding, result, now)
+    }
+
+    async fn interrogate_forwarding(&self) -> Result<BTreeMap<i32, Vec<ForwardingState>>, ExecError> {
+        let deadline = Probe::CallForwarding.deadline();
+        let output = executor::execute_within(self.backend.as_ref(), &ShellCommand::GetSubscriptions, deadline).await?;
+        let mut sub_ids: Vec<i32> = telephony::parse_subscriptions(&output).active.iter().map(|sim| sim.id).collect();
+        if sub_ids.is_empty() {
+            // No isub on this phone, ask for the SIM the other forwarding commands use
+            sub_ids.push(DEFAULT_SUB_ID);
+        }
+
+        let mut forwarding = BTreeMap::new();
+        for sub_id in sub_ids {
+            let mut states = Vec::new();
+            for reason in ForwardingReason::ALL {
+                let state = self.ussd.query_forwarding(reason, sub_id).await.map_err(|e| match e {
+                    UssdError::Exec(e) => e,
+                    // Busy with a USSD menu, or the network did not answer
+                    e => ExecError::NonZeroExit { code: None, stderr: e.to_string() },
+                })?;
+                states.push(state);
+            }
+            forwarding.insert(sub_id, states);
+        }
+        Ok(forwarding)
+    }
+
+    /// Keep a forwarding state the network has just reported, e.g. after a change,
+    /// rather than interrogating every reason again
+    pub fn record_forwarding(&self, sub_id: i32, state: ForwardingState) {
+        let mut snapshot = self.snapshot.lock().unwrap();
+        let known = snapshot.call_forwarding.value.as_mut().and_then(|forwarding| forwarding.get_mut(&sub_id));
+        if let Some(known) = known.and_then(|states| states.iter_mut().find(|known| known.reason == state.reason)) {
+            *known = state;
+        }
+    }
+
+    /// Run all probes concurrently, e.g. when the user pulls to refresh
+    pub async fn refresh_all(&self) {
+        let _ = tokio::join!(
+            self.refresh(Probe::Battery),
+            // Fills the cellular radio and call state as well
+            self.refresh(Probe::Signal),
+            self.refresh(Probe::Data),
+            self.refresh(Probe::Airplane),
+            self.refresh(Probe::Uptime),
+            // Not call forwarding: eight network round trips are too slow to wait on
+            self.refresh(Probe::Subscriptions),
+            self.refresh(Probe::Bluetooth),
+            self.refresh(Probe::Wifi),
+        );
+    }
+
+    /// Refresh the given probes in the background, e.g. after a toggle changed them
+    pub fn refresh_later(self: &Arc<Self>, probes: &'static [Probe]) {
+        let sampler = Arc::clone(self);
+        actix_web::rt::spawn(async move {
+            for &probe in probes {
+                let _ = sampler.refresh(probe).await;
+            }
+        });
+    }
+
+    /// Call state transitions as they are sampled
+    pub fn subscribe_calls(&self) -> broadcast::Receiver<CallEvent> {
+        self.call_events.subscribe()
+    }
+
+    /// Sample the call state now, for actions that depend on it
+    pub async fn current_call(&self) -> Result<CallStatus, ExecError> {
+        self.refresh(Probe::Call).await?;
+        Ok(self.snapshot().call.value.expect("set by the successful refresh"))
+    }
+
+    /// Sample which radios are on now, for actions that switch them.
+    /// A radio whose probe fails is unknown rather than its last sample.
+    pub async fn current_radios(&self) -> RadioStatus {
+        let (cell, wifi, bluetooth) =
+            tokio::join!(self.refresh(Probe::Cell), self.refresh(Probe::Wifi), self.refresh(Probe::Bluetooth));
+        let radios = self.snapshot().radios();
+        RadioStatus {
+            cell: radios.cell.filter(|_| cell.is_ok()),
+            wifi: radios.wifi.filter(|_| wifi.is_ok()),
+            bluetooth: radios.bluetooth.filter(|_| bluetooth.is_ok()),
+        }
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        self.snapshot.lock().unwrap().clone()
+    }
+
+    /// A sample older than this is reported as stale even if it did not fail,
+    /// which catches a refresh loop that has stopped making progress
+    pub fn max_age(&self, probe: Probe) -> Duration {
+        probe.interval(&self.config) * 2 + probe.deadline()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::simulator::SimulatedBackend;
+    use crate::config::UssdConfig;
+    use crate::executor::telephony::CallState;
+
+    fn sampler() -> StatusSampler {
+        let backend: Arc<dyn Backend> = Arc::new(SimulatedBackend::new());
+        let ussd = Arc::new(UssdService::new(backend.clone(), UssdConfig::default()));
+        StatusSampler::new(backend, ussd, SamplerConfig::default())
+    }
+
+    #[tokio::test]
+    async fn test_refresh_all_fills_snapshot() {
+        let sampler = sampler();
+        assert!(sampler.snapshot().battery.value.is_none());
+
+        sampler.refresh_all().await;
+        let snapshot = sampler.snapshot();
+        assert_eq!(snapshot.radios(), RadioStatus { cell: Some(true), wifi: Some(true), bluetooth: Some(true) });
+        let battery = snapshot.battery.value.unwrap();
+        assert_eq!((battery.level, battery.charging), (82, false));
+        let phones = snapshot.phones.value.unwrap();
+        assert_eq!(phones.len(), 2);
+        assert_eq!(phones[0].dbm, Some(-93));
+        assert_eq!(phones[1].data_enabled, Some(false));
+        assert_eq!(snapshot.subscriptions.value.unwrap().active.len(), 2);
+        assert_eq!(snapshot.data.value, Some(true));
+        assert_eq!(snapshot.airplane.value, Some(false));
+        assert!(snapshot.uptime.value.is_some());
+        assert!(snapshot.call_forwarding.value.is_none());
+
+        // Every reason on both SIMs, none forwarded
+        sampler.refresh(Probe::CallForwarding).await.unwrap();
+        let forwarding = sampler.snapshot().call_forwarding.value.unwrap();
+        assert_eq!(forwarding.len(), 2);
+        assert!(forwarding.values().all(|states| states.len() == 4 && states.iter().all(|s| !s.active)));
+        let bluetooth = snapshot.bluetooth.value.unwrap();
+        assert!(bluetooth.enabled);
+        assert_eq!(bluetooth.devices.iter().filter(|d| d.connected).count(), 1);
+        assert_eq!(snapshot.call.value.unwrap().state, CallState::Idle);
+        assert!(snapshot.battery.sampled_at.is_some());
+        assert!(snapshot.raw_battery.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_failed_probe_keeps_previous_value() {
+        let sampler = sampler();
+        sampler.refresh(Probe::Signal).await.unwrap();
+        let first = sampler.snapshot().phones;
+
+        let mut second = first.clone();
+        let unrecognised = Err(ExecError::Parse("unrecognised output".to_string()));
+        assert!(second.update(Probe::Signal, unrecognised, Utc::now().timestamp_millis()).is_err());
+        assert!(second.failed);
+        assert_eq!(second.value, first.value);
+        assert_eq!(second.sampled_at, first.sampled_at);
+    }
+
+    #[tokio::test]
+    async fn test_no_signal_is_sampled() {
+        let sampler = sampler();
+        sampler.refresh(Probe::Signal).await.unwrap();
+
+        // Airplane mode removes the signal line, which is no signal rather than a failure
+        sampler.backend.execute(&ShellCommand::EnableAirplaneMode).await.unwrap();
+        sampler.refresh(Probe::Signal).await.unwrap();
+        let phones = sampler.snapshot().phones;
+        assert!(!phones.failed);
+        assert!(phones.value.unwrap().iter().all(|phone| phone.dbm.is_none() && phone.signal.is_none()));
+    }
+
+    #[tokio::test]
+    async fn test_call_transitions_are_emitted() {
+        let backend = Arc::new(SimulatedBackend::new());
+        let ussd = Arc::new(UssdService::new(backend.clone(), UssdConfig::default()));
+        let sampler = StatusSampler::new(backend.clone(), ussd, SamplerConfig::default());
+        assert!(!sampler.calls_watched());
+        let mut events = sampler.subscribe_calls();
+        assert!(sampler.calls_watched());
+        sampler.refresh(Probe::Call).await.unwrap();
+        assert!(events.try_recv().is_err());
+
+        // The signal refresh reads the call state from the same output
+        backend.ring("+15550100");
+        sampler.refresh(Probe::Signal).await.unwrap();
+        let event = events.try_recv().unwrap();
+        assert_eq!((event.from, event.to), (CallState::Idle, CallState::Ringing));
+        assert_eq!(event.call.number.as_deref(), Some("+15550100"));
+        assert_eq!(sampler.snapshot().call.value.unwrap().state, CallState::Ringing);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_picks_up_changes() {
+        let sampler = sampler();
+        sampler.refresh(Probe::Data).await.unwrap();
+        assert_eq!(sampler.snapshot().data.value, Some(true));
+
+        sampler.backend.execute(&ShellCommand::DisableData).await.unwrap();
+        sampler.refresh(Probe::Data).await.unwrap();
+        assert_eq!(sampler.snapshot().data.value, Some(false));
+    }
+}
@@ -0,0 +1,262 @@
+//! USSD and supplementary service codes, one exchange with the network at a time
+
+use chrono::Utc;
+use log::{debug, info};
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::config::UssdConfig;
+use crate::executor::call_forwarding::{self, ForwardingReason, ForwardingState};
+use crate::executor::parcel::Parcel;
+use crate::executor::shell::{ExecError, ShellCommand};
+use crate::executor::ussd::{self, UssdOutcome};
+use crate::executor::Backend;
+
+/// How often the radio log is checked while waiting for the network
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// What the network answered
+#[derive(Debug, Clone, PartialEq)]
+pub struct UssdReply {
+    pub message: String,
+    /// Set while the network shows a menu; answer it with `UssdService::reply`
+    pub session_id: Option<u64>,
+}
+
+#[derive(Debug)]
+pub enum UssdError {
+    /// No codes are allowed
+    Disabled,
+    NotAllowed(String),
+    InvalidInput(String),
+    /// No open menu with this ID, or it timed out
+    UnknownSession(u64),
+    /// Another menu is open; the modem runs one USSD session at a time
+    Busy(u64),
+    /// The network rejected the code
+    Failed,
+    /// No reply within `reply_timeout_ms`
+    NoReply,
+    Exec(ExecError),
+}
+
+impl UssdError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            UssdError::Disabled => "disabled",
+            UssdError::NotAllowed(_) => "not_allowed",
+            UssdError::InvalidInput(_) => "invalid_request",
+            UssdError::UnknownSession(_) => "not_found",
+            UssdError::Busy(_) => "ussd_busy",
+            UssdError::Failed => "ussd_failed",
+            UssdError::NoReply => "ussd_timeout",
+            UssdError::Exec(e) => e.code(),
+        }
+    }
+}
+
+impl fmt::Display for UssdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UssdError::Disabled => write!(f, "USSD is disabled, no codes are allowed in the config"),
+            UssdError::NotAllowed(code) => write!(f, "USSD code {} is not in the allowlist", code),
+            UssdError::InvalidInput(input) => write!(f, "Invalid menu reply {:?}", input),
+            UssdError::UnknownSession(id) => write!(f, "No open USSD session with ID {}", id),
+            UssdError::Busy(id) => write!(f, "USSD session {} is still open", id),
+            UssdError::Failed => write!(f, "The network rejected the USSD request"),
+            UssdError::NoReply => write!(f, "No USSD reply from the network"),
+            UssdError::Exec(e) => write!(f, "Failed to run USSD code: {}", e),
+        }
+    }
+}
+
+/// The menu the network is waiting on
+struct OpenSession {
+    id: u64,
+    sub_id: i32,
+    last_active: Instant,
+}
+
+/// Runs allowlisted USSD codes and carries menu sessions across requests
+pub struct UssdService {
+    backend: Arc<dyn Backend>,
+    config: UssdConfig,
+    /// Held for a whole exchange, so two requests never wait on the same reply
+    exchange: tokio::sync::Mutex<()>,
+    session: Mutex<Option<OpenSession>>,
+    next_id: AtomicU64,
+}
+
+impl UssdService {
+    pub fn new(backend: Arc<dyn Backend>, config: UssdConfig) -> Self {
+        Self {
+            backend,
+            config,
+            exchange: tokio::sync::Mutex::new(()),
+            session: Mutex::new(None),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Run a code on the given subscription
+    pub async fn run(&self, code: &str, sub_id: i32) -> Result<UssdReply, UssdError> {
+        if self.config.allowlist.is_empty() {
+            return Err(UssdError::Disabled);
+        }
+        if !ussd::is_valid_code(code) || !self.config.allowlist.iter().any(|allowed| allowed == code) {
+            return Err(UssdError::NotAllowed(code.to_string()));
+        }
+
+        let _exchange = self.exchange.lock().await;
+        if let Some(id) = self.open_session().map(|s| s.0) {
+            return Err(UssdError::Busy(id));
+        }
+        info!("Running USSD code {}", code);
+        self.exchange(code, sub_id).await
+    }
+
+    /// Run a supplementary service code built by the daemon, such as a call
+    /// forwarding change, and return the network's answer as the phone words it.
+    /// Not subject to the allowlist; shares the modem with USSD sessions.
+    pub async fn service_code(&self, command: ShellCommand) -> Result<String, UssdError> {
+        let _exchange = self.exchange.lock().await;
+        if let Some(id) = self.open_session().map(|s| s.0) {
+            return Err(UssdError::Busy(id));
+        }
+        self.send(&command, ussd::parse_service_result).await
+    }
+
+    /// Interrogate the network for one forwarding reason
+    pub async fn query_forwarding(&self, reason: ForwardingReason, sub_id: i32) -> Result<ForwardingState, UssdError> {
+        let answer = self.service_code(ShellCommand::QueryCallForwarding { reason, sub_id }).await?;
+        call_forwarding::parse_interrogation(reason, &answer).ok_or_else(|| {
+            UssdError::Exec(ExecError::Parse(format!("unexpected forwarding answer: {}", answer.trim())))
+        })
+    }
+
+    /// Answer the menu of an open session
+    pub async fn reply(&self, session_id: u64, input: &str) -> Result<UssdReply, UssdError> {
+        if !ussd::is_valid_reply(input) {
+            return Err(UssdError::InvalidInput(input.to_string()));
+        }
+
+        let _exchange = self.exchange.lock().await;
+        let sub_id = match self.open_session() {
+            Some((id, sub_id)) if id == session_id => sub_id,
+            _ => return Err(UssdError::UnknownSession(session_id)),
+        };
+        debug!("Replying {:?} to USSD session {}", input, session_id);
+        self.exchange(input, sub_id).await
+    }
+
+    /// ID and subscription of the open session, dropping it once timed out
+    fn open_session(&self) -> Option<(u64, i32)> {
+        let mut session = self.session.lock().unwrap();
+        let timeout = Duration::from_secs(self.config.session_timeout_secs);
+        if session.as_ref().is_some_and(|s| s.last_active.elapsed() > timeout) {
+            *session = None;
+        }
+        session.as_ref().map(|s| (s.id, s.sub_id))
+    }
+
+    /// Send and wait for the network's answer; the session stays open only while
+    /// the network shows a menu
+    async fn exchange(&self, text: &str, sub_id: i32) -> Result<UssdReply, UssdError> {
+        let command = ShellCommand::SendUssd { code: text.to_string(), sub_id };
+        let outcome = self.send(&command, ussd::parse_ussd_log).await;
+        let mut session = self.session.lock().unwrap();
+        match outcome {
+            Ok(UssdOutcome::Reply { message, awaiting_reply: true }) => {
+                let id = match session.as_ref() {
+                    Some(open) => open.id,
+                    None => self.next_id.fetch_add(1, Ordering::Relaxed),
+                };
+                *session = Some(OpenSession { id, sub_id, last_active: Instant::now() });
+                Ok(UssdReply { message, session_id: Some(id) })
+            }
+            Ok(UssdOutcome::Reply { message, awaiting_reply: false }) => {
+                *session = None;
+                Ok(UssdReply { message, session_id: None })
+            }
+            Ok(UssdOutcome::Failed) => {
+                *session = None;
+                Err(UssdError::Failed)
+            }
+            Err(e) => {
+                *session = None;
+                Err(e)
+            }
+        }
+    }
+
+    /// Run an MMI command and wait until `parse` finds the answer in the radio log
+    async fn send<T>(&self, command: &ShellCommand, parse: fn(&str) -> Option<T>) -> Result<T, UssdError> {
+        let since_ms = Utc::now().timestamp_millis();
+        let output = self.backend.execute(command).await.map_err(UssdError::Exec)?;
+        // handlePinMmiForSubscriber answers whether the phone took the text as an MMI code
+        let handled = Parcel::reply(&output).and_then(|mut reply| reply.read_bool()).map_err(UssdError::Exec)?;
+        if !handled {
+            return Err(UssdError::Failed);
+        }
+
+        let deadline = Instant::now() + Duration::from_millis(self.config.reply_timeout_ms);
+        loop {
+            let output =
+                self.backend.execute(&ShellCommand::GetMmiLog { since_ms }).await.map_err(UssdError::Exec)?;
+            if let Some(answer) = parse(&output) {
+                return Ok(answer);
+            }
+            if Instant::now() >= deadline {
+                return Err(UssdError::NoReply);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::simulator::SimulatedBackend;
+
+    fn service() -> UssdService {
+        let config = UssdConfig { allowlist: vec!["*100#".to_string(), "*123#".to_string()], ..Default::default() };
+        UssdService::new(Arc::new(SimulatedBackend::new()), config)
+    }
+
+    #[tokio::test]
+    async fn test_allowlist() {
+        let ussd = service();
+        let reply = ussd.run("*100#", 1).await.unwrap();
+        assert!(reply.message.starts_with("Balance"));
+        assert_eq!(reply.session_id, None);
+
+        assert!(matches!(ussd.run("*101#", 1).await, Err(UssdError::NotAllowed(_))));
+        assert!(matches!(ussd.run("*100#; reboot", 1).await, Err(UssdError::NotAllowed(_))));
+
+        let disabled = UssdService::new(Arc::new(SimulatedBackend::new()), UssdConfig::default());
+        assert!(matches!(disabled.run("*100#", 1).await, Err(UssdError::Disabled)));
+    }
+
+    #[tokio::test]
+    async fn test_menu_session() {
+        let ussd = service();
+        let menu = ussd.run("*123#", 1).await.unwrap();
+        let id = menu.session_id.unwrap();
+        assert!(menu.message.contains("1. Balance"));
+
+        // One session at a time, and only with its own ID
+        assert!(matches!(ussd.run("*100#", 1).await, Err(UssdError::Busy(busy)) if busy == id));
+        assert!(matches!(ussd.reply(id + 1, "1").await, Err(UssdError::UnknownSession(_))));
+        assert!(matches!(ussd.reply(id, "one").await, Err(UssdError::InvalidInput(_))));
+
+        let submenu = ussd.reply(id, "2").await.unwrap();
+        assert_eq!(submenu.session_id, Some(id));
+        let done = ussd.reply(id, "1").await.unwrap();
+        assert_eq!(done.session_id, None);
+        assert!(matches!(ussd.reply(id, "1").await, Err(UssdError::UnknownSession(_))));
+        assert!(ussd.run("*100#", 1).await.is_ok());
+    }
+}
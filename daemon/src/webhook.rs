@@ -0,0 +1,134 @@
+//! Minimal HTTP/1.1 POST for pushing events to a webhook on the tailnet.
+//! Plain http only: traffic already runs inside Tailscale and the body is signed.
+
+use std::fmt;
+use std::io;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// A parsed `http://host[:port][/path]` URL
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookUrl {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl WebhookUrl {
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("webhook URL must start with http:// (got {})", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(slash) => (&rest[..slash], &rest[slash..]),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => {
+                let port = port.parse().map_err(|_| format!("invalid port in webhook URL {}", url))?;
+                (host, port)
+            }
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("webhook URL {} has no host", url));
+        }
+        Ok(Self { host: host.to_string(), port, path: path.to_string() })
+    }
+}
+
+impl fmt::Display for WebhookUrl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "http://{}:{}{}", self.host, self.port, self.path)
+    }
+}
+
+/// POST a JSON body and return the response status code
+pub async fn post_json(
+    url: &WebhookUrl,
+    body: &[u8],
+    headers: &[(&str, String)],
+    timeout: Duration,
+) -> io::Result<u16> {
+    let exchange = async {
+        let mut stream = TcpStream::connect((url.host.as_str(), url.port)).await?;
+        let mut request = format!(
+            "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n",
+            url.path,
+            url.host,
+            body.len()
+        );
+        for (name, value) in headers {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes()).await?;
+        stream.write_all(body).await?;
+
+        // Only the status line matters
+        let mut response = Vec::new();
+        let mut chunk = [0u8; 512];
+        while !response.contains(&b'\n') {
+            let n = stream.read(&mut chunk).await?;
+            if n == 0 {
+                break;
+            }
+            response.extend_from_slice(&chunk[..n]);
+        }
+        parse_status(&response)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no HTTP status line in webhook response"))
+    };
+    tokio::time::timeout(timeout, exchange)
+        .await
+        .unwrap_or_else(|_| Err(io::Error::new(io::ErrorKind::TimedOut, "webhook did not answer in time")))
+}
+
+/// "HTTP/1.1 204 No Content" -> 204
+fn parse_status(response: &[u8]) -> Option<u16> {
+    let line = String::from_utf8_lossy(response);
+    let mut words = line.lines().next()?.split_whitespace();
+    words.next().filter(|version| version.starts_with("HTTP/"))?;
+    words.next()?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+
+    #[test]
+    fn test_parse_url() {
+        let url = WebhookUrl::parse("http://100.64.0.2:9000/hooks/sms").unwrap();
+        assert_eq!((url.host.as_str(), url.port, url.path.as_str()), ("100.64.0.2", 9000, "/hooks/sms"));
+        assert_eq!(WebhookUrl::parse("http://phone.tailnet").unwrap().to_string(), "http://phone.tailnet:80/");
+        assert!(WebhookUrl::parse("https://example.com/").is_err());
+        assert!(WebhookUrl::parse("http://host:port/").is_err());
+        assert!(WebhookUrl::parse("http:///path").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_post_json() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = WebhookUrl::parse(&format!("http://{}/sms", listener.local_addr().unwrap())).unwrap();
+        let server = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut chunk = [0u8; 1024];
+            while !String::from_utf8_lossy(&request).ends_with("{\"id\":1}") {
+                let n = socket.read(&mut chunk).await.unwrap();
+                request.extend_from_slice(&chunk[..n]);
+            }
+            socket.write_all(b"HTTP/1.1 204 No Content\r\n\r\n").await.unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        let headers = [("X-Time", "1700000000000".to_string())];
+        let status = post_json(&url, b"{\"id\":1}", &headers, Duration::from_secs(5)).await.unwrap();
+        assert_eq!(status, 204);
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /sms HTTP/1.1\r\n"));
+        assert!(request.contains("Content-Length: 8\r\n"));
+        assert!(request.contains("X-Time: 1700000000000\r\n"));
+    }
+}